@@ -0,0 +1,277 @@
+//! Attaches an interactive console to a running server.
+//!
+//! Servers supervised by the daemon are reached through its socket. Servers started with `mvm run`
+//! receive commands through their console pipe, and their output is followed in the console log.
+//! Any number of consoles can be attached to the same server, and every console of an instance
+//! shares one command history.
+
+use crate::daemon::client::DaemonConnection;
+use crate::daemon::protocol::{Request, Response};
+use crate::logs::CONSOLE_LOG;
+use crate::process;
+use anyhow::{anyhow, Context, Result};
+use rustyline::error::ReadlineError;
+use rustyline::config::Configurer;
+use rustyline::{Cmd, DefaultEditor, ExternalPrinter, KeyEvent};
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+/// The name of the command history file inside an instance.
+pub const HISTORY_FILE: &str = "mvm-history";
+
+/// How many commands are kept in the history.
+const HISTORY_SIZE: usize = 1000;
+
+/// How many lines of earlier output are shown when attaching.
+const RECENT_LINES: usize = 50;
+
+/// How often the console log of a server started with `mvm run` is checked for new output.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+/// The server console an attached console talks to.
+pub enum AttachTarget {
+    /// A server supervised by the daemon, with a connection that already sent the `Attach` request.
+    Daemon { connection: DaemonConnection, instance: PathBuf },
+    /// A server started with `mvm run`.
+    Pipe,
+}
+
+/// Follows a log file as it grows, continuing in the new file after the log was rotated.
+pub struct LogFollower {
+    path: PathBuf,
+    file: Option<File>,
+    inode: Option<u64>,
+    partial: Vec<u8>,
+}
+
+impl LogFollower {
+    /// Starts following a log file from its current end. The file does not need to exist yet.
+    ///
+    /// # Arguments
+    /// - `path`: The path of the log file
+    pub fn open(path: &Path) -> Result<Self> {
+        let mut follower = LogFollower { path: path.to_path_buf(), file: None, inode: None, partial: Vec::new() };
+        if let Ok(mut file) = File::open(path) {
+            file.seek(SeekFrom::End(0))
+                .context(format!("Failed to read log {:?}", path))?;
+            follower.inode = file.metadata().ok().map(|metadata| metadata.ino());
+            follower.file = Some(file);
+        }
+
+        Ok(follower)
+    }
+
+    /// Reads the lines written since the last call. An incomplete last line is kept back until it
+    /// is finished.
+    pub fn read_lines(&mut self) -> Result<Vec<String>> {
+        let mut data = Vec::new();
+        if let Some(file) = &mut self.file {
+            file.read_to_end(&mut data)
+                .context(format!("Failed to read log {:?}", self.path))?;
+        }
+
+        // A different file at the path means the log was rotated (or created), the rest of the
+        // old file has been read above.
+        let inode = fs::metadata(&self.path).ok().map(|metadata| metadata.ino());
+        if inode.is_some() && inode != self.inode {
+            let mut file = File::open(&self.path)
+                .context(format!("Failed to open log {:?}", self.path))?;
+            file.read_to_end(&mut data)
+                .context(format!("Failed to read log {:?}", self.path))?;
+            self.inode = inode;
+            self.file = Some(file);
+        }
+
+        self.partial.extend_from_slice(&data);
+        let Some(end) = self.partial.iter().rposition(|byte| *byte == b'\n') else {
+            return Ok(Vec::new());
+        };
+        let complete: Vec<u8> = self.partial.drain(..=end).collect();
+
+        Ok(String::from_utf8_lossy(&complete[..end])
+            .split('\n')
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect())
+    }
+}
+
+/// Returns the path of the console log of an instance.
+pub fn console_log_path(instance_dir: &Path) -> PathBuf {
+    instance_dir.join("logs").join(CONSOLE_LOG)
+}
+
+/// Returns the last lines of a file, or nothing if it cannot be read.
+///
+/// # Arguments
+/// - `path`: The path of the file
+/// - `count`: The maximum number of lines to return
+pub fn last_lines(path: &Path, count: usize) -> Vec<String> {
+    let Ok(content) = fs::read(path) else {
+        return Vec::new();
+    };
+    let content = String::from_utf8_lossy(&content);
+    let lines: Vec<&str> = content.lines().collect();
+
+    lines[lines.len().saturating_sub(count)..].iter()
+        .map(|line| line.to_string())
+        .collect()
+}
+
+/// Opens an interactive console on the server of an instance until the user detaches with
+/// Ctrl-], Ctrl-C or Ctrl-D. Detaching leaves the server running.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `target`: The console to talk to
+pub async fn attach(instance_dir: &Path, target: AttachTarget) -> Result<()> {
+    let (recent, mut follower) = match &target {
+        AttachTarget::Daemon { .. } => (Vec::new(), None),
+        AttachTarget::Pipe => {
+            let log_path = console_log_path(instance_dir);
+            (last_lines(&log_path, RECENT_LINES), Some(LogFollower::open(&log_path)?))
+        }
+    };
+
+    println!("Attached to {:?}, press Ctrl-] to detach", instance_dir);
+    for line in recent {
+        println!("{}", line);
+    }
+
+    let (sender, mut commands) = mpsc::unbounded_channel::<String>();
+    let mut printer = spawn_line_editor(instance_dir.join(HISTORY_FILE), sender)?;
+
+    // The line editor owns the terminal until the user detaches, so errors are printed from
+    // here on instead of ending the session.
+    match target {
+        AttachTarget::Daemon { mut connection, instance } => {
+            let mut connected = true;
+            loop {
+                tokio::select! {
+                    command = commands.recv() => {
+                        let Some(command) = command else {
+                            break;
+                        };
+                        if !connected {
+                            printer.print("[mvm] Not connected to the daemon".to_string());
+                            continue;
+                        }
+                        if let Err(err) = connection.send(&Request::Command { instance: instance.clone(), command }).await {
+                            printer.print(format!("Error: {:#}", err));
+                        }
+                    }
+                    response = connection.receive(), if connected => match response {
+                        Ok(Some(Response::Output { line })) => printer.print(line),
+                        Ok(Some(Response::Error { message })) => printer.print(format!("Error: {}", message)),
+                        Ok(Some(_)) => {}
+                        Ok(None) | Err(_) => {
+                            printer.print("[mvm] The daemon closed the connection, press Ctrl-] to detach".to_string());
+                            connected = false;
+                        }
+                    }
+                }
+            }
+        }
+        AttachTarget::Pipe => {
+            let mut interval = tokio::time::interval(POLL_INTERVAL);
+            loop {
+                tokio::select! {
+                    command = commands.recv() => {
+                        let Some(command) = command else {
+                            break;
+                        };
+                        if let Err(err) = process::send_to_console_pipe(instance_dir, &command) {
+                            printer.print(format!("Error: {:#}", err));
+                        }
+                    }
+                    _ = interval.tick() => if let Some(follower) = &mut follower {
+                        match follower.read_lines() {
+                            Ok(lines) => lines.into_iter().for_each(|line| printer.print(line)),
+                            Err(err) => printer.print(format!("Error: {:#}", err))
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    println!("Detached, the server keeps running");
+    Ok(())
+}
+
+/// Prints server output above the prompt of the line editor.
+struct ConsolePrinter {
+    external: Option<Box<dyn ExternalPrinter + Send>>,
+}
+
+impl ConsolePrinter {
+    fn print(&mut self, line: String) {
+        match &mut self.external {
+            Some(external) => {
+                if external.print(format!("{}\n", line)).is_err() {
+                    println!("{}", line);
+                }
+            }
+            None => println!("{}", line)
+        }
+    }
+}
+
+/// Reads commands with line editing on a background thread and sends them to a channel. The
+/// channel is closed once the user detaches.
+///
+/// # Arguments
+/// - `history_path`: The file the command history is loaded from and appended to
+/// - `sender`: The channel receiving the commands
+fn spawn_line_editor(history_path: PathBuf, sender: mpsc::UnboundedSender<String>) -> Result<ConsolePrinter> {
+    let (printer_sender, printer_receiver) = std::sync::mpsc::channel();
+
+    std::thread::spawn(move || {
+        let mut editor = match DefaultEditor::new() {
+            Ok(editor) => editor,
+            Err(err) => {
+                let _ = printer_sender.send(Err(anyhow!("Failed to open the line editor: {}", err)));
+                return;
+            }
+        };
+        let _ = editor.set_max_history_size(HISTORY_SIZE);
+        let _ = editor.load_history(&history_path);
+        editor.bind_sequence(KeyEvent::ctrl(']'), Cmd::Interrupt);
+
+        // Without a terminal there is no prompt to protect, so output is printed directly.
+        let external = editor.create_external_printer()
+            .ok()
+            .map(|printer| Box::new(printer) as Box<dyn ExternalPrinter + Send>);
+        if printer_sender.send(Ok(ConsolePrinter { external })).is_err() {
+            return;
+        }
+
+        loop {
+            match editor.readline("> ") {
+                Ok(line) => {
+                    let command = line.trim().trim_start_matches('/');
+                    if command.is_empty() {
+                        continue;
+                    }
+                    let _ = editor.add_history_entry(line.trim());
+                    // Appending keeps the commands of other consoles attached at the same time.
+                    let _ = editor.append_history(&history_path);
+                    if sender.send(command.to_string()).is_err() {
+                        break;
+                    }
+                }
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+                Err(err) => {
+                    eprintln!("Failed to read command: {}", err);
+                    break;
+                }
+            }
+        }
+    });
+
+    printer_receiver.recv()
+        .context("The line editor exited unexpectedly")?
+}
@@ -0,0 +1,182 @@
+//! A content addressed store of deduplicated chunks.
+//!
+//! Files are split into chunks at boundaries chosen by their content, so that a change in one
+//! part of a file only produces new chunks around the change. Every chunk is stored once,
+//! zstd compressed, under `<MVM_HOME>/backups/chunks/<xx>/<sha256>`, where `<xx>` are the first
+//! two hex digits of its hash, no matter how many backups refer to it. The chunks are kept in the
+//! configured [`Storage`], so the store may also be a bucket on another host.
+
+use anyhow::{anyhow, Context, Result};
+use fastcdc::v2020::StreamCDC;
+use sha2::{Digest, Sha256};
+use crate::backup::storage::{open_storage, Storage};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// The smallest chunk files are split into, except for the end of a file.
+const MIN_CHUNK_SIZE: u32 = 64 * 1024;
+
+/// The size files are split into on average.
+const AVG_CHUNK_SIZE: u32 = 256 * 1024;
+
+/// The largest chunk files are split into.
+const MAX_CHUNK_SIZE: u32 = 1024 * 1024;
+
+/// The zstd compression level used for chunks.
+const COMPRESSION_LEVEL: i32 = 3;
+
+/// The file locked while backups use the store, so that garbage collection cannot delete chunks
+/// a backup that is being written refers to. It is always local, so it only guards against
+/// backups of the same host when the store is remote.
+const LOCK_FILE: &str = ".lock";
+
+/// The prefix of the keys of chunks in the storage.
+const CHUNK_PREFIX: &str = "chunks/";
+
+/// A store of compressed chunks, addressed by the SHA-256 hash of their content.
+#[derive(Clone)]
+pub struct ChunkStore {
+    storage: Arc<dyn Storage>,
+    lock_path: PathBuf,
+}
+
+impl ChunkStore {
+    /// Returns a chunk store kept in the given storage.
+    ///
+    /// # Arguments
+    /// - `storage`: Where the chunks are stored
+    /// - `path`: The root directory of server installations, which holds the lock file
+    pub fn new(storage: Arc<dyn Storage>, path: &Path) -> Self {
+        ChunkStore { storage, lock_path: path.join("backups").join(LOCK_FILE) }
+    }
+
+    /// Opens the chunk store in the storage configured in `config.toml`.
+    ///
+    /// # Arguments
+    /// - `path`: The root directory of server installations
+    pub fn open(path: &Path) -> Result<Self> {
+        Ok(ChunkStore::new(open_storage(path)?, path))
+    }
+
+    /// Returns the storage the chunks are kept in.
+    pub fn storage(&self) -> &dyn Storage {
+        self.storage.as_ref()
+    }
+
+    /// Locks the store until the returned file is dropped.
+    ///
+    /// # Arguments
+    /// - `exclusive`: Whether to lock out every other user, which garbage collection needs.
+    ///   Shared locks only exclude exclusive ones.
+    pub fn lock(&self, exclusive: bool) -> Result<File> {
+        if let Some(parent) = self.lock_path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create {:?}", parent))?;
+        }
+        let file = File::create(&self.lock_path)
+            .context(format!("Failed to open {:?}", self.lock_path))?;
+        let locked = if exclusive { file.lock() } else { file.lock_shared() };
+        locked.context(format!("Failed to lock {:?}", self.lock_path))?;
+
+        Ok(file)
+    }
+
+    /// Returns the key a chunk is stored under.
+    fn chunk_key(id: &str) -> Result<String> {
+        // Ids come from manifests, so they must not be able to point outside the chunks.
+        if id.len() != 64 || !id.bytes().all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte)) {
+            return Err(anyhow!("Invalid chunk id {:?}", id));
+        }
+        Ok(format!("{}{}/{}", CHUNK_PREFIX, &id[..2], id))
+    }
+
+    /// Stores a chunk unless it is known to be stored already.
+    ///
+    /// # Arguments
+    /// - `data`: The content of the chunk
+    /// - `known`: The ids of the chunks in the store, from [`ChunkStore::list`], updated with the new chunk
+    ///
+    /// # Returns
+    /// A `Result` containing the id of the chunk and how many bytes were added to the store,
+    /// which is 0 if the chunk was already stored
+    pub fn put(&self, data: &[u8], known: &mut HashSet<String>) -> Result<(String, u64)> {
+        let id = chunk_id(data);
+        if known.contains(&id) {
+            return Ok((id, 0));
+        }
+
+        let compressed = zstd::encode_all(data, COMPRESSION_LEVEL)
+            .context("Failed to compress chunk")?;
+        self.storage.put(&Self::chunk_key(&id)?, &compressed)
+            .context(format!("Failed to store chunk {}", id))?;
+        known.insert(id.clone());
+
+        Ok((id, compressed.len() as u64))
+    }
+
+    /// Reads a chunk and checks that its content matches its id.
+    ///
+    /// # Arguments
+    /// - `id`: The id of the chunk
+    ///
+    /// # Returns
+    /// A `Result` containing the content of the chunk if it is stored and intact
+    pub fn get(&self, id: &str) -> Result<Vec<u8>> {
+        let compressed = self.storage.get(&Self::chunk_key(id)?)
+            .context(format!("Failed to read chunk {}", id))?
+            .ok_or_else(|| anyhow!("Chunk {} is missing", id))?;
+        let data = zstd::decode_all(compressed.as_slice())
+            .context(format!("Chunk {} is corrupt", id))?;
+        if chunk_id(&data) != id {
+            return Err(anyhow!("Chunk {} is corrupt: its content does not match its hash", id));
+        }
+
+        Ok(data)
+    }
+
+    /// Lists the stored chunks.
+    ///
+    /// # Returns
+    /// A `Result` containing the id and the stored size of every chunk
+    pub fn list(&self) -> Result<Vec<(String, u64)>> {
+        let chunks = self.storage.list(CHUNK_PREFIX)?
+            .into_iter()
+            .filter_map(|object| {
+                let id = object.key.rsplit('/').next()?.to_string();
+                Self::chunk_key(&id).ok().filter(|key| *key == object.key)?;
+                Some((id, object.size))
+            })
+            .collect();
+
+        Ok(chunks)
+    }
+
+    /// Deletes a chunk.
+    pub fn remove(&self, id: &str) -> Result<()> {
+        self.storage.delete(&Self::chunk_key(id)?)
+            .context(format!("Failed to delete chunk {}", id))
+    }
+}
+
+/// Returns the id of a chunk, the hex encoded SHA-256 hash of its content.
+pub fn chunk_id(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+/// Splits the content of a reader into content defined chunks.
+///
+/// # Arguments
+/// - `reader`: The content to split
+/// - `on_chunk`: Called with every chunk in order
+pub fn split<R: Read>(reader: R, mut on_chunk: impl FnMut(&[u8]) -> Result<()>) -> Result<()> {
+    for chunk in StreamCDC::new(reader, MIN_CHUNK_SIZE, AVG_CHUNK_SIZE, MAX_CHUNK_SIZE) {
+        let chunk = chunk.map_err(std::io::Error::from)
+            .context("Failed to read data to chunk")?;
+        on_chunk(&chunk.data)?;
+    }
+
+    Ok(())
+}
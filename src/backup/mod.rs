@@ -0,0 +1,780 @@
+//! Backs up and restores the worlds of instances.
+//!
+//! Backups are deduplicated snapshots. The files of the worlds are split into chunks that are
+//! stored once in a shared [`ChunkStore`](chunks::ChunkStore), so a backup only takes up the space
+//! of the chunks that changed since earlier backups. Every backup is described by a manifest at
+//! `snapshots/<instance>/<id>.toml` in the configured [`Storage`](storage::Storage), recording
+//! when and why it was taken, the server version of the instance and the data version of its
+//! world. The list of files and their chunks is itself stored as a chunk that the manifest refers to.
+
+pub mod chunks;
+pub mod s3;
+pub mod storage;
+
+use crate::instance::read_instance_config;
+use crate::process;
+use crate::properties::read_properties;
+use crate::server::server_types::ServerType;
+use crate::stop::ConsoleChannel;
+use crate::world;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Local};
+use chunks::ChunkStore;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, UNIX_EPOCH};
+use storage::Storage;
+
+/// The prefix of the keys of backup manifests in the storage.
+const SNAPSHOT_PREFIX: &str = "snapshots/";
+
+/// The extension of backup manifests.
+const MANIFEST_EXTENSION: &str = ".toml";
+
+/// How long a running server gets to write its world to disk after `save-all flush`.
+const SAVE_DELAY: Duration = Duration::from_secs(5);
+
+/// The directory a backup is extracted into before it replaces the worlds of an instance.
+const RESTORE_DIR: &str = ".mvm-restore";
+
+/// Describes the content of a backup.
+///
+/// # Fields
+/// - `id`: The name of the backup, derived from the time it was taken
+/// - `instance`: The name of the instance the backup was taken from
+/// - `created`: When the backup was taken
+/// - `reason`: Why the backup was taken, such as `manual`, `scheduled` or `pre-upgrade`
+/// - `server_type`: The server type of the instance at the time
+/// - `version`: The server version of the instance at the time
+/// - `data_version`: The `DataVersion` of the main world, if it could be read
+/// - `worlds`: The world directories in the backup
+/// - `size`: The total size of the files in the backup
+/// - `added`: How many bytes of new chunks the backup added to the store
+/// - `index`: The id of the chunk listing the files of the backup
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BackupManifest {
+    pub id: String,
+    pub instance: String,
+    pub created: DateTime<Local>,
+    pub reason: String,
+    pub server_type: Option<ServerType>,
+    pub version: Option<String>,
+    pub data_version: Option<i32>,
+    pub worlds: Vec<String>,
+    pub size: u64,
+    pub added: u64,
+    pub index: String,
+}
+
+/// A file in a backup.
+///
+/// # Fields
+/// - `path`: The path of the file relative to the instance, with `/` separators
+/// - `size`: The size of the file
+/// - `modified`: When the file was last modified, in nanoseconds since the Unix epoch
+/// - `chunks`: The ids of the chunks making up the file, in order
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct BackupFile {
+    pub path: String,
+    pub size: u64,
+    pub modified: i64,
+    pub chunks: Vec<String>,
+}
+
+/// A backup manifest in the storage.
+///
+/// # Fields
+/// - `key`: The key of the manifest in the storage
+/// - `manifest`: The content of the manifest
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub key: String,
+    pub manifest: BackupManifest,
+}
+
+/// Represents which backups of an instance are kept when pruning.
+///
+/// A backup is kept if any rule keeps it. The daily and weekly rules keep the newest backup of
+/// each of the most recent days or weeks that have backups.
+///
+/// # Fields
+/// - `keep_last`: How many of the newest backups are kept.
+/// - `keep_daily`: For how many days the newest backup of the day is kept.
+/// - `keep_weekly`: For how many weeks the newest backup of the week is kept.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct BackupPolicy {
+    pub keep_last: usize,
+    pub keep_daily: usize,
+    pub keep_weekly: usize,
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        BackupPolicy {
+            keep_last: 5,
+            keep_daily: 7,
+            keep_weekly: 4,
+        }
+    }
+}
+
+/// The outcome of checking backups against the chunk store.
+///
+/// # Fields
+/// - `chunks`: How many distinct chunks were checked
+/// - `problems`: A description of every missing or corrupt chunk, empty if all backups are intact
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    pub chunks: usize,
+    pub problems: Vec<String>,
+}
+
+/// The chunks garbage collection deleted, or would delete in a dry run.
+///
+/// # Fields
+/// - `chunks`: How many chunks no backup refers to
+/// - `bytes`: How much space these chunks take up
+#[derive(Debug, Clone, Default)]
+pub struct GarbageReport {
+    pub chunks: usize,
+    pub bytes: u64,
+}
+
+/// Returns the name of an instance, which is the name of its directory.
+fn instance_name(instance_dir: &Path) -> String {
+    instance_dir.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+/// Returns the prefix of the keys of the manifests of the backups of an instance.
+fn snapshot_prefix(instance_dir: &Path) -> String {
+    format!("{}{}/", SNAPSHOT_PREFIX, instance_name(instance_dir))
+}
+
+/// Returns the names of the world directories of an instance that exist on disk.
+///
+/// The main world is named by `level-name` in `server.properties`, Bukkit based servers keep
+/// the nether and the end next to it in `<level-name>_nether` and `<level-name>_the_end`.
+pub async fn world_dirs(instance_dir: &Path) -> Vec<String> {
+    let level_name = match read_properties(&instance_dir.join("server.properties")).await {
+        Ok(properties) => properties.get("level-name").unwrap_or("world").to_string(),
+        Err(_) => "world".to_string()
+    };
+
+    [level_name.clone(), format!("{}_nether", level_name), format!("{}_the_end", level_name)]
+        .into_iter()
+        .filter(|world| instance_dir.join(world).is_dir())
+        .collect()
+}
+
+/// Reads the `DataVersion` of a world from its `level.dat`.
+///
+/// # Returns
+/// The data version, or `None` if the world has no readable `level.dat`
+pub fn world_data_version(world_dir: &Path) -> Option<i32> {
+    world::read_level_info(world_dir).ok()?.data_version
+}
+
+/// Creates a backup of the worlds of an instance.
+///
+/// Files that have the same size and modification time as in the previous backup are not read
+/// again. The server must not be writing to the world while the backup is created, use
+/// [`backup_instance`] for running servers.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `reason`: Why the backup is taken, recorded in the manifest
+///
+/// # Returns
+/// A `Result` containing the new backup if successful
+pub async fn create_backup(instance_dir: &Path, path: &Path, reason: &str) -> Result<Backup> {
+    let worlds = world_dirs(instance_dir).await;
+    if worlds.is_empty() {
+        return Err(anyhow!("No world found in {:?}", instance_dir));
+    }
+    let config = read_instance_config(instance_dir).await.ok();
+
+    let manifest = BackupManifest {
+        id: String::new(),
+        instance: instance_name(instance_dir),
+        created: Local::now(),
+        reason: reason.to_string(),
+        server_type: config.as_ref().map(|config| config.server_type.clone()),
+        version: config.map(|config| config.version),
+        data_version: world_data_version(&instance_dir.join(&worlds[0])),
+        worlds,
+        size: 0,
+        added: 0,
+        index: String::new(),
+    };
+
+    let (instance_dir, path) = (instance_dir.to_path_buf(), path.to_path_buf());
+    tokio::task::spawn_blocking(move || write_backup(&instance_dir, &path, manifest))
+        .await
+        .context("Backup task failed")?
+}
+
+/// Backs up the worlds of an instance. If the server is running, automatic saving is turned off
+/// and the world is flushed to disk through its console for the duration of the backup.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `reason`: Why the backup is taken, recorded in the manifest
+/// - `console`: The console of the running server, `None` if it is stopped
+///
+/// # Returns
+/// A `Result` containing the new backup if successful
+pub async fn backup_instance(instance_dir: &Path, path: &Path, reason: &str, mut console: Option<ConsoleChannel>) -> Result<Backup> {
+    if let Some(console) = &mut console {
+        console.send("save-off").await?;
+        console.send("save-all flush").await?;
+        tokio::time::sleep(SAVE_DELAY).await;
+    }
+
+    let result = create_backup(instance_dir, path, reason).await;
+
+    if let Some(console) = &mut console {
+        console.send("save-on").await?;
+    }
+    result
+}
+
+/// Stores the files of the worlds of a backup in the chunk store and writes its manifest.
+fn write_backup(instance_dir: &Path, path: &Path, mut manifest: BackupManifest) -> Result<Backup> {
+    let store = ChunkStore::open(path)?;
+    let _lock = store.lock(false)?;
+
+    // Read under the lock, so that garbage collection cannot delete the chunks that are reused.
+    let mut known: HashSet<String> = store.list()?
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    let backups = read_backups(store.storage(), &snapshot_prefix(instance_dir))?;
+    let previous: HashMap<String, BackupFile> = backups
+        .last()
+        .and_then(|backup| read_files(&store, &backup.manifest).ok())
+        .unwrap_or_default()
+        .into_iter()
+        .map(|file| (file.path.clone(), file))
+        .collect();
+
+    let mut files = Vec::new();
+    for world in &manifest.worlds {
+        collect_files(instance_dir, Path::new(world), &mut files)?;
+    }
+
+    let mut index = Vec::new();
+    for (file_path, relative) in files {
+        let metadata = fs::metadata(&file_path)
+            .context(format!("Failed to read metadata of {:?}", file_path))?;
+        let modified = metadata.modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|modified| modified.as_nanos() as i64)
+            .unwrap_or(0);
+
+        let unchanged = previous.get(&relative)
+            .filter(|file| file.size == metadata.len() && file.modified == modified)
+            .filter(|file| file.chunks.iter().all(|id| known.contains(id)));
+        if let Some(file) = unchanged {
+            manifest.size += file.size;
+            index.push(file.clone());
+            continue;
+        }
+
+        let file = File::open(&file_path)
+            .context(format!("Failed to open {:?}", file_path))?;
+        let mut backup_file = BackupFile { path: relative, size: 0, modified, chunks: Vec::new() };
+        chunks::split(file, |data| {
+            let (id, added) = store.put(data, &mut known)?;
+            backup_file.size += data.len() as u64;
+            backup_file.chunks.push(id);
+            manifest.added += added;
+            Ok(())
+        })
+            .context(format!("Failed to back up {:?}", file_path))?;
+        manifest.size += backup_file.size;
+        index.push(backup_file);
+    }
+
+    let index = serde_json::to_vec(&index)
+        .context("Failed to serialize backup index")?;
+    let (index_id, added) = store.put(&index, &mut known)?;
+    manifest.index = index_id;
+    manifest.added += added;
+
+    let timestamp = manifest.created.format("%Y-%m-%d_%H-%M-%S").to_string();
+    manifest.id = timestamp.clone();
+    let mut counter = 1;
+    while backups.iter().any(|backup| backup.manifest.id == manifest.id) {
+        counter += 1;
+        manifest.id = format!("{}-{}", timestamp, counter);
+    }
+
+    // The manifest is written last, so that an interrupted backup never looks complete.
+    let backup = Backup { key: format!("{}{}{}", snapshot_prefix(instance_dir), manifest.id, MANIFEST_EXTENSION), manifest };
+    let content = toml::to_string_pretty(&backup.manifest)
+        .context("Failed to serialize backup manifest")?;
+    store.storage().put(&backup.key, content.as_bytes())
+        .context(format!("Failed to write backup manifest {}", backup.key))?;
+
+    Ok(backup)
+}
+
+/// Collects the regular files below a directory of an instance, sorted by path.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `relative`: The directory to collect from, relative to the instance
+/// - `files`: Receives the path of every file and its path relative to the instance
+fn collect_files(instance_dir: &Path, relative: &Path, files: &mut Vec<(PathBuf, String)>) -> Result<()> {
+    let dir = instance_dir.join(relative);
+    let mut entries = fs::read_dir(&dir)
+        .context(format!("Failed to read {:?}", dir))?
+        .collect::<std::io::Result<Vec<_>>>()
+        .context(format!("Failed to read {:?}", dir))?;
+    entries.sort_by_key(|entry| entry.file_name());
+
+    for entry in entries {
+        let file_type = entry.file_type()
+            .context(format!("Failed to read {:?}", entry.path()))?;
+        let entry_relative = relative.join(entry.file_name());
+        if file_type.is_dir() {
+            collect_files(instance_dir, &entry_relative, files)?;
+        } else if file_type.is_file() {
+            let components: Vec<String> = entry_relative.components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect();
+            files.push((entry.path(), components.join("/")));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a backup manifest.
+///
+/// # Arguments
+/// - `storage`: The storage the backup is kept in
+/// - `key`: The key of the manifest
+///
+/// # Returns
+/// A `Result` containing the manifest if successful
+pub fn read_manifest(storage: &dyn Storage, key: &str) -> Result<BackupManifest> {
+    let content = storage.get(key)
+        .context(format!("Failed to read backup manifest {}", key))?
+        .ok_or_else(|| anyhow!("Backup manifest {} does not exist", key))?;
+    toml::from_str::<BackupManifest>(&String::from_utf8_lossy(&content))
+        .context(format!("Failed to parse backup manifest {}", key))
+}
+
+/// Reads the list of files of a backup from the chunk store.
+///
+/// # Arguments
+/// - `store`: The chunk store
+/// - `manifest`: The manifest of the backup
+///
+/// # Returns
+/// A `Result` containing the files of the backup if successful
+pub fn read_files(store: &ChunkStore, manifest: &BackupManifest) -> Result<Vec<BackupFile>> {
+    let index = store.get(&manifest.index)
+        .context(format!("Failed to read the index of backup {}", manifest.id))?;
+    serde_json::from_slice::<Vec<BackupFile>>(&index)
+        .context(format!("Failed to parse the index of backup {}", manifest.id))
+}
+
+/// Reads the backup manifests whose key starts with a prefix, oldest first.
+fn read_backups(storage: &dyn Storage, prefix: &str) -> Result<Vec<Backup>> {
+    let mut list = Vec::new();
+    for object in storage.list(prefix)? {
+        if !object.key.ends_with(MANIFEST_EXTENSION) {
+            continue;
+        }
+        let manifest = read_manifest(storage, &object.key)?;
+        list.push(Backup { key: object.key, manifest });
+    }
+    list.sort_by_key(|backup| backup.manifest.created);
+
+    Ok(list)
+}
+
+/// Lists the backups of an instance, oldest first.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+pub async fn list_backups(instance_dir: &Path, path: &Path) -> Result<Vec<Backup>> {
+    let (prefix, path) = (snapshot_prefix(instance_dir), path.to_path_buf());
+    tokio::task::spawn_blocking(move || read_backups(ChunkStore::open(&path)?.storage(), &prefix))
+        .await
+        .context("Backup task failed")?
+}
+
+/// Lists the backups of all instances in the storage, oldest first. This includes instances that
+/// do not exist on this host, such as those of another host sharing a bucket.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+pub async fn list_all_backups(path: &Path) -> Result<Vec<Backup>> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || read_backups(ChunkStore::open(&path)?.storage(), SNAPSHOT_PREFIX))
+        .await
+        .context("Backup task failed")?
+}
+
+/// Finds a backup of an instance by id.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `id`: The id of the backup, or `latest` for the newest one
+pub async fn find_backup(instance_dir: &Path, path: &Path, id: &str) -> Result<Backup> {
+    let backups = list_backups(instance_dir, path)
+        .await?;
+    let backup = match id {
+        "latest" => backups.into_iter().last(),
+        _ => backups.into_iter().find(|backup| backup.manifest.id == id)
+    };
+
+    backup.ok_or_else(|| anyhow!("No backup '{}' found for {:?}", id, instance_dir))
+}
+
+/// Replaces the worlds of an instance with the worlds of a backup. The current worlds are backed
+/// up first, so that a restore can be undone.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `id`: The id of the backup, or `latest` for the newest one
+///
+/// # Returns
+/// A `Result` containing the restored backup if successful
+pub async fn restore_backup(instance_dir: &Path, path: &Path, id: &str) -> Result<Backup> {
+    if let Some(pid) = process::find_server_pid(instance_dir).await {
+        return Err(anyhow!("The server of this instance is running with pid {}, stop it before restoring", pid));
+    }
+    let backup = find_backup(instance_dir, path, id)
+        .await?;
+
+    if !world_dirs(instance_dir).await.is_empty() {
+        let previous = create_backup(instance_dir, path, "pre-restore")
+            .await?;
+        println!("Backed up the current worlds as {}", previous.manifest.id);
+    }
+
+    let (instance_dir, path) = (instance_dir.to_path_buf(), path.to_path_buf());
+    let target = backup.clone();
+    tokio::task::spawn_blocking(move || extract_worlds(&ChunkStore::open(&path)?, &instance_dir, &target))
+        .await
+        .context("Restore task failed")??;
+
+    Ok(backup)
+}
+
+/// Restores a single file of a backup, such as a region file, in place.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `id`: The id of the backup, or `latest` for the newest one
+/// - `file`: The path of the file relative to the instance, such as `world/region/r.0.0.mca`
+///
+/// # Returns
+/// A `Result` containing the backup the file was restored from if successful
+pub async fn restore_file(instance_dir: &Path, path: &Path, id: &str, file: &str) -> Result<Backup> {
+    if let Some(pid) = process::find_server_pid(instance_dir).await {
+        return Err(anyhow!("The server of this instance is running with pid {}, stop it before restoring", pid));
+    }
+    let backup = find_backup(instance_dir, path, id)
+        .await?;
+    let file = file.trim_start_matches("./").replace('\\', "/");
+
+    let (instance_dir, path) = (instance_dir.to_path_buf(), path.to_path_buf());
+    let target = backup.clone();
+    tokio::task::spawn_blocking(move || {
+        let store = ChunkStore::open(&path)?;
+        let _lock = store.lock(false)?;
+        let backup_file = read_files(&store, &target.manifest)?
+            .into_iter()
+            .find(|backup_file| backup_file.path == file)
+            .ok_or_else(|| anyhow!("Backup {} does not contain {:?}", target.manifest.id, file))?;
+        if !is_safe_path(&backup_file.path) {
+            return Err(anyhow!("Backup {} contains the invalid path {:?}", target.manifest.id, backup_file.path));
+        }
+
+        let file_path = instance_dir.join(&backup_file.path);
+        let partial_path = file_path.with_extension("mvm-restore");
+        write_file(&store, &backup_file, &partial_path)?;
+        fs::rename(&partial_path, &file_path)
+            .context(format!("Failed to move restored file to {:?}", file_path))
+    })
+        .await
+        .context("Restore task failed")??;
+
+    Ok(backup)
+}
+
+/// Reassembles a file of a backup from its chunks.
+fn write_file(store: &ChunkStore, backup_file: &BackupFile, target: &Path) -> Result<()> {
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {:?}", parent))?;
+    }
+
+    let mut file = File::create(target)
+        .context(format!("Failed to create {:?}", target))?;
+    let mut size = 0;
+    for id in &backup_file.chunks {
+        let data = store.get(id)
+            .context(format!("Failed to restore {:?}", backup_file.path))?;
+        file.write_all(&data)
+            .context(format!("Failed to write {:?}", target))?;
+        size += data.len() as u64;
+    }
+    if size != backup_file.size {
+        return Err(anyhow!("Restored {:?} has {} bytes instead of {}", backup_file.path, size, backup_file.size));
+    }
+
+    Ok(())
+}
+
+/// Checks that a path from a backup index stays inside the directory it is restored into.
+fn is_safe_path(relative: &str) -> bool {
+    Path::new(relative).components().all(|component| matches!(component, std::path::Component::Normal(_)))
+}
+
+/// Reassembles the worlds of a backup and swaps them in for the worlds of an instance.
+fn extract_worlds(store: &ChunkStore, instance_dir: &Path, backup: &Backup) -> Result<()> {
+    let _lock = store.lock(false)?;
+    let files = read_files(store, &backup.manifest)?;
+
+    let staging = instance_dir.join(RESTORE_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)
+            .context(format!("Failed to remove {:?}", staging))?;
+    }
+    for world in &backup.manifest.worlds {
+        fs::create_dir_all(staging.join(world))
+            .context(format!("Failed to create {:?}", staging.join(world)))?;
+    }
+
+    for backup_file in &files {
+        if !is_safe_path(&backup_file.path) {
+            return Err(anyhow!("Backup {} contains the invalid path {:?}", backup.manifest.id, backup_file.path));
+        }
+        write_file(store, backup_file, &staging.join(&backup_file.path))?;
+    }
+
+    for world in &backup.manifest.worlds {
+        let target = instance_dir.join(world);
+        if target.exists() {
+            fs::remove_dir_all(&target)
+                .context(format!("Failed to remove {:?}", target))?;
+        }
+        fs::rename(staging.join(world), &target)
+            .context(format!("Failed to move restored world to {:?}", target))?;
+    }
+
+    fs::remove_dir_all(&staging)
+        .context(format!("Failed to remove {:?}", staging))
+}
+
+/// Checks that every chunk the given backups refer to is stored and intact. Chunks shared by
+/// several backups are only read once.
+///
+/// # Arguments
+/// - `backups`: The backups to check
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the findings, which is an error only if the check itself failed
+pub async fn verify_backups(backups: &[Backup], path: &Path) -> Result<VerifyReport> {
+    let (backups, path) = (backups.to_vec(), path.to_path_buf());
+    tokio::task::spawn_blocking(move || {
+        let store = ChunkStore::open(&path)?;
+        let _lock = store.lock(false)?;
+        let mut report = VerifyReport::default();
+        let mut checked: HashMap<String, Option<String>> = HashMap::new();
+        let mut check = |id: &str, report: &mut VerifyReport| -> Option<String> {
+            checked.entry(id.to_string())
+                .or_insert_with(|| {
+                    report.chunks += 1;
+                    store.get(id).err().map(|err| format!("{:#}", err))
+                })
+                .clone()
+        };
+
+        for backup in &backups {
+            let id = &backup.manifest.id;
+            if let Some(problem) = check(&backup.manifest.index, &mut report) {
+                report.problems.push(format!("{}: index: {}", id, problem));
+                continue;
+            }
+            let files = match read_files(&store, &backup.manifest) {
+                Ok(files) => files,
+                Err(err) => {
+                    report.problems.push(format!("{}: {:#}", id, err));
+                    continue;
+                }
+            };
+            for backup_file in files {
+                for chunk in &backup_file.chunks {
+                    if let Some(problem) = check(chunk, &mut report) {
+                        report.problems.push(format!("{}: {}: {}", id, backup_file.path, problem));
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    })
+        .await
+        .context("Verify task failed")?
+}
+
+/// Selects the backups a retention policy does not keep.
+///
+/// # Arguments
+/// - `backups`: The backups of an instance
+/// - `policy`: The retention policy
+///
+/// # Returns
+/// The backups to delete, newest first
+pub fn backups_to_prune(backups: &[Backup], policy: &BackupPolicy) -> Vec<Backup> {
+    let mut newest_first: Vec<&Backup> = backups.iter().collect();
+    newest_first.sort_by_key(|backup| std::cmp::Reverse(backup.manifest.created));
+
+    let mut keep: HashSet<&str> = newest_first.iter()
+        .take(policy.keep_last)
+        .map(|backup| backup.manifest.id.as_str())
+        .collect();
+
+    let mut days = HashSet::new();
+    let mut weeks = HashSet::new();
+    for backup in &newest_first {
+        let created = backup.manifest.created;
+        if days.len() < policy.keep_daily && days.insert(created.date_naive()) {
+            keep.insert(&backup.manifest.id);
+        }
+        let week = created.iso_week();
+        if weeks.len() < policy.keep_weekly && weeks.insert((week.year(), week.week())) {
+            keep.insert(&backup.manifest.id);
+        }
+    }
+
+    newest_first.into_iter()
+        .filter(|backup| !keep.contains(backup.manifest.id.as_str()))
+        .cloned()
+        .collect()
+}
+
+/// Deletes the backups of an instance that a retention policy does not keep. Their chunks stay
+/// in the store until [`collect_garbage`] runs.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `policy`: The retention policy
+/// - `dry_run`: Whether to only report what would be deleted
+///
+/// # Returns
+/// A `Result` containing the deleted backups if successful
+pub async fn prune_backups(instance_dir: &Path, path: &Path, policy: &BackupPolicy, dry_run: bool) -> Result<Vec<Backup>> {
+    if policy.keep_last == 0 && policy.keep_daily == 0 && policy.keep_weekly == 0 {
+        return Err(anyhow!("The retention policy would delete every backup, keep at least one"));
+    }
+
+    let pruned = backups_to_prune(&list_backups(instance_dir, path).await?, policy);
+    if dry_run || pruned.is_empty() {
+        return Ok(pruned);
+    }
+
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let store = ChunkStore::open(&path)?;
+        for backup in &pruned {
+            store.storage().delete(&backup.key)
+                .context(format!("Failed to delete backup {}", backup.manifest.id))?;
+        }
+        Ok(pruned)
+    })
+        .await
+        .context("Backup task failed")?
+}
+
+/// Backs up the worlds of an instance, then prunes its backups according to its retention
+/// policy and deletes the chunks only the pruned backups referred to.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `reason`: Why the backup is taken, recorded in the manifest
+/// - `policy`: The retention policy of the instance
+/// - `console`: The console of the running server, `None` if it is stopped
+///
+/// # Returns
+/// A `Result` containing the new backup, the pruned backups and the deleted chunks
+pub async fn backup_and_prune(instance_dir: &Path, path: &Path, reason: &str, policy: &BackupPolicy, console: Option<ConsoleChannel>) -> Result<(Backup, Vec<Backup>, GarbageReport)> {
+    let backup = backup_instance(instance_dir, path, reason, console)
+        .await?;
+    let pruned = prune_backups(instance_dir, path, policy, false)
+        .await?;
+    let garbage = if pruned.is_empty() {
+        GarbageReport::default()
+    } else {
+        collect_garbage(path, false).await?
+    };
+    Ok((backup, pruned, garbage))
+}
+
+/// Deletes the chunks that no backup of any instance refers to anymore.
+///
+/// Waits for backups of this host that are being written to finish. When several hosts share a
+/// bucket, it must not run while another host writes a backup. If any manifest or index cannot
+/// be read, nothing is deleted, since the chunks it refers to are unknown.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+/// - `dry_run`: Whether to only report what would be deleted
+///
+/// # Returns
+/// A `Result` containing the unreferenced chunks if successful
+pub async fn collect_garbage(path: &Path, dry_run: bool) -> Result<GarbageReport> {
+    let path = path.to_path_buf();
+    tokio::task::spawn_blocking(move || {
+        let store = ChunkStore::open(&path)?;
+        let _lock = store.lock(true)?;
+
+        let mut referenced = HashSet::new();
+        for backup in read_backups(store.storage(), SNAPSHOT_PREFIX)? {
+            referenced.insert(backup.manifest.index.clone());
+            for backup_file in read_files(&store, &backup.manifest)? {
+                referenced.extend(backup_file.chunks);
+            }
+        }
+
+        let mut report = GarbageReport::default();
+        if !dry_run {
+            store.storage().clean_up()?;
+        }
+        for (id, size) in store.list()? {
+            if referenced.contains(&id) {
+                continue;
+            }
+            if !dry_run {
+                store.remove(&id)?;
+            }
+            report.chunks += 1;
+            report.bytes += size;
+        }
+
+        Ok(report)
+    })
+        .await
+        .context("Garbage collection task failed")?
+}
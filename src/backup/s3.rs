@@ -0,0 +1,409 @@
+//! Stores backups in a bucket of Amazon S3 or an S3 compatible service such as MinIO.
+//!
+//! Requests are signed with AWS Signature Version 4. Objects larger than
+//! [`MULTIPART_THRESHOLD`] are uploaded in parts.
+
+use crate::backup::storage::{Storage, StoredObject};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::blocking::{Client, Response};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Objects larger than this are uploaded with a multipart upload.
+pub const MULTIPART_THRESHOLD: usize = 16 * 1024 * 1024;
+
+/// The size of the parts of a multipart upload. S3 requires at least 5 MiB for all but the last part.
+const PART_SIZE: usize = 8 * 1024 * 1024;
+
+/// How often a request is sent before giving up on network errors and server errors.
+const ATTEMPTS: u32 = 3;
+
+/// How long a single request may take.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Represents the `[backup.storage]` settings of an S3 bucket.
+///
+/// # Fields
+/// - `bucket`: The name of the bucket
+/// - `region`: The region of the bucket, `us-east-1` by default
+/// - `endpoint`: The URL of the service, such as `http://127.0.0.1:9000` for a local MinIO.
+///   Defaults to the AWS endpoint of the region.
+/// - `prefix`: A prefix for all keys, to share a bucket between hosts
+/// - `path_style`: Whether the bucket is addressed in the path instead of the host name.
+///   Defaults to `true` when an endpoint is given, as most compatible services require it.
+/// - `access_key_id`: The access key, read from `AWS_ACCESS_KEY_ID` if not given
+/// - `secret_access_key`: The secret key, read from `AWS_SECRET_ACCESS_KEY` if not given
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct S3Config {
+    pub bucket: String,
+    #[serde(default = "default_region")]
+    pub region: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub endpoint: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path_style: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub access_key_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub secret_access_key: Option<String>,
+}
+
+fn default_region() -> String {
+    "us-east-1".to_string()
+}
+
+/// The keys requests are signed with.
+///
+/// # Fields
+/// - `access_key_id`: The public part of the key
+/// - `secret_access_key`: The secret part of the key
+/// - `session_token`: The token of temporary credentials
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+/// A bucket of an S3 compatible service.
+pub struct S3Storage {
+    client: Client,
+    config: S3Config,
+    credentials: Credentials,
+    base_url: url::Url,
+}
+
+impl S3Storage {
+    /// Connects to a bucket. No request is sent until the storage is used.
+    ///
+    /// # Arguments
+    /// - `config`: The settings of the bucket
+    ///
+    /// # Returns
+    /// A `Result` containing the storage if the settings are valid and credentials are available
+    pub fn new(config: S3Config) -> Result<Self> {
+        let credentials = Credentials {
+            access_key_id: config.access_key_id.clone()
+                .or_else(|| std::env::var("AWS_ACCESS_KEY_ID").ok())
+                .ok_or_else(|| anyhow!("No S3 access key configured, set access_key_id in [backup.storage] or AWS_ACCESS_KEY_ID"))?,
+            secret_access_key: config.secret_access_key.clone()
+                .or_else(|| std::env::var("AWS_SECRET_ACCESS_KEY").ok())
+                .ok_or_else(|| anyhow!("No S3 secret key configured, set secret_access_key in [backup.storage] or AWS_SECRET_ACCESS_KEY"))?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        };
+
+        let endpoint = config.endpoint.clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", config.region));
+        let mut base_url = url::Url::parse(&endpoint)
+            .context(format!("Invalid S3 endpoint {:?}", endpoint))?;
+        if config.path_style.unwrap_or(config.endpoint.is_some()) {
+            base_url.set_path(&format!("/{}/", uri_encode(&config.bucket, true)));
+        } else {
+            let host = base_url.host_str().ok_or_else(|| anyhow!("Invalid S3 endpoint {:?}", endpoint))?;
+            base_url.set_host(Some(&format!("{}.{}", config.bucket, host)))
+                .context(format!("Invalid S3 bucket name {:?}", config.bucket))?;
+            base_url.set_path("/");
+        }
+
+        let client = Client::builder()
+            .timeout(REQUEST_TIMEOUT)
+            .build()
+            .context("Failed to create HTTP client")?;
+
+        Ok(S3Storage { client, config, credentials, base_url })
+    }
+
+    /// Returns the full key of an object, including the configured prefix.
+    fn full_key(&self, key: &str) -> String {
+        match self.config.prefix.as_deref().map(|prefix| prefix.trim_matches('/')) {
+            Some(prefix) if !prefix.is_empty() => format!("{}/{}", prefix, key),
+            _ => key.to_string()
+        }
+    }
+
+    /// Sends a signed request, retrying on network errors and server errors.
+    ///
+    /// # Arguments
+    /// - `method`: The HTTP method
+    /// - `key`: The full key of the object, empty for requests on the bucket
+    /// - `query`: The query parameters
+    /// - `body`: The request body
+    fn send(&self, method: Method, key: &str, query: &[(&str, &str)], body: &[u8]) -> Result<Response> {
+        let mut url = self.base_url.clone();
+        url.set_path(&format!("{}{}", self.base_url.path(), uri_encode(key, false)));
+        let canonical_query = canonical_query(query);
+        url.set_query(if canonical_query.is_empty() { None } else { Some(&canonical_query) });
+
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string()
+        };
+        let payload_hash = hex::encode(Sha256::digest(body));
+
+        let mut last_error = None;
+        for attempt in 0..ATTEMPTS {
+            if attempt > 0 {
+                std::thread::sleep(Duration::from_secs(1 << attempt));
+            }
+
+            let now = Utc::now();
+            let mut headers = BTreeMap::from([
+                ("host".to_string(), host.clone()),
+                ("x-amz-content-sha256".to_string(), payload_hash.clone()),
+                ("x-amz-date".to_string(), now.format("%Y%m%dT%H%M%SZ").to_string()),
+            ]);
+            if let Some(token) = &self.credentials.session_token {
+                headers.insert("x-amz-security-token".to_string(), token.clone());
+            }
+            let authorization = authorization(&self.credentials, &self.config.region, method.as_str(), url.path(), &canonical_query, &headers, &payload_hash, &now);
+
+            let mut request = self.client.request(method.clone(), url.clone())
+                .header("authorization", authorization)
+                .body(body.to_vec());
+            for (name, value) in headers.iter().filter(|(name, _)| *name != "host") {
+                request = request.header(name, value);
+            }
+
+            match request.send() {
+                Ok(response) if response.status().is_server_error() => {
+                    last_error = Some(anyhow!("{} {} failed with status {}", method, url, response.status()));
+                }
+                Ok(response) => return Ok(response),
+                Err(err) => last_error = Some(anyhow!(err).context(format!("{} {} failed", method, url))),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| anyhow!("{} {} failed", method, url)))
+    }
+
+    /// Turns an unsuccessful response into an error describing it.
+    fn check(response: Response, action: &str) -> Result<Response> {
+        if response.status().is_success() {
+            return Ok(response);
+        }
+        let status = response.status();
+        let body = response.text().unwrap_or_default();
+        let message = xml_values(&body, "Message").into_iter().next().unwrap_or(body);
+        Err(anyhow!("Failed to {}: {} {}", action, status, message))
+    }
+
+    /// Uploads a large object in parts.
+    fn put_multipart(&self, key: &str, data: &[u8]) -> Result<()> {
+        let response = self.send(Method::POST, key, &[("uploads", "")], &[])?;
+        let body = Self::check(response, &format!("start upload of {}", key))?
+            .text()
+            .context("Failed to read S3 response")?;
+        let upload_id = xml_values(&body, "UploadId").into_iter().next()
+            .ok_or_else(|| anyhow!("S3 did not return an upload id for {}", key))?;
+
+        let result = (|| {
+            let mut parts = String::new();
+            for (index, part) in data.chunks(PART_SIZE).enumerate() {
+                let part_number = (index + 1).to_string();
+                let response = self.send(Method::PUT, key, &[("partNumber", &part_number), ("uploadId", &upload_id)], part)?;
+                let response = Self::check(response, &format!("upload part {} of {}", part_number, key))?;
+                let etag = response.headers().get("etag")
+                    .and_then(|etag| etag.to_str().ok())
+                    .ok_or_else(|| anyhow!("S3 did not return an ETag for part {} of {}", part_number, key))?;
+                parts.push_str(&format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", part_number, xml_escape(etag)));
+            }
+
+            let complete = format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", parts);
+            let response = self.send(Method::POST, key, &[("uploadId", &upload_id)], complete.as_bytes())?;
+            let response = Self::check(response, &format!("complete upload of {}", key))?;
+            // S3 may report errors of a completion in the body of a successful response.
+            let body = response.text().context("Failed to read S3 response")?;
+            if body.contains("<Error>") {
+                let message = xml_values(&body, "Message").into_iter().next().unwrap_or(body);
+                return Err(anyhow!("Failed to complete upload of {}: {}", key, message));
+            }
+            Ok(())
+        })();
+
+        if result.is_err() {
+            // Parts of an abandoned upload are billed until the upload is aborted.
+            let _ = self.send(Method::DELETE, key, &[("uploadId", &upload_id)], &[]);
+        }
+        result
+    }
+}
+
+impl Storage for S3Storage {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        let response = self.send(Method::GET, &self.full_key(key), &[], &[])?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let data = Self::check(response, &format!("download {}", key))?
+            .bytes()
+            .context(format!("Failed to download {}", key))?;
+        Ok(Some(data.to_vec()))
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        let full_key = self.full_key(key);
+        if data.len() > MULTIPART_THRESHOLD {
+            return self.put_multipart(&full_key, data);
+        }
+        let response = self.send(Method::PUT, &full_key, &[], data)?;
+        Self::check(response, &format!("upload {}", key))?;
+        Ok(())
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<StoredObject>> {
+        let full_prefix = self.full_key(prefix);
+        let strip = full_prefix.len() - prefix.len();
+        let mut objects = Vec::new();
+        let mut continuation: Option<String> = None;
+
+        loop {
+            let mut query = vec![("list-type", "2"), ("prefix", full_prefix.as_str())];
+            if let Some(token) = &continuation {
+                query.push(("continuation-token", token.as_str()));
+            }
+            let response = self.send(Method::GET, "", &query, &[])?;
+            let body = Self::check(response, &format!("list {}", prefix))?
+                .text()
+                .context("Failed to read S3 response")?;
+
+            for contents in body.split("<Contents>").skip(1) {
+                let key = xml_values(contents, "Key").into_iter().next()
+                    .ok_or_else(|| anyhow!("Invalid S3 listing: object without key"))?;
+                let size = xml_values(contents, "Size").into_iter().next()
+                    .and_then(|size| size.parse::<u64>().ok())
+                    .unwrap_or(0);
+                objects.push(StoredObject { key: key[strip..].to_string(), size });
+            }
+
+            continuation = match xml_values(&body, "IsTruncated").first().map(String::as_str) {
+                Some("true") => xml_values(&body, "NextContinuationToken").into_iter().next(),
+                _ => None
+            };
+            if continuation.is_none() {
+                return Ok(objects);
+            }
+        }
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let response = self.send(Method::DELETE, &self.full_key(key), &[], &[])?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(());
+        }
+        Self::check(response, &format!("delete {}", key))?;
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        format!("s3://{}/{}", self.config.bucket, self.full_key(""))
+    }
+}
+
+/// Percent-encodes a string as required by Signature Version 4, leaving only unreserved characters.
+///
+/// # Arguments
+/// - `value`: The string to encode
+/// - `encode_slash`: Whether to encode `/`, which must stay as it is in object keys
+pub fn uri_encode(value: &str, encode_slash: bool) -> String {
+    let mut encoded = String::new();
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => encoded.push(byte as char),
+            b'/' if !encode_slash => encoded.push('/'),
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Builds the canonical query string of a request, which is also the query string that is sent.
+fn canonical_query(query: &[(&str, &str)]) -> String {
+    let mut pairs: Vec<(String, String)> = query.iter()
+        .map(|(name, value)| (uri_encode(name, true), uri_encode(value, true)))
+        .collect();
+    pairs.sort();
+    pairs.iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data.as_bytes());
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Computes the `Authorization` header of a request with Signature Version 4.
+///
+/// # Arguments
+/// - `credentials`: The keys to sign with
+/// - `region`: The region of the bucket
+/// - `method`: The HTTP method
+/// - `path`: The URI encoded path of the request
+/// - `query`: The canonical query string
+/// - `headers`: The headers to sign with lowercase names, including `host` and `x-amz-date`
+/// - `payload_hash`: The hex encoded SHA-256 hash of the body
+/// - `time`: The time of the request, matching `x-amz-date`
+///
+/// # Returns
+/// The value of the `Authorization` header
+#[allow(clippy::too_many_arguments)]
+pub fn authorization(credentials: &Credentials, region: &str, method: &str, path: &str, query: &str, headers: &BTreeMap<String, String>, payload_hash: &str, time: &DateTime<Utc>) -> String {
+    let canonical_headers: String = headers.iter()
+        .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+        .collect();
+    let signed_headers = headers.keys().cloned().collect::<Vec<_>>().join(";");
+    let canonical_request = format!("{}\n{}\n{}\n{}\n{}\n{}", method, path, query, canonical_headers, signed_headers, payload_hash);
+
+    let date = time.format("%Y%m%d").to_string();
+    let scope = format!("{}/{}/s3/aws4_request", date, region);
+    let string_to_sign = format!("AWS4-HMAC-SHA256\n{}\n{}\n{}", time.format("%Y%m%dT%H%M%SZ"), scope, hex::encode(Sha256::digest(canonical_request.as_bytes())));
+
+    let mut key = hmac_sha256(format!("AWS4{}", credentials.secret_access_key).as_bytes(), &date);
+    for part in [region, "s3", "aws4_request"] {
+        key = hmac_sha256(&key, part);
+    }
+    let signature = hex::encode(hmac_sha256(&key, &string_to_sign));
+
+    format!("AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}", credentials.access_key_id, scope, signed_headers, signature)
+}
+
+/// Returns the text of every element with the given name in an XML document. Sufficient for the
+/// flat responses of S3, which have no attributes or nested elements of the same name.
+fn xml_values(xml: &str, tag: &str) -> Vec<String> {
+    let (open, close) = (format!("<{}>", tag), format!("</{}>", tag));
+    let mut values = Vec::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find(&open) {
+        let after = &rest[start + open.len()..];
+        let Some(end) = after.find(&close) else {
+            break;
+        };
+        values.push(xml_unescape(&after[..end]));
+        rest = &after[end + close.len()..];
+    }
+    values
+}
+
+fn xml_unescape(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
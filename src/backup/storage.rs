@@ -0,0 +1,248 @@
+//! Where backups are stored.
+//!
+//! Backups are written to a [`Storage`], a flat namespace of objects addressed by `/` separated
+//! keys. By default this is the `backups` directory of mvm, a bucket of an S3 compatible service
+//! can be configured in `<MVM_HOME>/config.toml` to keep backups off the host:
+//!
+//! ```toml
+//! [backup.storage]
+//! type = "s3"
+//! bucket = "mvm-backups"
+//! region = "eu-central-1"
+//! # Optional, for services other than AWS such as MinIO.
+//! endpoint = "http://127.0.0.1:9000"
+//! ```
+
+use crate::backup::s3::{S3Config, S3Storage};
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Distinguishes temporary files of concurrent writers within the same process.
+static WRITE_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// The extension of files that are still being written.
+const PARTIAL_EXTENSION: &str = "partial";
+
+/// An object in a storage.
+///
+/// # Fields
+/// - `key`: The key of the object
+/// - `size`: The size of the object in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredObject {
+    pub key: String,
+    pub size: u64,
+}
+
+/// A place backups are stored. The methods block, so they must not be called on an async runtime
+/// thread.
+pub trait Storage: Send + Sync {
+    /// Reads an object, returning `None` if it does not exist.
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>>;
+
+    /// Writes an object, replacing it if it exists. Readers never see a partially written object.
+    fn put(&self, key: &str, data: &[u8]) -> Result<()>;
+
+    /// Lists the objects whose key starts with the given prefix.
+    fn list(&self, prefix: &str) -> Result<Vec<StoredObject>>;
+
+    /// Deletes an object. Deleting an object that does not exist is not an error.
+    fn delete(&self, key: &str) -> Result<()>;
+
+    /// Removes leftovers of writes that were interrupted. Only called while no backup is written.
+    fn clean_up(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Describes where the objects are stored, for messages.
+    fn location(&self) -> String;
+}
+
+/// Which storage backups are written to.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum StorageConfig {
+    /// A directory on this host, `<MVM_HOME>/backups` unless `path` is given.
+    Local {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        path: Option<PathBuf>
+    },
+    /// A bucket of Amazon S3 or a compatible service.
+    S3(S3Config),
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        StorageConfig::Local { path: None }
+    }
+}
+
+/// The `[backup]` table of `config.toml`.
+#[derive(Debug, Default, Deserialize)]
+struct BackupConfig {
+    #[serde(default)]
+    storage: StorageConfig,
+}
+
+/// The parts of `config.toml` that concern backups.
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    backup: BackupConfig,
+}
+
+/// Reads the storage configuration from `config.toml`.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the configured storage, the local default if none is configured
+pub fn read_storage_config(path: &Path) -> Result<StorageConfig> {
+    let config_path = path.join("config.toml");
+    if !config_path.exists() {
+        return Ok(StorageConfig::default());
+    }
+
+    let content = fs::read_to_string(&config_path)
+        .context(format!("Failed to read {:?}", config_path))?;
+    let config = toml::from_str::<ConfigFile>(&content)
+        .context(format!("Failed to parse the [backup] settings in {:?}", config_path))?;
+    Ok(config.backup.storage)
+}
+
+/// Opens the storage configured in `config.toml`.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the storage if it is configured correctly
+pub fn open_storage(path: &Path) -> Result<Arc<dyn Storage>> {
+    let storage: Arc<dyn Storage> = match read_storage_config(path)? {
+        StorageConfig::Local { path: None } => Arc::new(LocalStorage::new(&path.join("backups"))),
+        StorageConfig::Local { path: Some(root) } => Arc::new(LocalStorage::new(&root)),
+        StorageConfig::S3(config) => Arc::new(S3Storage::new(config)?),
+    };
+    Ok(storage)
+}
+
+/// Checks that a key is a relative path without `.` or `..` components.
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() || key.split('/').any(|part| part.is_empty() || part == "." || part == "..") {
+        return Err(anyhow!("Invalid storage key {:?}", key));
+    }
+    Ok(())
+}
+
+/// Stores objects as files below a directory.
+#[derive(Debug, Clone)]
+pub struct LocalStorage {
+    root: PathBuf,
+}
+
+impl LocalStorage {
+    /// Returns a storage that keeps objects below the given directory.
+    pub fn new(root: &Path) -> Self {
+        LocalStorage { root: root.to_path_buf() }
+    }
+
+    /// Collects the files below a directory whose key starts with a prefix.
+    fn collect(&self, dir: &Path, key: &str, prefix: &str, objects: &mut Vec<StoredObject>) -> Result<()> {
+        for entry in fs::read_dir(dir).context(format!("Failed to read {:?}", dir))? {
+            let entry = entry.context(format!("Failed to read {:?}", dir))?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            let entry_key = if key.is_empty() { name.clone() } else { format!("{}/{}", key, name) };
+            // Only descend into directories that can contain keys with the prefix.
+            if !entry_key.starts_with(prefix) && !prefix.starts_with(&format!("{}/", entry_key)) {
+                continue;
+            }
+
+            let file_type = entry.file_type()
+                .context(format!("Failed to read {:?}", entry.path()))?;
+            if file_type.is_dir() {
+                self.collect(&entry.path(), &entry_key, prefix, objects)?;
+            } else if file_type.is_file() && !name.starts_with('.') && !name.ends_with(&format!(".{}", PARTIAL_EXTENSION)) {
+                let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+                objects.push(StoredObject { key: entry_key, size });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Deletes the temporary files below a directory.
+    fn remove_partial_files(dir: &Path) -> Result<()> {
+        for entry in fs::read_dir(dir).context(format!("Failed to read {:?}", dir))? {
+            let entry_path = entry.context(format!("Failed to read {:?}", dir))?.path();
+            if entry_path.is_dir() {
+                Self::remove_partial_files(&entry_path)?;
+            } else if entry_path.extension().is_some_and(|extension| extension == PARTIAL_EXTENSION) {
+                fs::remove_file(&entry_path)
+                    .context(format!("Failed to delete {:?}", entry_path))?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Storage for LocalStorage {
+    fn get(&self, key: &str) -> Result<Option<Vec<u8>>> {
+        validate_key(key)?;
+        let file_path = self.root.join(key);
+        match fs::read(&file_path) {
+            Ok(data) => Ok(Some(data)),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(err) => Err(err).context(format!("Failed to read {:?}", file_path))
+        }
+    }
+
+    fn put(&self, key: &str, data: &[u8]) -> Result<()> {
+        validate_key(key)?;
+        let file_path = self.root.join(key);
+        let parent = file_path.parent().unwrap_or(&self.root);
+        fs::create_dir_all(parent)
+            .context(format!("Failed to create {:?}", parent))?;
+
+        // Written under a temporary name and renamed, so that the file is never seen half written.
+        let file_name = file_path.file_name().map(|name| name.to_string_lossy().to_string()).unwrap_or_default();
+        let partial_path = parent.join(format!("{}.{}-{}.{}", file_name, std::process::id(), WRITE_COUNTER.fetch_add(1, Ordering::Relaxed), PARTIAL_EXTENSION));
+        fs::write(&partial_path, data)
+            .context(format!("Failed to write {:?}", partial_path))?;
+        fs::rename(&partial_path, &file_path)
+            .context(format!("Failed to move {:?} to {:?}", partial_path, file_path))
+    }
+
+    fn list(&self, prefix: &str) -> Result<Vec<StoredObject>> {
+        let mut objects = Vec::new();
+        if self.root.is_dir() {
+            self.collect(&self.root, "", prefix, &mut objects)?;
+        }
+        Ok(objects)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        validate_key(key)?;
+        let file_path = self.root.join(key);
+        match fs::remove_file(&file_path) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err).context(format!("Failed to delete {:?}", file_path)),
+            _ => Ok(())
+        }
+    }
+
+    fn clean_up(&self) -> Result<()> {
+        if self.root.is_dir() {
+            Self::remove_partial_files(&self.root)?;
+        }
+        Ok(())
+    }
+
+    fn location(&self) -> String {
+        self.root.display().to_string()
+    }
+}
@@ -0,0 +1,75 @@
+//! Connects the CLI to a running daemon.
+
+use crate::daemon::protocol::{Request, Response};
+use crate::daemon::socket_path;
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::unix::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::UnixStream;
+
+/// A connection to the daemon socket.
+pub struct DaemonConnection {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl DaemonConnection {
+    /// Connects to the daemon of the given mvm directory.
+    ///
+    /// # Arguments
+    /// - `path`: The root directory of server installations
+    pub async fn connect(path: &Path) -> Result<Self> {
+        let socket = socket_path(path);
+        let stream = UnixStream::connect(&socket)
+            .await
+            .context(format!("The mvm daemon is not running, socket: {:?}", socket))?;
+        let (reader, writer) = stream.into_split();
+
+        Ok(DaemonConnection { lines: BufReader::new(reader).lines(), writer })
+    }
+
+    /// Sends a request to the daemon.
+    pub async fn send(&mut self, request: &Request) -> Result<()> {
+        let mut line = serde_json::to_string(request).context("Failed to serialize request")?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())
+            .await
+            .context("Failed to send request to the daemon")
+    }
+
+    /// Receives the next response, or `None` once the daemon closed the connection.
+    pub async fn receive(&mut self) -> Result<Option<Response>> {
+        match self.lines.next_line().await.context("Failed to read response from the daemon")? {
+            Some(line) => serde_json::from_str::<Response>(&line)
+                .map(Some)
+                .context("Failed to parse response from the daemon"),
+            None => Ok(None)
+        }
+    }
+}
+
+/// Returns whether a daemon is listening for the given mvm directory.
+pub async fn is_daemon_running(path: &Path) -> bool {
+    UnixStream::connect(socket_path(path)).await.is_ok()
+}
+
+/// Sends a single request to the daemon and waits for its response.
+/// Error responses are turned into errors.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+/// - `request`: The request to send
+///
+/// # Returns
+/// A `Result` containing the response if successful
+pub async fn request(path: &Path, request: &Request) -> Result<Response> {
+    let mut connection = DaemonConnection::connect(path).await?;
+    connection.send(request).await?;
+
+    match connection.receive().await? {
+        Some(Response::Error { message }) => Err(anyhow!(message)),
+        Some(response) => Ok(response),
+        None => Err(anyhow!("The daemon closed the connection without a response"))
+    }
+}
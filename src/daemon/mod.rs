@@ -0,0 +1,23 @@
+//! Supervises the servers of instances in a long running daemon.
+//!
+//! `mvm daemon` starts the configured instances with their pinned jars, captures their output into
+//! rotating logs, restarts them after crashes and runs their scheduled tasks. The CLI talks to
+//! it over a Unix socket at `<MVM_HOME>/mvm.sock` using newline-delimited JSON messages.
+
+pub mod client;
+pub mod protocol;
+pub mod scheduler;
+pub mod supervisor;
+
+use std::path::{Path, PathBuf};
+
+/// The name of the daemon's socket inside the mvm directory.
+pub const SOCKET_FILE: &str = "mvm.sock";
+
+/// Returns the path of the daemon's socket.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+pub fn socket_path(path: &Path) -> PathBuf {
+    path.join(SOCKET_FILE)
+}
@@ -0,0 +1,79 @@
+//! Messages exchanged between the CLI and the daemon.
+//!
+//! Every message is a single line of JSON. A connection starts with one `Request` from the CLI,
+//! which the daemon answers with one `Response`. After an `Attach` request the daemon keeps
+//! streaming `Output` responses and accepts further `Command` requests on the same connection.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// A request sent by the CLI. Instances are identified by their canonical directory.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Start { instance: PathBuf },
+    Stop { instance: PathBuf, countdown: u64, message: String, timeout_secs: u64 },
+    Restart { instance: PathBuf, countdown: u64, message: String, timeout_secs: u64 },
+    Status { instance: Option<PathBuf> },
+    Command { instance: PathBuf, command: String },
+    Attach { instance: PathBuf },
+    Backup { instance: PathBuf },
+}
+
+/// A response sent by the daemon.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Ok { message: String },
+    Error { message: String },
+    Status { instances: Vec<InstanceStatus> },
+    Output { line: String },
+}
+
+/// The supervision state of an instance.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum InstanceState {
+    Stopped,
+    Running,
+    Stopping,
+    Backoff,
+    CrashLoop,
+    /// The daemon does not know the instance, it was never started through the daemon.
+    NotSupervised,
+}
+
+/// Converts an `InstanceState` into a displayable string.
+impl Display for InstanceState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            InstanceState::Stopped => "stopped",
+            InstanceState::Running => "running",
+            InstanceState::Stopping => "stopping",
+            InstanceState::Backoff => "waiting to restart",
+            InstanceState::CrashLoop => "crash loop, not restarting",
+            InstanceState::NotSupervised => "not supervised",
+        };
+        write!(f, "{}", str)
+    }
+}
+
+/// Represents the supervision status of an instance.
+///
+/// # Fields
+/// - `instance`: The directory of the instance.
+/// - `state`: The supervision state.
+/// - `pid`: The process id of the running server.
+/// - `restarts`: How many times the server was restarted after a crash.
+/// - `uptime_secs`: How long the current process has been running.
+/// - `last_exit`: The exit status of the previous process.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InstanceStatus {
+    pub instance: PathBuf,
+    pub state: InstanceState,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    pub uptime_secs: Option<u64>,
+    pub last_exit: Option<String>,
+}
@@ -0,0 +1,113 @@
+//! Runs the scheduled tasks of instances inside the daemon.
+//!
+//! The schedules are read from the `instance.toml` files every minute, so changes apply without
+//! restarting the daemon.
+
+use crate::backup;
+use crate::daemon::protocol::InstanceState;
+use crate::daemon::supervisor::Supervisor;
+use crate::instance::{list_instances, InstanceConfig};
+use crate::schedule::{ScheduledTask, TaskAction};
+use crate::stop::{open_console_channel, ConsoleChannel, StopOptions};
+use crate::version_manager::resolve_version;
+use anyhow::{anyhow, Result};
+use chrono::{Local, Timelike};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// How long a scheduled restart waits for the server to exit before sending signals.
+const RESTART_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Runs the scheduled tasks of all instances until the daemon exits.
+///
+/// # Arguments
+/// - `supervisor`: The supervisor of the daemon
+/// - `path`: The root directory of server installations
+pub async fn run_scheduler(supervisor: Arc<Supervisor>, path: PathBuf) {
+    loop {
+        let now = Local::now();
+        let elapsed = Duration::new(now.second() as u64, now.nanosecond() % 1_000_000_000);
+        tokio::time::sleep(Duration::from_secs(60).saturating_sub(elapsed)).await;
+
+        // Rounded so that waking up a moment early still counts as the new minute.
+        let minute = (Local::now() + chrono::Duration::seconds(1)).naive_local();
+        for (instance_dir, config) in list_instances(&path).await {
+            for task in config.schedule.iter().filter(|task| task.cron.matches(&minute)) {
+                let supervisor = Arc::clone(&supervisor);
+                let (instance_dir, config, task, path) = (instance_dir.clone(), config.clone(), task.clone(), path.clone());
+                tokio::spawn(async move {
+                    run_task(&supervisor, &instance_dir, &config, &task, &path).await;
+                });
+            }
+        }
+    }
+}
+
+/// Runs a single scheduled task and logs its outcome to the console log of the instance.
+///
+/// # Arguments
+/// - `supervisor`: The supervisor of the daemon
+/// - `instance_dir`: The canonical directory of the instance
+/// - `config`: The configuration of the instance
+/// - `task`: The task to run
+/// - `path`: The root directory of server installations
+pub async fn run_task(supervisor: &Arc<Supervisor>, instance_dir: &Path, config: &InstanceConfig, task: &ScheduledTask, path: &Path) {
+    supervisor.log_line(instance_dir, &format!("[mvm] Running scheduled task '{}'", task.label()));
+
+    let result = match &task.action {
+        TaskAction::Restart { countdown, message } => {
+            let options = StopOptions { countdown: *countdown, message: message.clone(), timeout: RESTART_TIMEOUT };
+            scheduled_restart(supervisor, instance_dir, &options).await
+        }
+        TaskAction::Backup => scheduled_backup(supervisor, instance_dir, config, path).await,
+        TaskAction::Command { command } => match console(supervisor, instance_dir).await {
+            Some(mut console) => console.send(command).await.map(|_| "Command sent".to_string()),
+            None => Err(anyhow!("The server is not running"))
+        },
+        TaskAction::UpdateCheck => update_check(config, path).await,
+    };
+
+    match result {
+        Ok(message) => supervisor.log_line(instance_dir, &format!("[mvm] Scheduled task '{}': {}", task.label(), message)),
+        Err(err) => supervisor.log_line(instance_dir, &format!("[mvm] Scheduled task '{}' failed: {:#}", task.label(), err)),
+    }
+}
+
+/// Returns the console of a running server, whether it runs under the daemon or on its own.
+async fn console(supervisor: &Supervisor, instance_dir: &Path) -> Option<ConsoleChannel> {
+    match supervisor.console(instance_dir) {
+        Some(console) => Some(console),
+        None => open_console_channel(instance_dir).await
+    }
+}
+
+/// Restarts a server running under the daemon. Stopped servers are left alone.
+async fn scheduled_restart(supervisor: &Arc<Supervisor>, instance_dir: &Path, options: &StopOptions) -> Result<String> {
+    match supervisor.state(instance_dir) {
+        Some(InstanceState::Running) => supervisor.restart(instance_dir, options).await,
+        Some(_) => Err(anyhow!("The server is not running, skipping the restart")),
+        None => Err(anyhow!("The server is not supervised by the daemon, start it with `mvm start` to allow restarts"))
+    }
+}
+
+/// Runs a backup task, leaving the backup and retention to the backup module.
+async fn scheduled_backup(supervisor: &Supervisor, instance_dir: &Path, config: &InstanceConfig, path: &Path) -> Result<String> {
+    let (backup, pruned, garbage) = backup::backup_and_prune(instance_dir, path, "scheduled", &config.backup, console(supervisor, instance_dir).await)
+        .await?;
+    if pruned.is_empty() {
+        return Ok(format!("Created backup {}", backup.manifest.id));
+    }
+    Ok(format!("Created backup {}, pruned {} old backups and freed {} bytes", backup.manifest.id, pruned.len(), garbage.bytes))
+}
+
+/// Compares the version of an instance to the latest available one.
+async fn update_check(config: &InstanceConfig, path: &Path) -> Result<String> {
+    let latest = resolve_version("latest", &config.server_type, path)
+        .await?;
+    if latest == config.version {
+        Ok(format!("{} {} is up to date", config.server_type, config.version))
+    } else {
+        Ok(format!("Update available: {} {} (instance runs {})", config.server_type, latest, config.version))
+    }
+}
@@ -0,0 +1,620 @@
+//! Starts, monitors and restarts the server processes of instances, and serves the daemon socket.
+
+use crate::backup;
+use crate::logs::{RotatingLog, CONSOLE_LOG, DEFAULT_MAX_FILES, DEFAULT_MAX_SIZE};
+use crate::daemon::protocol::{InstanceState, InstanceStatus, Request, Response};
+use crate::daemon::{scheduler, socket_path};
+use crate::instance::{self, check_eula, read_instance_config, server_command};
+use crate::process;
+use crate::stop::{stop_process, ConsoleChannel, StopOptions};
+use anyhow::{anyhow, Context, Result};
+use std::collections::{HashMap, VecDeque};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::signal::unix::{signal, SignalKind};
+use tokio::sync::{broadcast, mpsc, Notify};
+
+/// How many recent console lines are replayed to a newly attached console.
+const RECENT_LINES: usize = 200;
+
+/// How long servers get to exit when the daemon shuts down.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The supervision state of a single instance.
+struct ManagedInstance {
+    state: InstanceState,
+    desired_running: bool,
+    pid: Option<u32>,
+    started: Option<Instant>,
+    restarts: u32,
+    last_exit: Option<String>,
+    stdin: Option<mpsc::UnboundedSender<String>>,
+    output: broadcast::Sender<String>,
+    recent: VecDeque<String>,
+    wake: Arc<Notify>,
+}
+
+impl ManagedInstance {
+    fn new() -> Self {
+        ManagedInstance {
+            state: InstanceState::Stopped,
+            desired_running: false,
+            pid: None,
+            started: None,
+            restarts: 0,
+            last_exit: None,
+            stdin: None,
+            output: broadcast::channel(1024).0,
+            recent: VecDeque::with_capacity(RECENT_LINES),
+            wake: Arc::new(Notify::new()),
+        }
+    }
+}
+
+/// Supervises the servers of all instances started through the daemon.
+pub struct Supervisor {
+    path: PathBuf,
+    instances: Mutex<HashMap<PathBuf, ManagedInstance>>,
+}
+
+impl Supervisor {
+    /// Creates a supervisor for the given mvm directory.
+    ///
+    /// # Arguments
+    /// - `path`: The root directory of server installations
+    pub fn new(path: &Path) -> Arc<Self> {
+        Arc::new(Supervisor {
+            path: path.to_path_buf(),
+            instances: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Runs a closure with the state of an instance, creating the state if needed.
+    fn with_instance<T>(&self, instance_dir: &Path, f: impl FnOnce(&mut ManagedInstance) -> T) -> T {
+        let mut instances = self.instances.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(instances.entry(instance_dir.to_path_buf()).or_insert_with(ManagedInstance::new))
+    }
+
+    /// Starts the server of an instance and keeps it running until it is stopped.
+    ///
+    /// # Arguments
+    /// - `instance_dir`: The canonical directory of the instance
+    pub async fn start(self: &Arc<Self>, instance_dir: &Path) -> Result<String> {
+        let config = read_instance_config(instance_dir)
+            .await?;
+        check_eula(instance_dir, &config.server_type)
+            .await?;
+
+        let already_running = self.with_instance(instance_dir, |managed| {
+            if managed.desired_running || managed.state == InstanceState::Stopping {
+                return true;
+            }
+            managed.desired_running = true;
+            managed.restarts = 0;
+            managed.wake = Arc::new(Notify::new());
+            false
+        });
+        if already_running {
+            return Err(anyhow!("Instance {:?} is already running", instance_dir));
+        }
+
+        if let Some(pid) = process::find_server_pid(instance_dir).await {
+            self.with_instance(instance_dir, |managed| managed.desired_running = false);
+            return Err(anyhow!("A server is already running in {:?} outside of the daemon with pid {}", instance_dir, pid));
+        }
+
+        let supervisor = Arc::clone(self);
+        let dir = instance_dir.to_path_buf();
+        tokio::spawn(async move {
+            supervisor.supervise(&dir).await;
+        });
+
+        Ok(format!("Started {:?}", instance_dir))
+    }
+
+    /// Runs the server of an instance, restarting it with a growing delay after crashes until it
+    /// is stopped or crashes too often.
+    async fn supervise(&self, instance_dir: &Path) {
+        let mut crashes: VecDeque<Instant> = VecDeque::new();
+
+        loop {
+            let policy = match read_instance_config(instance_dir).await {
+                Ok(config) => config.restart,
+                Err(err) => {
+                    self.record_exit(instance_dir, format!("failed to read instance config: {}", err));
+                    self.with_instance(instance_dir, |managed| {
+                        managed.state = InstanceState::Stopped;
+                        managed.desired_running = false;
+                    });
+                    return;
+                }
+            };
+
+            let (exit, clean) = match self.run_once(instance_dir).await {
+                Ok(status) => (status.to_string(), status.success()),
+                Err(err) => (format!("failed to start: {:#}", err), false)
+            };
+            self.record_exit(instance_dir, exit.clone());
+
+            // A clean exit means the server was stopped from its own console, which is not a crash.
+            if clean {
+                self.with_instance(instance_dir, |managed| managed.desired_running = false);
+            }
+
+            let (desired_running, wake) = self.with_instance(instance_dir, |managed| {
+                (managed.desired_running, Arc::clone(&managed.wake))
+            });
+            if !desired_running {
+                self.with_instance(instance_dir, |managed| managed.state = InstanceState::Stopped);
+                return;
+            }
+
+            let now = Instant::now();
+            crashes.push_back(now);
+            while crashes.front().is_some_and(|crash| now.duration_since(*crash).as_secs() > policy.window_secs) {
+                crashes.pop_front();
+            }
+
+            if !policy.enabled || crashes.len() as u32 > policy.max_restarts {
+                self.log_line(instance_dir, &format!("[mvm] Server crashed ({}), not restarting", exit));
+                self.with_instance(instance_dir, |managed| {
+                    managed.state = InstanceState::CrashLoop;
+                    managed.desired_running = false;
+                });
+                return;
+            }
+
+            let exponent = (crashes.len() as u32).saturating_sub(1).min(16);
+            let backoff = policy.backoff_secs.saturating_mul(1 << exponent).min(policy.max_backoff_secs);
+            self.log_line(instance_dir, &format!("[mvm] Server crashed ({}), restarting in {} seconds", exit, backoff));
+            self.with_instance(instance_dir, |managed| managed.state = InstanceState::Backoff);
+
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_secs(backoff)) => {}
+                _ = wake.notified() => {}
+            }
+
+            let desired_running = self.with_instance(instance_dir, |managed| {
+                if managed.desired_running {
+                    managed.restarts += 1;
+                }
+                managed.desired_running
+            });
+            if !desired_running {
+                self.with_instance(instance_dir, |managed| managed.state = InstanceState::Stopped);
+                return;
+            }
+        }
+    }
+
+    /// Starts the server process once and waits for it to exit.
+    async fn run_once(&self, instance_dir: &Path) -> Result<std::process::ExitStatus> {
+        // Opened before the spawn, so that failing to open it cannot leave a running server and its pid file behind.
+        let mut log = RotatingLog::open(&instance_dir.join("logs").join(CONSOLE_LOG), DEFAULT_MAX_SIZE, DEFAULT_MAX_FILES)?;
+        let mut child = server_command(instance_dir, &self.path)
+            .await?
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn()
+            .context("Failed to start the server, is Java installed?")?;
+
+        let pid = child.id();
+        if let Some(pid) = pid {
+            if let Err(err) = process::write_pid_file(instance_dir, pid).await {
+                let _ = child.kill().await;
+                return Err(err);
+            }
+        }
+
+        let (line_sender, mut line_receiver) = mpsc::unbounded_channel::<String>();
+        if let Some(stdout) = child.stdout.take() {
+            forward_output(stdout, line_sender.clone());
+        }
+        if let Some(stderr) = child.stderr.take() {
+            forward_output(stderr, line_sender);
+        }
+
+        let (stdin_sender, mut stdin_receiver) = mpsc::unbounded_channel::<String>();
+        let Some(mut stdin) = child.stdin.take() else {
+            let _ = child.kill().await;
+            process::remove_pid_file(instance_dir)
+                .await?;
+            return Err(anyhow!("Failed to open server stdin"));
+        };
+        tokio::spawn(async move {
+            while let Some(command) = stdin_receiver.recv().await {
+                if stdin.write_all(format!("{}\n", command).as_bytes()).await.is_err() {
+                    break;
+                }
+                let _ = stdin.flush().await;
+            }
+        });
+
+        self.with_instance(instance_dir, |managed| {
+            managed.state = InstanceState::Running;
+            managed.pid = pid;
+            managed.started = Some(Instant::now());
+            managed.stdin = Some(stdin_sender);
+        });
+
+        let mut exit_status = None;
+        loop {
+            tokio::select! {
+                line = line_receiver.recv() => match line {
+                    Some(line) => {
+                        if let Err(err) = log.write_line(&line) {
+                            eprintln!("{:?}: {:#}", instance_dir, err);
+                        }
+                        self.publish(instance_dir, line);
+                    }
+                    None => break
+                },
+                status = child.wait(), if exit_status.is_none() => {
+                    exit_status = Some(status.context("Failed to wait for the server process")?);
+                }
+            }
+        }
+        let exit_status = match exit_status {
+            Some(status) => status,
+            None => child.wait().await.context("Failed to wait for the server process")?
+        };
+
+        self.with_instance(instance_dir, |managed| {
+            managed.pid = None;
+            managed.started = None;
+            managed.stdin = None;
+        });
+        process::remove_pid_file(instance_dir)
+            .await?;
+
+        Ok(exit_status)
+    }
+
+    /// Records the exit of a server process.
+    fn record_exit(&self, instance_dir: &Path, exit: String) {
+        self.log_line(instance_dir, &format!("[mvm] Server exited: {}", exit));
+        self.with_instance(instance_dir, |managed| managed.last_exit = Some(exit));
+    }
+
+    /// Writes a daemon message to the console log and attached consoles.
+    pub fn log_line(&self, instance_dir: &Path, line: &str) {
+        let log_path = instance_dir.join("logs").join(CONSOLE_LOG);
+        if let Err(err) = RotatingLog::open(&log_path, DEFAULT_MAX_SIZE, DEFAULT_MAX_FILES).and_then(|mut log| log.write_line(line)) {
+            eprintln!("{:?}: {:#}", instance_dir, err);
+        }
+        println!("{:?}: {}", instance_dir, line);
+        self.publish(instance_dir, line.to_string());
+    }
+
+    /// Sends a console line to attached consoles and remembers it for later attaches.
+    /// Lines of instances the daemon does not supervise are dropped.
+    fn publish(&self, instance_dir: &Path, line: String) {
+        let mut instances = self.instances.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(managed) = instances.get_mut(instance_dir) {
+            if managed.recent.len() == RECENT_LINES {
+                managed.recent.pop_front();
+            }
+            managed.recent.push_back(line.clone());
+            let _ = managed.output.send(line);
+        }
+    }
+
+    /// Stops the server of an instance gracefully and keeps it from being restarted.
+    ///
+    /// # Arguments
+    /// - `instance_dir`: The canonical directory of the instance
+    /// - `options`: The countdown and timeout to use
+    pub async fn stop(&self, instance_dir: &Path, options: &StopOptions) -> Result<String> {
+        let (state, pid, stdin) = self.with_instance(instance_dir, |managed| {
+            managed.desired_running = false;
+            managed.wake.notify_one();
+            let state = managed.state;
+            if state == InstanceState::Running {
+                managed.state = InstanceState::Stopping;
+            }
+            (state, managed.pid, managed.stdin.clone())
+        });
+
+        match state {
+            InstanceState::Running => {
+                let result = stop_process(stdin.map(ConsoleChannel::Stdin), pid, options).await;
+                self.wait_until_stopped(instance_dir).await;
+                result.map(|_| format!("Stopped {:?}", instance_dir))
+            }
+            InstanceState::Stopping => Err(anyhow!("Instance {:?} is already stopping", instance_dir)),
+            InstanceState::Backoff | InstanceState::CrashLoop => {
+                self.wait_until_stopped(instance_dir).await;
+                Ok(format!("Stopped {:?}", instance_dir))
+            }
+            InstanceState::Stopped | InstanceState::NotSupervised => Err(anyhow!("Instance {:?} is not running", instance_dir))
+        }
+    }
+
+    /// Waits until the supervision loop of an instance has noticed that it should stop.
+    async fn wait_until_stopped(&self, instance_dir: &Path) {
+        for _ in 0..100 {
+            let state = self.with_instance(instance_dir, |managed| {
+                if managed.state == InstanceState::CrashLoop {
+                    managed.state = InstanceState::Stopped;
+                }
+                managed.state
+            });
+            if state == InstanceState::Stopped {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
+        }
+    }
+
+    /// Stops the server of an instance if it is running and starts it again.
+    ///
+    /// # Arguments
+    /// - `instance_dir`: The canonical directory of the instance
+    /// - `options`: The countdown and timeout to use for the stop
+    pub async fn restart(self: &Arc<Self>, instance_dir: &Path, options: &StopOptions) -> Result<String> {
+        let state = self.with_instance(instance_dir, |managed| managed.state);
+        if state != InstanceState::Stopped {
+            self.stop(instance_dir, options).await?;
+        }
+        self.start(instance_dir)
+            .await
+            .map(|_| format!("Restarted {:?}", instance_dir))
+    }
+
+    /// Returns the console of an instance if its server is running under the daemon.
+    pub fn console(&self, instance_dir: &Path) -> Option<ConsoleChannel> {
+        let instances = self.instances.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        instances.get(instance_dir)
+            .and_then(|managed| managed.stdin.clone())
+            .map(ConsoleChannel::Stdin)
+    }
+
+    /// Returns the supervision state of an instance, or `None` if the daemon does not know it.
+    pub fn state(&self, instance_dir: &Path) -> Option<InstanceState> {
+        let instances = self.instances.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        instances.get(instance_dir).map(|managed| managed.state)
+    }
+
+    /// Sends a command to the console of a running instance.
+    pub fn command(&self, instance_dir: &Path, command: &str) -> Result<()> {
+        let instances = self.instances.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let stdin = instances.get(instance_dir)
+            .and_then(|managed| managed.stdin.clone())
+            .ok_or_else(|| anyhow!("Instance {:?} is not running", instance_dir))?;
+        stdin.send(command.to_string())
+            .map_err(|_| anyhow!("The console of {:?} is closed", instance_dir))
+    }
+
+    /// Returns the status of one instance, `NotSupervised` if the daemon does not know it, or the
+    /// status of every instance known to the daemon.
+    pub fn status(&self, instance_dir: Option<&Path>) -> Vec<InstanceStatus> {
+        let status_of = |dir: &Path, managed: &ManagedInstance| InstanceStatus {
+            instance: dir.to_path_buf(),
+            state: managed.state,
+            pid: managed.pid,
+            restarts: managed.restarts,
+            uptime_secs: managed.started.map(|started| started.elapsed().as_secs()),
+            last_exit: managed.last_exit.clone(),
+        };
+
+        let instances = self.instances.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        match instance_dir {
+            // Looked up without inserting, so that querying an instance does not make it supervised.
+            Some(dir) => vec![instances.get(dir).map(|managed| status_of(dir, managed)).unwrap_or_else(|| InstanceStatus {
+                instance: dir.to_path_buf(),
+                state: InstanceState::NotSupervised,
+                pid: None,
+                restarts: 0,
+                uptime_secs: None,
+                last_exit: None,
+            })],
+            None => {
+                let mut statuses: Vec<InstanceStatus> = instances.iter()
+                    .map(|(dir, managed)| status_of(dir, managed))
+                    .collect();
+                statuses.sort_by(|a, b| a.instance.cmp(&b.instance));
+                statuses
+            }
+        }
+    }
+
+    /// Returns whether the daemon knows an instance, started or not.
+    pub fn is_supervised(&self, instance_dir: &Path) -> bool {
+        let instances = self.instances.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        instances.contains_key(instance_dir)
+    }
+
+    /// Subscribes to the console output of an instance.
+    ///
+    /// # Returns
+    /// The recent console lines and a receiver for new ones
+    pub fn subscribe(&self, instance_dir: &Path) -> (Vec<String>, broadcast::Receiver<String>) {
+        self.with_instance(instance_dir, |managed| {
+            (managed.recent.iter().cloned().collect(), managed.output.subscribe())
+        })
+    }
+
+    /// Stops every running instance.
+    pub async fn shutdown(&self) {
+        let running: Vec<PathBuf> = self.status(None).into_iter()
+            .filter(|status| status.state != InstanceState::Stopped)
+            .map(|status| status.instance)
+            .collect();
+
+        let options = StopOptions {
+            countdown: 0,
+            message: String::new(),
+            timeout: SHUTDOWN_TIMEOUT,
+        };
+        for instance_dir in running {
+            if let Err(err) = self.stop(&instance_dir, &options).await {
+                eprintln!("Failed to stop {:?}: {:#}", instance_dir, err);
+            }
+        }
+    }
+}
+
+/// Forwards the lines of a server output stream to a channel.
+fn forward_output<R: AsyncRead + Unpin + Send + 'static>(reader: R, sender: mpsc::UnboundedSender<String>) {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
+
+/// Converts the stop fields of a request into `StopOptions`.
+fn stop_options(countdown: u64, message: String, timeout_secs: u64) -> StopOptions {
+    StopOptions { countdown, message, timeout: Duration::from_secs(timeout_secs) }
+}
+
+/// Writes a response as a line of JSON.
+async fn write_response(stream: &mut (impl AsyncWriteExt + Unpin), response: &Response) -> Result<()> {
+    let mut line = serde_json::to_string(response).context("Failed to serialize response")?;
+    line.push('\n');
+    stream.write_all(line.as_bytes())
+        .await
+        .context("Failed to write response")
+}
+
+/// Handles a single connection to the daemon socket.
+async fn handle_connection(supervisor: Arc<Supervisor>, stream: UnixStream) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+
+    let Some(line) = lines.next_line().await.context("Failed to read request")? else {
+        return Ok(());
+    };
+    let request = match serde_json::from_str::<Request>(&line) {
+        Ok(request) => request,
+        Err(err) => {
+            return write_response(&mut writer, &Response::Error { message: format!("Invalid request: {}", err) }).await;
+        }
+    };
+
+    let result = match request {
+        Request::Start { instance } => supervisor.start(&instance).await.map(|message| Response::Ok { message }),
+        Request::Stop { instance, countdown, message, timeout_secs } => supervisor
+            .stop(&instance, &stop_options(countdown, message, timeout_secs))
+            .await
+            .map(|message| Response::Ok { message }),
+        Request::Restart { instance, countdown, message, timeout_secs } => supervisor
+            .restart(&instance, &stop_options(countdown, message, timeout_secs))
+            .await
+            .map(|message| Response::Ok { message }),
+        Request::Status { instance } => Ok(Response::Status { instances: supervisor.status(instance.as_deref()) }),
+        Request::Command { instance, command } => supervisor.command(&instance, &command)
+            .map(|_| Response::Ok { message: String::new() }),
+        Request::Backup { instance } => backup::backup_instance(&instance, &supervisor.path, "manual", supervisor.console(&instance))
+            .await
+            .map(|backup| Response::Ok { message: format!("Created backup {}", backup.manifest.id) }),
+        Request::Attach { instance } if !supervisor.is_supervised(&instance) => {
+            Err(anyhow!("{:?} is not supervised by the daemon", instance))
+        }
+        Request::Attach { instance } => {
+            let (recent, mut output) = supervisor.subscribe(&instance);
+            for line in recent {
+                write_response(&mut writer, &Response::Output { line }).await?;
+            }
+
+            loop {
+                tokio::select! {
+                    line = output.recv() => match line {
+                        Ok(line) => write_response(&mut writer, &Response::Output { line }).await?,
+                        Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                            let line = format!("[mvm] {} lines skipped", skipped);
+                            write_response(&mut writer, &Response::Output { line }).await?;
+                        }
+                        Err(broadcast::error::RecvError::Closed) => return Ok(())
+                    },
+                    request = lines.next_line() => match request.context("Failed to read request")? {
+                        Some(line) => {
+                            let result = match serde_json::from_str::<Request>(&line) {
+                                Ok(Request::Command { command, .. }) => supervisor.command(&instance, &command),
+                                Ok(_) => Err(anyhow!("Only commands can be sent to an attached console")),
+                                Err(err) => Err(anyhow!("Invalid request: {}", err))
+                            };
+                            if let Err(err) = result {
+                                write_response(&mut writer, &Response::Error { message: format!("{:#}", err) }).await?;
+                            }
+                        }
+                        None => return Ok(())
+                    }
+                }
+            }
+        }
+    };
+
+    let response = result.unwrap_or_else(|err| Response::Error { message: format!("{:#}", err) });
+    write_response(&mut writer, &response)
+        .await
+}
+
+/// Runs the daemon until it receives SIGINT or SIGTERM, then stops all supervised servers.
+///
+/// Instances under `<MVM_HOME>/instances` with `autostart = true` are started right away, and the
+/// scheduled tasks of all instances run while the daemon is up.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+pub async fn run_daemon(path: &Path) -> Result<()> {
+    let socket = socket_path(path);
+    if socket.exists() {
+        if UnixStream::connect(&socket).await.is_ok() {
+            return Err(anyhow!("The daemon is already running, socket: {:?}", socket));
+        }
+        std::fs::remove_file(&socket).context("Failed to remove stale daemon socket")?;
+    }
+
+    std::fs::create_dir_all(path).context("Failed to create the mvm directory")?;
+    let listener = UnixListener::bind(&socket)
+        .context(format!("Failed to bind daemon socket {:?}", socket))?;
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&socket, std::fs::Permissions::from_mode(0o600))
+            .context("Failed to restrict daemon socket permissions")?;
+    }
+    println!("mvm daemon listening on {:?}", socket);
+
+    let supervisor = Supervisor::new(path);
+    let autostart = instance::list_instances(path).await.into_iter()
+        .filter(|(_, config)| config.autostart);
+    for (instance_dir, _) in autostart {
+        match supervisor.start(&instance_dir).await {
+            Ok(message) => println!("{}", message),
+            Err(err) => eprintln!("Failed to start {:?}: {:#}", instance_dir, err)
+        }
+    }
+    tokio::spawn(scheduler::run_scheduler(Arc::clone(&supervisor), path.to_path_buf()));
+
+    let mut terminate = signal(SignalKind::terminate()).context("Failed to listen for SIGTERM")?;
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let (stream, _) = accepted.context("Failed to accept connection")?;
+                let supervisor = Arc::clone(&supervisor);
+                tokio::spawn(async move {
+                    if let Err(err) = handle_connection(supervisor, stream).await {
+                        eprintln!("Connection error: {:#}", err);
+                    }
+                });
+            }
+            _ = tokio::signal::ctrl_c() => break,
+            _ = terminate.recv() => break,
+        }
+    }
+
+    println!("Shutting down, stopping supervised servers...");
+    supervisor.shutdown().await;
+    let _ = std::fs::remove_file(&socket);
+    Ok(())
+}
@@ -0,0 +1,308 @@
+//! Imports server jars and server directories that were set up without mvm.
+//!
+//! A jar is identified by its contents: the `version.json` Mojang embeds names the game version,
+//! the manifest of Paperclip jars and of the jar they bundle identifies Paper, Folia or Purpur and
+//! the build, the manifest of Velocity names its version and build, and Fabric's server launcher
+//! is imported as a custom jar named after the game and loader versions of its `install.properties`.
+//! The jar is then copied or hard-linked to `<MVM_HOME>/<type>/versions/<version>/server.jar`
+//! like a downloaded one, and the directory around it can be adopted as an instance.
+
+use crate::backup::BackupPolicy;
+use crate::instance::{write_instance_config, InstanceConfig, RestartPolicy};
+use crate::metadata::{read_jar_metadata, store_metadata};
+use crate::provenance::{hash_file, record_install};
+use crate::server::server_types::{is_valid_name, ServerType};
+use crate::version_manager::{check_version_name, get_server_jar_path};
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek};
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+/// The manifest of a jar.
+const MANIFEST_FILE: &str = "META-INF/MANIFEST.MF";
+
+/// The directory in which Paperclip jars bundle the actual server jar.
+const BUNDLED_VERSIONS_DIR: &str = "META-INF/versions/";
+
+/// The properties Fabric's server launcher records its game and loader version in.
+const FABRIC_INSTALL_FILE: &str = "install.properties";
+
+/// The properties of Fabric's older server launcher.
+const FABRIC_LAUNCH_FILE: &str = "fabric-server-launch.properties";
+
+/// What a jar was identified as.
+///
+/// # Fields
+/// - `server_type`: The type of server
+/// - `version`: The game version, unknown for jars older than 1.14
+/// - `build`: The Paper, Folia or Purpur build, if the jar records it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JarIdentity {
+    pub server_type: ServerType,
+    pub version: Option<String>,
+    pub build: Option<u32>,
+}
+
+/// Parses the main attributes of a jar manifest, joining continuation lines.
+fn parse_manifest(content: &str) -> HashMap<String, String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (line.strip_prefix(' '), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if line.is_empty() => break,
+            _ => lines.push(line.to_string())
+        }
+    }
+
+    lines.into_iter()
+        .filter_map(|line| line.split_once(": ").map(|(key, value)| (key.to_string(), value.to_string())))
+        .collect()
+}
+
+/// Reads a text entry of a jar, returning `None` if it does not exist.
+fn read_entry<R: Read + Seek>(archive: &mut ZipArchive<R>, name: &str) -> Result<Option<String>> {
+    let Ok(mut entry) = archive.by_name(name) else {
+        return Ok(None);
+    };
+    let mut content = String::new();
+    entry.read_to_string(&mut content)
+        .context(format!("Failed to read {}", name))?;
+    Ok(Some(content))
+}
+
+/// Extracts the build from the `Implementation-Version` of a Paper jar, which is either
+/// `<version>-<build>-<commit>` or `git-Paper-<build>` in older builds, and of a Purpur jar,
+/// which is `git-Purpur-<build>`.
+pub fn parse_paper_build(implementation_version: &str) -> Option<u32> {
+    let prefixed = implementation_version.strip_prefix("git-Paper-")
+        .or_else(|| implementation_version.strip_prefix("git-Purpur-"));
+    if let Some(rest) = prefixed {
+        return rest.split(|c: char| !c.is_ascii_digit()).next()?.parse().ok();
+    }
+    implementation_version.split('-').nth(1)?.parse().ok()
+}
+
+/// Splits the `Implementation-Version` of a Velocity jar, such as
+/// `3.4.0-SNAPSHOT (git-e8b8ba4e-b436)`, into the version and the build.
+pub fn parse_velocity_version(implementation_version: &str) -> (Option<String>, Option<u32>) {
+    let mut parts = implementation_version.split_whitespace();
+    let version = parts.next().map(str::to_string);
+    let build = parts.next()
+        .and_then(|commit| commit.trim_end_matches(')').rsplit_once("-b"))
+        .and_then(|(_, build)| build.parse().ok());
+    (version, build)
+}
+
+/// Identifies the server jar bundled in a Paperclip jar as Paper, Folia or Purpur and reads its build
+/// from its manifest.
+fn bundled_server<R: Read + Seek>(archive: &mut ZipArchive<R>) -> Result<(ServerType, Option<u32>)> {
+    let bundled = archive.file_names()
+        .find(|name| name.starts_with(BUNDLED_VERSIONS_DIR) && name.ends_with(".jar"))
+        .map(str::to_string);
+    let Some(name) = bundled else {
+        return Ok((ServerType::Paper, None));
+    };
+
+    let mut jar = Vec::new();
+    archive.by_name(&name)
+        .context(format!("Failed to open {}", name))?
+        .read_to_end(&mut jar)
+        .context(format!("Failed to read {}", name))?;
+    let mut bundled_archive = ZipArchive::new(Cursor::new(jar))
+        .context(format!("Failed to open {} as a jar", name))?;
+    let manifest = read_entry(&mut bundled_archive, MANIFEST_FILE)?
+        .map(|content| parse_manifest(&content))
+        .unwrap_or_default();
+
+    let file_name = name.rsplit('/').next().unwrap_or_default();
+    let title = manifest.get("Implementation-Title").map(|title| title.to_lowercase()).unwrap_or_default();
+    let server_type = if file_name.starts_with("purpur-") || title == "purpur" {
+        ServerType::Purpur
+    } else if file_name.starts_with("folia-") || title == "folia" {
+        ServerType::Folia
+    } else {
+        ServerType::Paper
+    };
+    Ok((server_type, manifest.get("Implementation-Version").and_then(|version| parse_paper_build(version))))
+}
+
+/// Identifies the server type and version of a jar from its contents.
+///
+/// # Arguments
+/// - `jar_path`: The path of the jar
+///
+/// # Returns
+/// A `Result` containing what the jar was identified as, or an error if it is not a server jar
+/// mvm can manage
+pub fn identify_jar(jar_path: &Path) -> Result<JarIdentity> {
+    let file = File::open(jar_path)
+        .context(format!("Failed to open {:?}", jar_path))?;
+    let mut archive = ZipArchive::new(file)
+        .context(format!("Failed to open {:?} as a jar", jar_path))?;
+    let manifest = read_entry(&mut archive, MANIFEST_FILE)?
+        .map(|content| parse_manifest(&content))
+        .unwrap_or_default();
+    let main_class = manifest.get("Main-Class").cloned().unwrap_or_default();
+
+    let fabric = read_entry(&mut archive, FABRIC_INSTALL_FILE)?
+        .or(read_entry(&mut archive, FABRIC_LAUNCH_FILE)?);
+    if fabric.is_some() || main_class.starts_with("net.fabricmc") {
+        // mvm has no Fabric server type, so the launcher is kept as a custom jar named after
+        // the game and loader versions it launches.
+        let property = |key: &str| fabric.as_deref()
+            .and_then(|content| content.lines().find_map(|line| line.strip_prefix(key)?.strip_prefix('=')))
+            .map(|value| value.trim().to_string());
+        let version = match (property("game-version"), property("fabric-loader-version")) {
+            (Some(game_version), Some(loader_version)) => Some(format!("{}-{}", game_version, loader_version)),
+            (game_version, _) => game_version
+        };
+        return Ok(JarIdentity { server_type: ServerType::Custom, version, build: None });
+    }
+
+    let version = read_jar_metadata(jar_path)?
+        .map(|metadata| metadata.name);
+    if main_class.to_lowercase().contains("paperclip") {
+        let (server_type, build) = bundled_server(&mut archive)?;
+        return Ok(JarIdentity { server_type, version, build });
+    }
+    if main_class.starts_with("com.velocitypowered.") {
+        let (version, build) = manifest.get("Implementation-Version")
+            .map(|version| parse_velocity_version(version))
+            .unwrap_or_default();
+        return Ok(JarIdentity { server_type: ServerType::Velocity, version, build });
+    }
+    if main_class.starts_with("net.minecraft.") {
+        return Ok(JarIdentity { server_type: ServerType::Vanilla, version, build: None });
+    }
+
+    Err(anyhow!("{:?} is not a vanilla, Paper, Folia, Purpur, Velocity or Fabric server jar", jar_path))
+}
+
+/// Finds the server jar of a server directory: its `server.jar`, or the only jar in it that is
+/// a server jar mvm can manage.
+///
+/// # Arguments
+/// - `server_dir`: The server directory
+///
+/// # Returns
+/// A `Result` containing the path of the server jar if exactly one was found
+pub fn find_server_jar(server_dir: &Path) -> Result<PathBuf> {
+    let server_jar = server_dir.join("server.jar");
+    if server_jar.is_file() {
+        return Ok(server_jar);
+    }
+
+    let mut candidates = Vec::new();
+    for entry in fs::read_dir(server_dir).context(format!("Failed to read {:?}", server_dir))? {
+        let entry_path = entry.context(format!("Failed to read {:?}", server_dir))?.path();
+        if entry_path.is_file() && entry_path.extension().is_some_and(|extension| extension == "jar") && identify_jar(&entry_path).is_ok() {
+            candidates.push(entry_path);
+        }
+    }
+
+    match candidates.len() {
+        1 => Ok(candidates.remove(0)),
+        0 => Err(anyhow!("No server jar found in {:?}", server_dir)),
+        _ => Err(anyhow!("Found several server jars in {:?}: {:?}, import one of them directly", server_dir, candidates))
+    }
+}
+
+/// Installs a jar that was not downloaded by mvm, as if it had been.
+///
+/// # Arguments
+/// - `jar_path`: The path of the jar
+/// - `path`: The root directory of server installations
+/// - `version`: The version to install the jar as, overriding the one found in the jar
+/// - `link`: Whether to hard-link the jar instead of copying it
+///
+/// # Returns
+/// A `Result` containing the server type and version the jar was installed as
+pub fn import_jar(jar_path: &Path, path: &Path, version: Option<&str>, link: bool) -> Result<(ServerType, String)> {
+    let identity = identify_jar(jar_path)?;
+    let version = version.map(str::to_string)
+        .or(identity.version)
+        .ok_or_else(|| anyhow!("{:?} does not record its version, pass it with --version", jar_path))?;
+    check_version_name(&version)?;
+
+    let target = get_server_jar_path(&version, &identity.server_type, path);
+    if target.exists() {
+        if hash_file(&target)?.sha256 == hash_file(jar_path)?.sha256 {
+            println!("{} {} is already installed", identity.server_type, version);
+            return Ok((identity.server_type, version));
+        }
+        return Err(anyhow!("{} {} is already installed from a different jar at {:?}", identity.server_type, version, target));
+    }
+
+    let version_dir = target.parent().unwrap_or(path);
+    fs::create_dir_all(version_dir)
+        .context(format!("Failed to create directory for the version, path: {:?}", version_dir))?;
+    if link {
+        fs::hard_link(jar_path, &target)
+            .context(format!("Failed to hard-link {:?} to {:?}, the jar has to be on the same file system", jar_path, target))?;
+    } else {
+        fs::copy(jar_path, &target)
+            .context(format!("Failed to copy {:?} to {:?}", jar_path, target))?;
+    }
+
+    let source = fs::canonicalize(jar_path).unwrap_or_else(|_| jar_path.to_path_buf());
+    record_install(&target, &format!("file://{}", source.display()), &identity.server_type, &version, identity.build)?;
+    store_metadata(&target)?;
+
+    println!("Imported {:?} as {} {}", jar_path, identity.server_type, version);
+    Ok((identity.server_type, version))
+}
+
+/// Adopts an existing server directory as an instance, keeping its worlds and configuration
+/// where they are. The directory is linked into `<MVM_HOME>/instances` under the given name.
+///
+/// # Arguments
+/// - `server_dir`: The server directory
+/// - `name`: The name of the instance
+/// - `server_type`: The type of server the directory runs
+/// - `version`: The installed version the instance is pinned to
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the directory of the instance if successful
+pub async fn adopt_instance(server_dir: &Path, name: &str, server_type: &ServerType, version: &str, path: &Path) -> Result<PathBuf> {
+    if !is_valid_name(name) {
+        return Err(anyhow!("Invalid instance name {:?}, use lowercase letters, digits, '-' and '_'", name));
+    }
+    if server_dir.join("instance.toml").exists() {
+        return Err(anyhow!("{:?} already is an instance", server_dir));
+    }
+    let instance_dir = path.join("instances").join(name);
+    if instance_dir.exists() || instance_dir.is_symlink() {
+        return Err(anyhow!("Instance '{}' already exists", name));
+    }
+
+    let server_dir = tokio::fs::canonicalize(server_dir)
+        .await
+        .context(format!("Failed to resolve {:?}", server_dir))?;
+    tokio::fs::create_dir_all(path.join("instances"))
+        .await
+        .context("Failed to create the instances directory")?;
+    #[cfg(unix)]
+    tokio::fs::symlink(&server_dir, &instance_dir)
+        .await
+        .context(format!("Failed to link {:?} to {:?}", instance_dir, server_dir))?;
+    #[cfg(not(unix))]
+    return Err(anyhow!("Adopting directories as instances is only supported on Unix, move {:?} to {:?} instead", server_dir, instance_dir));
+
+    let config = InstanceConfig {
+        server_type: server_type.clone(),
+        version: version.to_string(),
+        java_args: Vec::new(),
+        autostart: false,
+        restart: RestartPolicy::default(),
+        schedule: Vec::new(),
+        backup: BackupPolicy::default(),
+    };
+    write_instance_config(&server_dir, &config)
+        .await?;
+
+    println!("Adopted {:?} as instance '{}' on {} {}", server_dir, name, server_type, version);
+    Ok(instance_dir)
+}
@@ -0,0 +1,508 @@
+//! Manages server instances.
+//!
+//! An instance is a directory in which a pinned server version runs with its own world and
+//! configuration files. Instances created by mvm live under `<MVM_HOME>/instances/<name>` and
+//! record their server type and version in an `instance.toml` file.
+
+use crate::backup::{self, BackupPolicy};
+use crate::logs::{RotatingLog, CONSOLE_LOG, DEFAULT_MAX_FILES, DEFAULT_MAX_SIZE};
+use crate::process;
+use crate::schedule::ScheduledTask;
+use crate::server::loader::read_launch_config;
+use crate::server::server_types::{is_valid_name, ServerType};
+use crate::version_manager::{ensure_installed, get_server_jar_path, resolve_version};
+use crate::world;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::path::{Path, PathBuf};
+use std::process::{ExitStatus, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWriteExt, BufReader};
+use tokio::process::Command;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+
+/// The location of the Minecraft EULA that has to be accepted before a server will start.
+pub const EULA_URL: &str = "https://aka.ms/MinecraftEULA";
+
+/// How long a headless first boot may take before the server is killed.
+const FIRST_BOOT_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Represents the configuration of an instance stored in its `instance.toml`.
+///
+/// # Fields
+/// - `server_type`: The type of server the instance runs.
+/// - `version`: The server version pinned for the instance.
+/// - `java_args`: Extra arguments passed to the JVM before `-jar`.
+/// - `autostart`: Whether `mvm daemon` starts the instance when it launches.
+/// - `restart`: How `mvm daemon` restarts the instance after a crash.
+/// - `schedule`: Maintenance tasks `mvm daemon` runs on a cron schedule.
+/// - `backup`: Which backups are kept when pruning.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct InstanceConfig {
+    pub server_type: ServerType,
+    pub version: String,
+    #[serde(default)]
+    pub java_args: Vec<String>,
+    #[serde(default)]
+    pub autostart: bool,
+    #[serde(default)]
+    pub restart: RestartPolicy,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub schedule: Vec<ScheduledTask>,
+    #[serde(default)]
+    pub backup: BackupPolicy,
+}
+
+/// Represents how a crashed server is restarted by the daemon.
+///
+/// # Fields
+/// - `enabled`: Whether crashed servers are restarted at all.
+/// - `max_restarts`: How many crashes within `window_secs` are tolerated before giving up.
+/// - `window_secs`: The time window in which crashes are counted.
+/// - `backoff_secs`: The delay before the first restart, doubled after every further crash.
+/// - `max_backoff_secs`: The upper bound of the restart delay.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RestartPolicy {
+    pub enabled: bool,
+    pub max_restarts: u32,
+    pub window_secs: u64,
+    pub backoff_secs: u64,
+    pub max_backoff_secs: u64,
+}
+
+impl Default for RestartPolicy {
+    fn default() -> Self {
+        RestartPolicy {
+            enabled: true,
+            max_restarts: 5,
+            window_secs: 600,
+            backoff_secs: 5,
+            max_backoff_secs: 300,
+        }
+    }
+}
+
+/// Resolves an instance argument into the directory of the instance.
+///
+/// The argument is treated as a path if it points to an existing directory or contains a path
+/// separator, otherwise it is the name of an instance under `<MVM_HOME>/instances`.
+///
+/// # Arguments
+/// - `instance`: The name of the instance or a path to its directory
+/// - `path`: The root directory of server installations
+pub fn get_instance_dir(instance: &str, path: &Path) -> PathBuf {
+    let candidate = PathBuf::from(instance);
+    if candidate.is_dir() || instance.contains(std::path::MAIN_SEPARATOR) {
+        return candidate;
+    }
+
+    path.join("instances").join(instance)
+}
+
+/// Reads the `instance.toml` of an instance.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+///
+/// # Returns
+/// A `Result` containing the instance configuration if successful
+pub async fn read_instance_config(instance_dir: &Path) -> Result<InstanceConfig> {
+    let config_path = instance_dir.join("instance.toml");
+    let toml_content = fs::read_to_string(&config_path)
+        .await
+        .context(format!("No instance found at {:?}", instance_dir))?;
+
+    toml::from_str::<InstanceConfig>(&toml_content)
+        .context("Failed to deserialize instance config")
+}
+
+/// Writes the `instance.toml` of an instance.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `config`: The configuration to store
+pub async fn write_instance_config(instance_dir: &Path, config: &InstanceConfig) -> Result<()> {
+    let toml_string = toml::to_string_pretty(config)
+        .context("Failed to serialize instance config")?;
+
+    fs::write(instance_dir.join("instance.toml"), toml_string)
+        .await
+        .context("Failed to write to instance.toml file")
+}
+
+/// Returns the instances under `<MVM_HOME>/instances` with their configuration. Directories
+/// without a readable `instance.toml` are skipped.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// The canonical instance directories and their configuration, sorted by directory
+pub async fn list_instances(path: &Path) -> Vec<(PathBuf, InstanceConfig)> {
+    let mut instances = Vec::new();
+    let Ok(mut entries) = fs::read_dir(path.join("instances")).await else {
+        return instances;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let instance_dir = entry.path();
+        if let Ok(config) = read_instance_config(&instance_dir).await {
+            instances.push((fs::canonicalize(&instance_dir).await.unwrap_or(instance_dir), config));
+        }
+    }
+
+    instances.sort_by(|a, b| a.0.cmp(&b.0));
+    instances
+}
+
+/// Creates a new instance directory pinned to a server version.
+/// "latest" and "recent" are resolved to a concrete version, which is downloaded if it is not installed yet.
+///
+/// # Arguments
+/// - `name`: The name of the instance
+/// - `version`: A reference to the version of the minecraft server to pin
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the directory of the new instance if successful
+pub async fn init_instance(name: &str, version: &str, server_type: &ServerType, path: &Path) -> Result<PathBuf> {
+    if !is_valid_name(name) {
+        return Err(anyhow!("Invalid instance name {:?}, use lowercase letters, digits, '-' and '_'", name));
+    }
+    let instance_dir = path.join("instances").join(name);
+    if instance_dir.join("instance.toml").exists() {
+        return Err(anyhow!("Instance '{}' already exists", name));
+    }
+
+    let version = resolve_version(version, server_type, path)
+        .await?;
+    ensure_installed(&version, server_type, path)
+        .await?;
+
+    fs::create_dir_all(&instance_dir)
+        .await
+        .context(format!("Failed to create directory for the instance, path: {:?}", &instance_dir))?;
+
+    let config = InstanceConfig {
+        server_type: server_type.clone(),
+        version: version.clone(),
+        java_args: Vec::new(),
+        autostart: false,
+        restart: RestartPolicy::default(),
+        schedule: Vec::new(),
+        backup: BackupPolicy::default(),
+    };
+    write_instance_config(&instance_dir, &config)
+        .await?;
+
+    println!("Created instance '{}' for {} {} at {:?}", name, server_type, version, &instance_dir);
+    Ok(instance_dir)
+}
+
+/// Switches an instance to another server version of its server type.
+///
+/// The worlds are backed up before the switch, since a newer server upgrades them irreversibly.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `version`: The version to switch to, or "latest"
+/// - `path`: The root directory of server installations
+/// - `force`: Whether to switch even if a world was saved by a newer version
+///
+/// # Returns
+/// A `Result` containing the updated instance configuration if successful
+pub async fn set_instance_version(instance_dir: &Path, version: &str, path: &Path, force: bool) -> Result<InstanceConfig> {
+    let mut config = read_instance_config(instance_dir)
+        .await?;
+    if let Some(pid) = process::find_server_pid(instance_dir).await {
+        return Err(anyhow!("The server of this instance is running with pid {}, stop it before switching versions", pid));
+    }
+
+    let version = resolve_version(version, &config.server_type, path)
+        .await?;
+    if version == config.version {
+        println!("Instance already uses {} {}", config.server_type, version);
+        return Ok(config);
+    }
+    ensure_installed(&version, &config.server_type, path)
+        .await?;
+    if !force {
+        world::check_downgrade(instance_dir, &version, &config.server_type, path)
+            .await?;
+    }
+
+    if !backup::world_dirs(instance_dir).await.is_empty() {
+        let backup = backup::create_backup(instance_dir, path, "pre-upgrade")
+            .await?;
+        println!("Backed up the worlds as {} before switching from {}", backup.manifest.id, config.version);
+    }
+
+    config.version = version;
+    write_instance_config(instance_dir, &config)
+        .await?;
+
+    println!("Instance now uses {} {}", config.server_type, config.version);
+    Ok(config)
+}
+
+/// Returns whether the EULA has been accepted in the given server directory.
+pub async fn eula_accepted(instance_dir: &Path) -> bool {
+    match fs::read_to_string(instance_dir.join("eula.txt")).await {
+        Ok(content) => content.lines().any(|line| line.trim() == "eula=true"),
+        Err(_) => false
+    }
+}
+
+/// Checks that the EULA has been accepted in an instance that needs it. Proxies do not run the
+/// game, so their instances do not.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `server_type`: The type of server the instance runs
+pub async fn check_eula(instance_dir: &Path, server_type: &ServerType) -> Result<()> {
+    if server_type.is_proxy() || eula_accepted(instance_dir).await {
+        return Ok(());
+    }
+    Err(anyhow!("The EULA has to be accepted before the server can start, see {}", EULA_URL))
+}
+
+/// Writes an `eula.txt` accepting the Minecraft EULA into the given server directory.
+/// Callers must only do this after the user explicitly agreed to the EULA.
+pub async fn accept_eula(instance_dir: &Path) -> Result<()> {
+    let content = format!(
+        "#By changing the setting below to TRUE you are indicating your agreement to our EULA ({}).\neula=true\n",
+        EULA_URL
+    );
+
+    fs::write(instance_dir.join("eula.txt"), content)
+        .await
+        .context("Failed to write to eula.txt file")
+}
+
+/// Returns the Java executable used to launch servers.
+/// Uses `$JAVA_HOME/bin/java` when `JAVA_HOME` is set, otherwise `java` from the `PATH`.
+pub fn java_binary() -> PathBuf {
+    match env::var("JAVA_HOME") {
+        Ok(java_home) if !java_home.is_empty() => PathBuf::from(java_home).join("bin").join("java"),
+        _ => PathBuf::from("java")
+    }
+}
+
+/// Builds the command launching the pinned server jar of an instance inside its directory.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the command if successful
+pub async fn server_command(instance_dir: &Path, path: &Path) -> Result<Command> {
+    let config = read_instance_config(instance_dir)
+        .await?;
+    let server_jar_path = get_server_jar_path(&config.version, &config.server_type, path);
+    if !server_jar_path.exists() {
+        return Err(anyhow!("Version '{}' is not installed", &config.version));
+    }
+
+    let mut command = Command::new(java_binary());
+    command.args(&config.java_args)
+        .current_dir(instance_dir);
+    match read_launch_config(server_jar_path.parent().unwrap_or(path))? {
+        Some(launch) => command.args(launch.args),
+        None => command.arg("-jar").arg(server_jar_path)
+    };
+    if !config.server_type.is_proxy() {
+        command.arg("nogui");
+    }
+
+    Ok(command)
+}
+
+/// Starts the server of an instance once without a console so that it generates its default
+/// configuration files and world, then stops it as soon as it reports that it is done loading.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+pub async fn first_boot(instance_dir: &Path, path: &Path) -> Result<()> {
+    let config = read_instance_config(instance_dir)
+        .await?;
+    check_eula(instance_dir, &config.server_type)
+        .await?;
+
+    let mut child = server_command(instance_dir, path)
+        .await?
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start the server, is Java installed?")?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("Failed to open server stdin"))?;
+    let stdout = child.stdout.take().ok_or_else(|| anyhow!("Failed to open server stdout"))?;
+
+    let boot = async {
+        let mut lines = BufReader::new(stdout).lines();
+        while let Some(line) = lines.next_line().await.context("Failed to read server output")? {
+            println!("{}", line);
+            if line.contains("Done (") {
+                stdin.write_all(b"stop\n")
+                    .await
+                    .context("Failed to send stop command to the server")?;
+                stdin.flush()
+                    .await
+                    .context("Failed to send stop command to the server")?;
+            }
+        }
+        child.wait().await.context("Failed to wait for the server to stop")
+    };
+
+    match tokio::time::timeout(FIRST_BOOT_TIMEOUT, boot).await {
+        Ok(status) => {
+            let status = status?;
+            if !status.success() {
+                return Err(anyhow!("Server exited with {} during first boot", status));
+            }
+            println!("First boot completed, default configuration generated in {:?}", instance_dir);
+            Ok(())
+        }
+        Err(_) => Err(anyhow!("Server did not finish its first boot within {} seconds", FIRST_BOOT_TIMEOUT.as_secs()))
+    }
+}
+
+/// Runs the server of an instance in the foreground.
+///
+/// The process id is recorded in `mvm.pid`, and console commands are read both from the terminal
+/// and from the instance's `console.pipe`, so that other mvm commands can reach the console.
+/// The server output is also appended to `logs/mvm-console.log` for `mvm attach`.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `path`: The root directory of server installations
+/// - `force`: Whether to start even if a world was saved by a newer version
+///
+/// # Returns
+/// A `Result` containing the exit status of the server if successful
+pub async fn run_instance(instance_dir: &Path, path: &Path, force: bool) -> Result<ExitStatus> {
+    let config = read_instance_config(instance_dir)
+        .await?;
+    check_eula(instance_dir, &config.server_type)
+        .await?;
+    if let Some(pid) = process::find_server_pid(instance_dir).await {
+        return Err(anyhow!("The server of this instance is already running with pid {}", pid));
+    }
+    if !force && get_server_jar_path(&config.version, &config.server_type, path).exists() {
+        world::check_downgrade(instance_dir, &config.version, &config.server_type, path)
+            .await?;
+    }
+
+    // The output is copied into the console log so that `mvm attach` can follow it.
+    let log = RotatingLog::open(&instance_dir.join("logs").join(CONSOLE_LOG), DEFAULT_MAX_SIZE, DEFAULT_MAX_FILES)?;
+    let log = Arc::new(Mutex::new(log));
+
+    // The server must not outlive this function if it returns early.
+    let mut child = server_command(instance_dir, path)
+        .await?
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .context("Failed to start the server, is Java installed?")?;
+
+    let Some(mut stdin) = child.stdin.take() else {
+        let _ = child.kill().await;
+        return Err(anyhow!("Failed to open server stdin"));
+    };
+    if let Some(pid) = child.id() {
+        if let Err(err) = process::write_pid_file(instance_dir, pid).await {
+            let _ = child.kill().await;
+            return Err(err);
+        }
+    }
+
+    let mut output_tasks = Vec::new();
+    if let Some(stdout) = child.stdout.take() {
+        output_tasks.push(tee_output(stdout, log.clone(), false));
+    }
+    if let Some(stderr) = child.stderr.take() {
+        output_tasks.push(tee_output(stderr, log, true));
+    }
+
+    let (sender, mut receiver) = mpsc::unbounded_channel::<String>();
+    forward_lines(std::io::stdin(), sender.clone());
+    match process::create_console_pipe(instance_dir) {
+        Ok(pipe_path) => {
+            // Opening the pipe for writing too keeps it from reporting the end of input whenever
+            // a writer disconnects, and keeps the open call from blocking until a writer appears.
+            match std::fs::OpenOptions::new().read(true).write(true).open(&pipe_path) {
+                Ok(pipe) => forward_lines(pipe, sender),
+                Err(err) => eprintln!("Console pipe unavailable: {}", err)
+            }
+        }
+        Err(err) => eprintln!("Console pipe unavailable: {}", err)
+    }
+
+    tokio::spawn(async move {
+        while let Some(line) = receiver.recv().await {
+            if stdin.write_all(format!("{}\n", line).as_bytes()).await.is_err() {
+                break;
+            }
+            let _ = stdin.flush().await;
+        }
+    });
+
+    let status = child.wait()
+        .await
+        .context("Failed to wait for the server to stop");
+    for task in output_tasks {
+        let _ = task.await;
+    }
+
+    process::remove_pid_file(instance_dir)
+        .await?;
+    let _ = fs::remove_file(instance_dir.join(process::CONSOLE_PIPE)).await;
+
+    status
+}
+
+/// Prints the lines of a server output stream and appends them to the console log.
+fn tee_output<R: AsyncRead + Unpin + Send + 'static>(reader: R, log: Arc<Mutex<RotatingLog>>, stderr: bool) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(reader).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if stderr {
+                eprintln!("{}", line);
+            } else {
+                println!("{}", line);
+            }
+
+            let mut log = log.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            if let Err(err) = log.write_line(&line) {
+                eprintln!("{:#}", err);
+            }
+        }
+    })
+}
+
+/// Forwards the lines read from a blocking reader to a channel on a background thread.
+/// A plain thread is used because a pending read must not keep the runtime from shutting down.
+fn forward_lines<R: std::io::Read + Send + 'static>(reader: R, sender: mpsc::UnboundedSender<String>) {
+    std::thread::spawn(move || {
+        use std::io::BufRead;
+        for line in std::io::BufReader::new(reader).lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+}
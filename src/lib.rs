@@ -2,4 +2,24 @@
 pub mod version_manager;
 pub mod config;
 pub mod server;
+pub mod instance;
+pub mod properties;
+pub mod rcon;
+pub mod status;
+pub mod process;
+pub mod stop;
+pub mod logs;
+pub mod schedule;
+pub mod backup;
+pub mod nbt;
+pub mod world;
+pub mod metadata;
+pub mod provenance;
+pub mod verify;
+pub mod import;
+pub mod network;
+#[cfg(unix)]
+pub mod daemon;
+#[cfg(unix)]
+pub mod attach;
 
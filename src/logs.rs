@@ -0,0 +1,96 @@
+//! Writes server output to size-limited, rotating log files.
+
+use anyhow::{Context, Result};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// The name of the console log `mvm run` and the daemon write inside an instance's `logs` directory.
+pub const CONSOLE_LOG: &str = "mvm-console.log";
+
+/// The size at which the console log is rotated.
+pub const DEFAULT_MAX_SIZE: u64 = 10 * 1024 * 1024;
+
+/// How many rotated console logs are kept.
+pub const DEFAULT_MAX_FILES: usize = 5;
+
+/// A log file that is renamed to `<name>.1` once it reaches its size limit, shifting older files
+/// up to `<name>.<max_files>` and deleting the oldest.
+pub struct RotatingLog {
+    path: PathBuf,
+    file: File,
+    size: u64,
+    max_size: u64,
+    max_files: usize,
+}
+
+impl RotatingLog {
+    /// Opens a log file for appending, creating it and its directory if needed.
+    ///
+    /// # Arguments
+    /// - `path`: The path of the log file
+    /// - `max_size`: The size in bytes at which the file is rotated
+    /// - `max_files`: How many rotated files are kept
+    pub fn open(path: &Path, max_size: u64, max_files: usize) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)
+                .context(format!("Failed to create log directory {:?}", parent))?;
+        }
+
+        let file = open_append(path)?;
+        let size = file.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+
+        Ok(RotatingLog { path: path.to_path_buf(), file, size, max_size, max_files })
+    }
+
+    /// Appends a line, rotating the file first if the line would exceed the size limit.
+    pub fn write_line(&mut self, line: &str) -> Result<()> {
+        let length = line.len() as u64 + 1;
+        if self.size > 0 && self.size + length > self.max_size {
+            self.rotate()?;
+        }
+
+        writeln!(self.file, "{}", line).context("Failed to write to log file")?;
+        self.size += length;
+        Ok(())
+    }
+
+    /// Returns the path of the rotated file with the given index.
+    pub fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = self.path.file_name().unwrap_or_default().to_os_string();
+        name.push(format!(".{}", index));
+        self.path.with_file_name(name)
+    }
+
+    fn rotate(&mut self) -> Result<()> {
+        self.file.flush().context("Failed to flush log file")?;
+
+        if self.max_files == 0 {
+            fs::remove_file(&self.path).context("Failed to remove log file")?;
+        } else {
+            let oldest = self.rotated_path(self.max_files);
+            if oldest.exists() {
+                fs::remove_file(&oldest).context("Failed to remove the oldest log file")?;
+            }
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    fs::rename(&from, self.rotated_path(index + 1)).context("Failed to rotate log file")?;
+                }
+            }
+            fs::rename(&self.path, self.rotated_path(1)).context("Failed to rotate log file")?;
+        }
+
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+fn open_append(path: &Path) -> Result<File> {
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open log file {:?}", path))
+}
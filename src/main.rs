@@ -8,24 +8,49 @@
 //! - `Install`: Installs a specific or latest version of the server.
 //! - `Uninstall`: Removes a specific server version.
 //! - `Which`: Determines the path of a specific or recent server version.
+//! - `List`: Lists the installed server versions.
+//! - `Verify`: Checks installed server jars against their recorded and published hashes.
+//! - `Import`: Installs a server jar obtained elsewhere and optionally adopts its directory as an instance.
+//! - `Info`: Shows the data, protocol, Java and pack versions of an installed server version.
+//! - `Init`: Creates a server directory for a version and bootstraps it.
+//! - `Props`: Reads, changes and compares the `server.properties` of an instance.
+//! - `Rcon`: Runs commands on a running instance over RCON.
+//! - `Status`: Queries whether a server is up and who is online.
+//! - `Run`: Runs the server of an instance in the foreground.
+//! - `Stop`: Gracefully stops the running server of an instance.
+//! - `Daemon`: Supervises instances, restarting them after crashes.
+//! - `Start`/`Restart`: Starts or restarts an instance under the daemon.
+//! - `Attach`: Opens an interactive console on a running server.
+//! - `Schedule`: Lists the scheduled tasks of instances and their next runs.
+//! - `Backup`: Creates, lists, restores and prunes backups of instance worlds.
+//! - `Network`: Creates a Velocity proxy with backends configured for modern forwarding.
 
 
-mod version_manager;
-mod config;
-mod server;
-
 use clap::{Parser, Subcommand};
-use crate::version_manager::download_server_jar;
-use crate::server::server_types::ServerType;
-use anyhow::{anyhow, Result};
-use config::{get_dir};
+use mvm::{backup, import, instance, metadata, network, process, provenance, verify, properties, rcon, schedule, status, stop, version_manager};
+use mvm::version_manager::download_server_jar;
+use mvm::server::loader::read_launch_config;
+use mvm::server::server_types::{is_valid_name, ServerType};
+#[cfg(unix)]
+use mvm::attach::{self, AttachTarget};
+#[cfg(unix)]
+use mvm::daemon::{self, protocol::{InstanceState, InstanceStatus, Request, Response}};
+use anyhow::{anyhow, Context, Result};
+use mvm::config::{get_dir};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::io::AsyncBufReadExt;
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
-
 struct Cli {
     #[arg(long, global = true)]
     paper: bool,
+    /// The server type to manage, such as `vanilla`, `paper`, `folia`, `purpur`, `velocity`, `forge`, `neoforge`, `quilt`, `custom` or one declared under
+    /// `[server_types]` in config.toml. Overrides `--paper`.
+    #[arg(long = "type", global = true)]
+    server_type: Option<String>,
     #[command(subcommand)]
     command: Option<Commands>,
 }
@@ -36,14 +61,34 @@ enum Commands {
         version: Option<String>,
 
         #[arg(long)]
-        paper: bool
+        paper: bool,
+
+        /// Switches this instance to the version instead, backing up its worlds first.
+        #[arg(long)]
+        instance: Option<String>,
+
+        /// Switches the instance even if a world was saved by a newer version.
+        #[arg(long, requires = "instance")]
+        force: bool
     },
     Install {
         #[arg(default_value = "latest")]
         version: String,
 
         #[arg(long)]
-        paper: bool
+        paper: bool,
+
+        /// Installs the jar from this URL or file instead of the upstream API.
+        #[arg(long)]
+        from: Option<String>,
+
+        /// The name to install a jar given with `--from` as, defaults to the version argument.
+        #[arg(long, requires = "from")]
+        name: Option<String>,
+
+        /// Installs this Purpur build instead of the latest one.
+        #[arg(long, conflicts_with = "from")]
+        build: Option<u32>
     },
     Uninstall {
         version: Option<String>,
@@ -55,8 +100,270 @@ enum Commands {
         #[arg(default_value = "recent")]
         version: String,
 
+        #[arg(long)]
+        paper: bool,
+
+        /// Also shows where the jar came from and whether it changed since it was installed.
+        #[arg(long)]
+        verbose: bool
+    },
+    List {
+        #[arg(long)]
+        paper: bool
+    },
+    Info {
+        #[arg(default_value = "recent")]
+        version: String,
+
         #[arg(long)]
         paper: bool
+    },
+    Import {
+        /// A server jar, or a server directory containing one.
+        path: PathBuf,
+
+        /// The version to install the jar as, for jars that do not record it.
+        #[arg(long)]
+        version: Option<String>,
+
+        /// Hard-links the jar instead of copying it.
+        #[arg(long)]
+        link: bool,
+
+        /// Adopts the server directory as an instance with this name.
+        #[arg(long)]
+        instance: Option<String>
+    },
+    Verify {
+        /// Downloads modified and missing jars again.
+        #[arg(long)]
+        redownload: bool,
+
+        /// Only compares with the hashes recorded at install time.
+        #[arg(long)]
+        offline: bool
+    },
+    Init {
+        name: String,
+
+        #[arg(default_value = "latest")]
+        version: String,
+
+        #[arg(long)]
+        paper: bool,
+
+        #[arg(long)]
+        accept_eula: bool,
+
+        #[arg(long)]
+        first_boot: bool
+    },
+    Props {
+        #[command(subcommand)]
+        command: PropsCommands
+    },
+    Rcon {
+        instance: String,
+
+        /// The command to run. Opens an interactive console if omitted.
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>
+    },
+    Status {
+        /// An instance name or directory, or a `host[:port]` address.
+        /// Lists the instances supervised by the daemon if omitted.
+        target: Option<String>,
+
+        #[arg(long)]
+        json: bool
+    },
+    Run {
+        instance: String,
+
+        /// Starts the server even if a world was saved by a newer version.
+        #[arg(long)]
+        force: bool
+    },
+    Stop {
+        instance: String,
+
+        /// Seconds to warn players before stopping.
+        #[arg(long, default_value_t = 30)]
+        countdown: u64,
+
+        /// The warning broadcast to players, `{seconds}` is replaced with the remaining time.
+        #[arg(long, default_value = "Server stopping in {seconds} seconds")]
+        message: String,
+
+        /// Seconds to wait for the server to exit before sending signals.
+        #[arg(long, default_value_t = 60)]
+        timeout: u64
+    },
+    #[cfg(unix)]
+    Daemon,
+    #[cfg(unix)]
+    Start {
+        instance: String
+    },
+    #[cfg(unix)]
+    Restart {
+        instance: String,
+
+        /// Seconds to warn players before stopping.
+        #[arg(long, default_value_t = 30)]
+        countdown: u64,
+
+        /// The warning broadcast to players, `{seconds}` is replaced with the remaining time.
+        #[arg(long, default_value = "Server restarting in {seconds} seconds")]
+        message: String,
+
+        /// Seconds to wait for the server to exit before sending signals.
+        #[arg(long, default_value_t = 60)]
+        timeout: u64
+    },
+    #[cfg(unix)]
+    Attach {
+        instance: String
+    },
+    Schedule {
+        #[command(subcommand)]
+        command: ScheduleCommands
+    },
+    Backup {
+        #[command(subcommand)]
+        command: BackupCommands
+    },
+    Network {
+        #[command(subcommand)]
+        command: NetworkCommands
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum NetworkCommands {
+    /// Creates a Velocity proxy with backend instances that accept the players it forwards.
+    /// The backends are Paper servers unless `--type` names another server type.
+    Create {
+        name: String,
+
+        /// The name of a backend, repeat for several. Players join the first one.
+        #[arg(long = "backend", required = true)]
+        backends: Vec<String>,
+
+        /// The version of the backends.
+        #[arg(long, default_value = "latest")]
+        version: String,
+
+        /// The Velocity version of the proxy.
+        #[arg(long, default_value = "latest")]
+        proxy_version: String,
+
+        /// The port players connect to, defaults to the first free port from 25565.
+        #[arg(long)]
+        port: Option<u16>,
+
+        /// The first port tried for the backends.
+        #[arg(long, default_value_t = network::DEFAULT_BACKEND_PORT)]
+        backend_port: u16,
+
+        #[arg(long)]
+        accept_eula: bool
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupCommands {
+    /// Backs up the worlds of an instance, pausing saving if the server is running.
+    Create {
+        instance: String
+    },
+    /// Lists the backups of an instance, or of every instance in the backup storage.
+    List {
+        instance: Option<String>
+    },
+    /// Replaces the worlds of a stopped instance with a backup.
+    Restore {
+        instance: String,
+
+        /// The id of the backup.
+        #[arg(default_value = "latest")]
+        backup: String,
+
+        /// Only restore this file, such as `world/region/r.0.0.mca`, leaving the rest of the world as it is.
+        #[arg(long)]
+        file: Option<String>
+    },
+    /// Checks that the chunks of the backups of an instance are present and intact.
+    Verify {
+        instance: String,
+
+        /// The id of the backup to check, all backups of the instance are checked if omitted.
+        backup: Option<String>
+    },
+    /// Deletes stored chunks that no backup refers to anymore.
+    Gc {
+        /// Only print how much space would be freed.
+        #[arg(long)]
+        dry_run: bool
+    },
+    /// Deletes the backups the retention policy of the instance does not keep, and the chunks only they used.
+    Prune {
+        instance: String,
+
+        /// How many of the newest backups to keep, overrides `keep_last` in instance.toml.
+        #[arg(long)]
+        keep_last: Option<usize>,
+
+        /// For how many days to keep the newest backup of the day.
+        #[arg(long)]
+        keep_daily: Option<usize>,
+
+        /// For how many weeks to keep the newest backup of the week.
+        #[arg(long)]
+        keep_weekly: Option<usize>,
+
+        /// Only print what would be deleted.
+        #[arg(long)]
+        dry_run: bool
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum ScheduleCommands {
+    /// Lists the scheduled tasks of one or all instances with their next run.
+    List {
+        instance: Option<String>
+    },
+    /// Shows the upcoming runs of scheduled tasks in chronological order.
+    Next {
+        instance: Option<String>,
+
+        /// How many runs to show.
+        #[arg(long, default_value_t = 10)]
+        count: usize
+    }
+}
+
+#[derive(Subcommand, Debug)]
+enum PropsCommands {
+    /// Prints a single property, or all properties if no key is given.
+    Get {
+        instance: String,
+        key: Option<String>
+    },
+    /// Validates and sets a property.
+    Set {
+        instance: String,
+        key: String,
+        value: String,
+
+        #[arg(long)]
+        force: bool
+    },
+    /// Compares the properties with another instance, or with the defaults of the instance's version.
+    Diff {
+        instance: String,
+        other: Option<String>
     }
 }
 
@@ -72,42 +379,375 @@ async fn main() -> Result<()> {
     let cli = Cli::parse();
 
     match cli.command {
-        Some(Commands::r#Use {version, paper}) => {
-            let server_type_string = bool_to_string(paper);
-            let server_type = ServerType::from_string(server_type_string)?;
+        Some(Commands::r#Use {version, paper, instance, force}) => {
+            let server_type = select_server_type(paper, cli.server_type.as_deref())?;
             let version = version.ok_or_else(|| anyhow!("No version provided, please specify a version."))?;
-            version_manager::use_version(&version, &server_type, &get_dir().await?)
-                .await?;
+            let mvm_dir = get_dir().await?;
+            match instance {
+                Some(instance) => {
+                    // An instance keeps the server type it was created with.
+                    if paper || cli.server_type.is_some() {
+                        return Err(anyhow!("--paper and --type cannot be used with --instance, the instance keeps its server type"));
+                    }
+                    instance::set_instance_version(&instance::get_instance_dir(&instance, &mvm_dir), &version, &mvm_dir, force)
+                        .await?;
+                }
+                None => version_manager::use_version(&version, &server_type, &mvm_dir)
+                    .await?
+            }
         }
 
-        Some(Commands::Install { version, paper}) => {
-            let server_type_string = bool_to_string(paper);
-            let server_type = ServerType::from_string(server_type_string)?;
+        Some(Commands::Install { version, paper, from, name, build}) => {
+            let server_type = select_server_type(paper, cli.server_type.as_deref())?;
+            if let Some(source) = from {
+                let name = name.unwrap_or(version);
+                if name == "latest" {
+                    return Err(anyhow!("Name the jar with --name, e.g. --name mypatch-1.21"));
+                }
+                version_manager::install_from(&source, &name, &server_type, &get_dir().await?)
+                    .await?;
+                return Ok(());
+            }
 
-            let download_url = version_manager::get_version_download(&version, &server_type)
+            let mvm_dir = get_dir().await?;
+            let version = version_manager::resolve_version(&version, &server_type, &mvm_dir)
                 .await?;
+            let download_url = match build {
+                Some(build) if server_type == ServerType::Purpur => version_manager::get_purpur_build_url(&version, build)
+                    .await?,
+                Some(_) => return Err(anyhow!("--build is only supported for purpur")),
+                None => version_manager::get_download_url(&version, &server_type, &mvm_dir)
+                    .await?
+            };
 
             println!("Found version, downloading...");
 
-            download_server_jar(download_url, &version, &server_type,&get_dir().await?)
+            download_server_jar(download_url, &version, &server_type, &mvm_dir)
                 .await?;
         }
 
         Some(Commands::Uninstall {version, paper}) => {
             let version = version.ok_or_else(|| anyhow!("No version provided, please specify a version."))?;
-            let server_type_string = bool_to_string(paper);
-            let server_type = ServerType::from_string(server_type_string)?;
+            let server_type = select_server_type(paper, cli.server_type.as_deref())?;
             version_manager::delete_server_jar(&version, &server_type, &get_dir().await?)
                 .await?;
         }
 
-        Some(Commands::Which {version, paper}) => {
-            let server_type_string = bool_to_string(paper);
-            let server_type = ServerType::from_string(server_type_string)?;
-            let path = version_manager::get_version(&version, &server_type, &get_dir().await?)
+        Some(Commands::Which {version, paper, verbose}) => {
+            let server_type = select_server_type(paper, cli.server_type.as_deref())?;
+            let mvm_dir = get_dir().await?;
+            let path = version_manager::get_version(&version, &server_type, &mvm_dir)
                 .await?;
 
             println!("{}", path);
+            // Installs of mod loaders are started with the arguments their installer wrote.
+            if let Some(launch) = read_launch_config(Path::new(&path).parent().unwrap_or(&mvm_dir))? {
+                println!("Launch: java {}", launch.args.join(" "));
+            }
+            if verbose {
+                let version = version_manager::resolve_version(&version, &server_type, &mvm_dir)
+                    .await?;
+                let (check_version, check_type, check_dir) = (version.clone(), server_type.clone(), mvm_dir.clone());
+                let (info, integrity) = tokio::task::spawn_blocking(move || -> Result<_> {
+                    let info = provenance::read_install_info(&check_version, &check_type, &check_dir)?;
+                    let integrity = provenance::check_integrity(&check_version, &check_type, &check_dir, false)?;
+                    Ok((info, integrity))
+                })
+                .await
+                .context("Failed to check the jar")??;
+
+                println!("Type: {}", server_type);
+                println!("Version: {}", version);
+                match info {
+                    Some(info) => {
+                        if let Some(build) = info.build {
+                            println!("Build: {}", build);
+                        }
+                        println!("Source: {}", info.source);
+                        println!("Installed: {} by mvm {}", info.installed.format("%Y-%m-%d %H:%M:%S"), info.mvm_version);
+                        println!("Size: {}", format_size(info.size));
+                        println!("SHA-1: {}", info.sha1);
+                        println!("SHA-256: {}", info.sha256);
+                    }
+                    None => println!("Source: unknown, installed without a record")
+                }
+                println!("Integrity: {}", integrity);
+            }
+        }
+
+        Some(Commands::List {paper}) => {
+            let server_type = select_server_type(paper, cli.server_type.as_deref())?;
+            let mvm_dir = get_dir().await?;
+            let recent = version_manager::resolve_version("recent", &server_type, &mvm_dir).await.ok();
+            let list_dir = mvm_dir.clone();
+            let list_type = server_type.clone();
+            let versions = tokio::task::spawn_blocking(move || -> Result<_> {
+                metadata::installed_versions(&list_type, &list_dir)?
+                    .into_iter()
+                    .map(|(version, version_metadata)| {
+                        let info = provenance::read_install_info(&version, &list_type, &list_dir).unwrap_or(None);
+                        let integrity = provenance::check_integrity(&version, &list_type, &list_dir, true)?;
+                        Ok((version, version_metadata, info, integrity))
+                    })
+                    .collect::<Result<Vec<_>>>()
+            })
+            .await
+            .context("Failed to list versions")??;
+            if versions.is_empty() {
+                println!("No {} versions installed", server_type);
+            }
+            for (version, version_metadata, info, integrity) in versions {
+                let marker = if recent.as_deref() == Some(version.as_str()) { "*" } else { " " };
+                let mut details = version_metadata.as_ref().map(format_metadata).into_iter().collect::<Vec<_>>();
+                if let Some(build) = info.and_then(|info| info.build) {
+                    details.push(format!("build {}", build));
+                }
+                if integrity != provenance::Integrity::Intact {
+                    details.push(integrity.to_string());
+                }
+                println!("{}", format!("{} {:<12} {}", marker, version, details.join(", ")).trim_end());
+            }
+        }
+
+        Some(Commands::Import {path, version, link, instance}) => {
+            let mvm_dir = get_dir().await?;
+            // Checked before the jar is imported, so that an invalid name does not leave a half done import.
+            if let Some(name) = instance.as_deref().filter(|name| !is_valid_name(name)) {
+                return Err(anyhow!("Invalid instance name {:?}, use lowercase letters, digits, '-' and '_'", name));
+            }
+            let (jar_path, server_dir) = if path.is_dir() {
+                (import::find_server_jar(&path)?, path.clone())
+            } else {
+                (path.clone(), path.parent().map(Path::to_path_buf).unwrap_or_default())
+            };
+
+            let import_dir = mvm_dir.clone();
+            let (server_type, version) = tokio::task::spawn_blocking(move || import::import_jar(&jar_path, &import_dir, version.as_deref(), link))
+                .await
+                .context("Failed to import the jar")??;
+            if let Some(name) = instance {
+                let server_dir = if server_dir.as_os_str().is_empty() { PathBuf::from(".") } else { server_dir };
+                import::adopt_instance(&server_dir, &name, &server_type, &version, &mvm_dir)
+                    .await?;
+            }
+        }
+
+        Some(Commands::Verify {redownload, offline}) => {
+            let mvm_dir = get_dir().await?;
+            let mut reports = verify::verify_jars(&mvm_dir, !offline)
+                .await?;
+            if reports.is_empty() {
+                println!("No versions installed");
+            }
+            for report in &reports {
+                println!("{}", format_jar_report(report));
+            }
+
+            if redownload {
+                for report in reports.iter_mut().filter(|report| report.is_failure()) {
+                    verify::redownload(report, &mvm_dir)
+                        .await?;
+                    *report = verify::verify_jar(&report.version, &report.server_type, &mvm_dir, !offline)
+                        .await?;
+                    println!("{}", format_jar_report(report));
+                }
+            }
+
+            let failures = reports.iter().filter(|report| report.is_failure()).count();
+            if failures > 0 {
+                return Err(anyhow!("{} of {} jars failed verification", failures, reports.len()));
+            }
+        }
+
+        Some(Commands::Info {version, paper}) => {
+            let server_type = select_server_type(paper, cli.server_type.as_deref())?;
+            let mvm_dir = get_dir().await?;
+            let version = version_manager::resolve_version(&version, &server_type, &mvm_dir)
+                .await?;
+            let (info_version, info_type, info_dir) = (version.clone(), server_type.clone(), mvm_dir.clone());
+            let version_metadata = tokio::task::spawn_blocking(move || metadata::version_metadata(&info_version, &info_type, &info_dir))
+                .await
+                .context("Failed to read the version metadata")??;
+
+            println!("{} {}", server_type, version);
+            println!("Path: {}", version_manager::get_server_jar_path(&version, &server_type, &mvm_dir).display());
+            match version_metadata {
+                Some(version_metadata) => {
+                    println!("Game version: {}", version_metadata.name);
+                    println!("Data version: {}", version_metadata.world_version);
+                    println!("Protocol version: {}", version_metadata.protocol_version);
+                    if let Some(java_version) = version_metadata.java_version {
+                        println!("Java version: {}", java_version);
+                    }
+                    if let Some(pack_version) = version_metadata.pack_version {
+                        println!("Pack version: {}", pack_version);
+                    }
+                }
+                None => println!("The jar does not record its data and protocol versions")
+            }
+        }
+
+        Some(Commands::Init {name, version, paper, accept_eula, first_boot}) => {
+            let server_type = select_server_type(paper, cli.server_type.as_deref())?;
+            let mvm_dir = get_dir().await?;
+            let instance_dir = instance::init_instance(&name, &version, &server_type, &mvm_dir)
+                .await?;
+
+            // Proxies run no Minecraft server, so the EULA does not apply to them.
+            if !server_type.is_proxy() {
+                if accept_eula || prompt_eula()? {
+                    instance::accept_eula(&instance_dir)
+                        .await?;
+                    println!("EULA accepted");
+                } else {
+                    println!("EULA not accepted, the server will not start until eula.txt is set to true ({})", instance::EULA_URL);
+                }
+            }
+
+            if first_boot {
+                instance::first_boot(&instance_dir, &mvm_dir)
+                    .await?;
+            }
+        }
+
+        Some(Commands::Props {command}) => {
+            props_command(command, &get_dir().await?)
+                .await?;
+        }
+
+        Some(Commands::Rcon {instance, command}) => {
+            let instance_dir = instance::get_instance_dir(&instance, &get_dir().await?);
+            let mut client = rcon::connect_instance(&instance_dir)
+                .await?;
+
+            if command.is_empty() {
+                rcon_console(&mut client)
+                    .await?;
+            } else {
+                let response = client.command(&command.join(" "))
+                    .await?;
+                println!("{}", response);
+            }
+        }
+
+        Some(Commands::Status {target, json}) => {
+            let mvm_dir = get_dir().await?;
+            let Some(target) = target else {
+                return print_daemon_status(&mvm_dir, json).await;
+            };
+
+            #[cfg(unix)]
+            if !json {
+                let instance_dir = instance::get_instance_dir(&target, &mvm_dir);
+                if let Some(supervised) = supervised_status(&mvm_dir, &instance_dir).await {
+                    println!("Supervisor: {}", format_supervised(&supervised));
+                }
+            }
+
+            let (host, port) = status::resolve_target(&target, &mvm_dir)
+                .await?;
+            let server_status = status::query_status(&host, port)
+                .await?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&server_status).context("Failed to serialize status")?);
+            } else {
+                println!("MOTD: {}", server_status.motd);
+                println!("Version: {} (protocol {})", server_status.version_name, server_status.protocol);
+                println!("Players: {}/{}", server_status.players_online, server_status.players_max);
+                println!("Latency: {} ms", server_status.latency_ms);
+            }
+        }
+
+        Some(Commands::Run {instance, force}) => {
+            let mvm_dir = get_dir().await?;
+            let instance_dir = instance::get_instance_dir(&instance, &mvm_dir);
+            let exit_status = instance::run_instance(&instance_dir, &mvm_dir, force)
+                .await?;
+            println!("Server exited with {}", exit_status);
+        }
+
+        Some(Commands::Stop {instance, countdown, message, timeout}) => {
+            let mvm_dir = get_dir().await?;
+            let instance_dir = instance::get_instance_dir(&instance, &mvm_dir);
+
+            #[cfg(unix)]
+            if let Some(supervised) = supervised_status(&mvm_dir, &instance_dir).await {
+                if supervised.state != InstanceState::Stopped {
+                    let request = Request::Stop {instance: supervised.instance, countdown, message, timeout_secs: timeout};
+                    print_daemon_response(daemon::client::request(&mvm_dir, &request).await?);
+                    return Ok(());
+                }
+            }
+
+            let options = stop::StopOptions {
+                countdown,
+                message,
+                timeout: Duration::from_secs(timeout),
+            };
+            stop::stop_instance(&instance_dir, &options)
+                .await?;
+        }
+
+        #[cfg(unix)]
+        Some(Commands::Daemon) => {
+            daemon::supervisor::run_daemon(&get_dir().await?)
+                .await?;
+        }
+
+        #[cfg(unix)]
+        Some(Commands::Start {instance}) => {
+            let mvm_dir = get_dir().await?;
+            let request = Request::Start {instance: canonical_instance_dir(&instance, &mvm_dir)?};
+            print_daemon_response(daemon::client::request(&mvm_dir, &request).await?);
+        }
+
+        #[cfg(unix)]
+        Some(Commands::Restart {instance, countdown, message, timeout}) => {
+            let mvm_dir = get_dir().await?;
+            let request = Request::Restart {
+                instance: canonical_instance_dir(&instance, &mvm_dir)?,
+                countdown,
+                message,
+                timeout_secs: timeout
+            };
+            print_daemon_response(daemon::client::request(&mvm_dir, &request).await?);
+        }
+
+        #[cfg(unix)]
+        Some(Commands::Attach {instance}) => {
+            let mvm_dir = get_dir().await?;
+            let instance_dir = instance::get_instance_dir(&instance, &mvm_dir);
+
+            let target = match supervised_status(&mvm_dir, &instance_dir).await {
+                Some(supervised) if supervised.state != InstanceState::Stopped => {
+                    let mut connection = daemon::client::DaemonConnection::connect(&mvm_dir).await?;
+                    connection.send(&Request::Attach {instance: supervised.instance.clone()}).await?;
+                    AttachTarget::Daemon {connection, instance: supervised.instance}
+                }
+                _ if instance_dir.join(process::CONSOLE_PIPE).exists() => AttachTarget::Pipe,
+                _ => return Err(anyhow!("The server of this instance is not running"))
+            };
+            attach::attach(&instance_dir, target)
+                .await?;
+        }
+        Some(Commands::Schedule {command}) => {
+            schedule_command(command, &get_dir().await?)
+                .await?;
+        }
+
+        Some(Commands::Backup {command}) => {
+            backup_command(command, &get_dir().await?)
+                .await?;
+        }
+
+        Some(Commands::Network {command}) => {
+            let backend_type = match cli.server_type {
+                Some(server_type) => ServerType::from_string(server_type)?,
+                None => ServerType::Paper
+            };
+            network_command(command, &backend_type, &get_dir().await?)
+                .await?;
         }
         None => {
             println!("Unknown command: {:?}", cli.command);
@@ -118,17 +758,502 @@ async fn main() -> Result<()> {
     Ok(())
 }
 
-/// Converts a boolean value to its corresponding server type string.
+/// Selects the server type a command manages.
+///
+/// # Arguments
+/// - `paper`: Whether `--paper` was given
+/// - `server_type`: The value of `--type`, which takes precedence
+///
+/// # Returns
+/// A `Result` containing the server type, Vanilla unless another one was asked for
+fn select_server_type(paper: bool, server_type: Option<&str>) -> Result<ServerType> {
+    match (server_type, paper) {
+        (Some(server_type), _) => ServerType::from_string(server_type.to_string()),
+        (None, true) => Ok(ServerType::Paper),
+        (None, false) => Ok(ServerType::Vanilla)
+    }
+}
+
+/// Runs a `props` subcommand against the `server.properties` of an instance.
+///
+/// # Arguments
+/// - `command`: The subcommand to run
+/// - `mvm_dir`: The root directory of server installations
+async fn props_command(command: PropsCommands, mvm_dir: &Path) -> Result<()> {
+    match command {
+        PropsCommands::Get {instance, key} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let server_properties = properties::read_properties(&instance_dir.join("server.properties"))
+                .await?;
+            match key {
+                Some(key) => {
+                    let value = server_properties.get(&key)
+                        .ok_or_else(|| anyhow!("Property '{}' is not set", key))?;
+                    println!("{}", value);
+                }
+                None => {
+                    for (key, value) in server_properties.entries() {
+                        println!("{}={}", key, value);
+                    }
+                }
+            }
+        }
+
+        PropsCommands::Set {instance, key, value, force} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let properties_path = instance_dir.join("server.properties");
+
+            if properties::find_property(&key).is_none() {
+                println!("Warning: '{}' is not a known server property", key);
+            } else if !force {
+                properties::validate(&key, &value)?;
+            }
+
+            let mut server_properties = if properties_path.exists() {
+                properties::read_properties(&properties_path).await?
+            } else {
+                properties::ServerProperties::default()
+            };
+            server_properties.set(&key, &value);
+            properties::write_properties(&properties_path, &server_properties)
+                .await?;
+
+            println!("{}={}", key, value);
+        }
+
+        PropsCommands::Diff {instance, other} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let left = properties::read_properties(&instance_dir.join("server.properties"))
+                .await?
+                .to_map();
+
+            let right = match &other {
+                Some(other) => {
+                    let other_dir = instance::get_instance_dir(other, mvm_dir);
+                    properties::read_properties(&other_dir.join("server.properties"))
+                        .await?
+                        .to_map()
+                }
+                None => {
+                    let version = instance::read_instance_config(&instance_dir)
+                        .await
+                        .ok()
+                        .map(|config| config.version);
+                    properties::default_properties(version.as_deref())
+                }
+            };
+
+            let right_name = other.as_deref().unwrap_or("defaults");
+            for diff in properties::diff_properties(&left, &right) {
+                // Keys missing on either side are filled in or ignored by the server, so only
+                // changed values are of interest when comparing against the defaults.
+                if other.is_none() && (diff.left.is_none() || diff.right.is_none()) {
+                    continue;
+                }
+                println!(
+                    "{}: {} ({}) -> {} ({})",
+                    diff.key,
+                    diff.left.as_deref().unwrap_or("<unset>"),
+                    instance,
+                    diff.right.as_deref().unwrap_or("<unset>"),
+                    right_name
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads commands from stdin and runs them over RCON until `exit` or the end of input.
+///
+/// # Arguments
+/// - `client`: The authenticated RCON client
+async fn rcon_console(client: &mut rcon::RconClient) -> Result<()> {
+    println!("Connected, type 'exit' to leave the console");
+    let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+
+    loop {
+        print!("> ");
+        io::stdout().flush().context("Failed to write prompt")?;
+
+        let Some(line) = lines.next_line().await.context("Failed to read command")? else {
+            break;
+        };
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+        if line == "exit" || line == "quit" {
+            break;
+        }
+
+        let response = client.command(line.trim_start_matches('/'))
+            .await?;
+        if !response.is_empty() {
+            println!("{}", response);
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a `schedule` subcommand.
+///
+/// # Arguments
+/// - `command`: The subcommand to run
+/// - `mvm_dir`: The root directory of server installations
+async fn schedule_command(command: ScheduleCommands, mvm_dir: &Path) -> Result<()> {
+    let (instance, count) = match &command {
+        ScheduleCommands::List {instance} => (instance, 1),
+        ScheduleCommands::Next {instance, count} => (instance, *count)
+    };
+    let instances = match instance {
+        Some(instance) => {
+            let instance_dir = instance::get_instance_dir(instance, mvm_dir);
+            let config = instance::read_instance_config(&instance_dir)
+                .await?;
+            vec![(instance_dir, config)]
+        }
+        None => instance::list_instances(mvm_dir).await
+    };
+    let instance_name = |instance_dir: &Path| instance_dir.file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    if instances.iter().all(|(_, config)| config.schedule.is_empty()) {
+        println!("No scheduled tasks, add [[schedule]] tables to instance.toml");
+        return Ok(());
+    }
+
+    let now = chrono::Local::now();
+    match command {
+        ScheduleCommands::List {..} => {
+            for (instance_dir, config) in &instances {
+                for task in &config.schedule {
+                    let next = task.cron.next_after(&now)
+                        .map(|time| time.format("%Y-%m-%d %H:%M").to_string())
+                        .unwrap_or_else(|| "never".to_string());
+                    println!("{}: {} [{}], next run {}", instance_name(instance_dir), task.label(), task.cron, next);
+                }
+            }
+        }
+        ScheduleCommands::Next {..} => {
+            for run in schedule::upcoming_runs(&instances, &now, count).into_iter().take(count) {
+                println!("{}  {}: {}", run.time.format("%Y-%m-%d %H:%M"), instance_name(&run.instance), run.task.label());
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    if !daemon::client::is_daemon_running(mvm_dir).await {
+        println!("The daemon is not running, scheduled tasks only run under `mvm daemon`");
+    }
+    Ok(())
+}
+
+/// Runs a `network` subcommand.
+///
+/// # Arguments
+/// - `command`: The subcommand to run
+/// - `backend_type`: The server type of the backends
+/// - `mvm_dir`: The root directory of server installations
+async fn network_command(command: NetworkCommands, backend_type: &ServerType, mvm_dir: &Path) -> Result<()> {
+    match command {
+        NetworkCommands::Create {name, backends, version, proxy_version, port, backend_port, accept_eula} => {
+            let created = network::create_network(&name, &proxy_version, &backends, backend_type, &version, port, backend_port, mvm_dir)
+                .await?;
+
+            println!("Proxy {:?} listens on port {}", created.proxy_dir, created.proxy_port);
+            for backend in &created.backends {
+                println!("Backend '{}' {:?} listens on port {} (RCON {}, query {})", backend.name, backend.instance_dir, backend.port, backend.rcon_port, backend.query_port);
+            }
+            println!("Modern forwarding is enabled with a shared secret in {}", network::SECRET_FILE);
+
+            if accept_eula || prompt_eula()? {
+                for backend in &created.backends {
+                    instance::accept_eula(&backend.instance_dir)
+                        .await?;
+                }
+                println!("EULA accepted");
+            } else {
+                println!("EULA not accepted, the backends will not start until their eula.txt is set to true ({})", instance::EULA_URL);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Runs a `backup` subcommand.
 ///
 /// # Arguments
-/// - `paper`: A boolean flag indicating whether the server type is Paper.
+/// - `command`: The subcommand to run
+/// - `mvm_dir`: The root directory of server installations
+async fn backup_command(command: BackupCommands, mvm_dir: &Path) -> Result<()> {
+    match command {
+        BackupCommands::Create {instance} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+
+            // A server under the daemon is only reachable through the daemon's console.
+            #[cfg(unix)]
+            if let Some(supervised) = supervised_status(mvm_dir, &instance_dir).await {
+                if supervised.state != InstanceState::Stopped {
+                    let request = Request::Backup {instance: supervised.instance};
+                    print_daemon_response(daemon::client::request(mvm_dir, &request).await?);
+                    return Ok(());
+                }
+            }
+
+            let console = match process::find_server_pid(&instance_dir).await {
+                Some(_) => {
+                    let console = stop::open_console_channel(&instance_dir).await;
+                    if console.is_none() {
+                        println!("Warning: the server is running but its console is unreachable, the backup may be inconsistent");
+                    }
+                    console
+                }
+                None => None
+            };
+            let backup = backup::backup_instance(&instance_dir, mvm_dir, "manual", console)
+                .await?;
+            println!("Created backup {} of {}, {} new", backup.manifest.id, format_size(backup.manifest.size), format_size(backup.manifest.added));
+        }
+        BackupCommands::List {instance: Some(instance)} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let backups = backup::list_backups(&instance_dir, mvm_dir)
+                .await?;
+            if backups.is_empty() {
+                println!("No backups of {:?}", instance_dir);
+            }
+            for backup in backups {
+                println!("{}", format_backup(&backup));
+            }
+        }
+        BackupCommands::List {instance: None} => {
+            let backups = backup::list_all_backups(mvm_dir)
+                .await?;
+            if backups.is_empty() {
+                println!("No backups found");
+            }
+            for backup in backups {
+                println!("{}/{}", backup.manifest.instance, format_backup(&backup));
+            }
+        }
+        BackupCommands::Restore {instance, backup, file: Some(file)} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let backup = backup::restore_file(&instance_dir, mvm_dir, &backup, &file)
+                .await?;
+            println!("Restored {} from backup {}", file, backup.manifest.id);
+        }
+        BackupCommands::Restore {instance, backup, file: None} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let backup = backup::restore_backup(&instance_dir, mvm_dir, &backup)
+                .await?;
+            println!("Restored backup {}", backup.manifest.id);
+
+            if let (Ok(config), Some(version)) = (instance::read_instance_config(&instance_dir).await, &backup.manifest.version) {
+                if config.version != *version {
+                    println!("The backup was taken on version {}, the instance uses {}", version, config.version);
+                }
+            }
+        }
+        BackupCommands::Prune {instance, keep_last, keep_daily, keep_weekly, dry_run} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let mut policy = instance::read_instance_config(&instance_dir)
+                .await
+                .map(|config| config.backup)
+                .unwrap_or_default();
+            policy.keep_last = keep_last.unwrap_or(policy.keep_last);
+            policy.keep_daily = keep_daily.unwrap_or(policy.keep_daily);
+            policy.keep_weekly = keep_weekly.unwrap_or(policy.keep_weekly);
+
+            let pruned = backup::prune_backups(&instance_dir, mvm_dir, &policy, dry_run)
+                .await?;
+            let verb = if dry_run { "Would delete" } else { "Deleted" };
+            for backup in &pruned {
+                println!("{} {}", verb, backup.manifest.id);
+            }
+            println!("{} {} backups", verb, pruned.len());
+
+            if !dry_run && !pruned.is_empty() {
+                let garbage = backup::collect_garbage(mvm_dir, false)
+                    .await?;
+                println!("Freed {} of chunks", format_size(garbage.bytes));
+            }
+        }
+        BackupCommands::Verify {instance, backup} => {
+            let instance_dir = instance::get_instance_dir(&instance, mvm_dir);
+            let backups = match backup {
+                Some(id) => vec![backup::find_backup(&instance_dir, mvm_dir, &id).await?],
+                None => backup::list_backups(&instance_dir, mvm_dir).await?
+            };
+
+            let report = backup::verify_backups(&backups, mvm_dir)
+                .await?;
+            for problem in &report.problems {
+                println!("{}", problem);
+            }
+            if !report.problems.is_empty() {
+                return Err(anyhow!("Found {} problems in {} chunks of {} backups", report.problems.len(), report.chunks, backups.len()));
+            }
+            println!("Checked {} chunks of {} backups, all intact", report.chunks, backups.len());
+        }
+        BackupCommands::Gc {dry_run} => {
+            let garbage = backup::collect_garbage(mvm_dir, dry_run)
+                .await?;
+            let verb = if dry_run { "Would delete" } else { "Deleted" };
+            println!("{} {} unused chunks, {}", verb, garbage.chunks, format_size(garbage.bytes));
+        }
+    }
+
+    Ok(())
+}
+
+/// Formats a backup as a single line for `backup list`.
+fn format_backup(backup: &backup::Backup) -> String {
+    let manifest = &backup.manifest;
+    let mut text = format!("{}: {}", manifest.id, manifest.reason);
+    if let (Some(server_type), Some(version)) = (&manifest.server_type, &manifest.version) {
+        text.push_str(&format!(", {} {}", server_type, version));
+    }
+    if let Some(data_version) = manifest.data_version {
+        text.push_str(&format!(", data version {}", data_version));
+    }
+    text.push_str(&format!(", {}", manifest.worlds.join(" ")));
+    text.push_str(&format!(", {} ({} new)", format_size(manifest.size), format_size(manifest.added)));
+    text
+}
+
+/// Formats a size in bytes for humans.
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    format!("{:.1} {}", size, UNITS[unit])
+}
+
+/// Summarizes the metadata of a server version for `mvm list`.
+fn format_metadata(version_metadata: &metadata::VersionMetadata) -> String {
+    let mut summary = format!("data {}, protocol {}", version_metadata.world_version, version_metadata.protocol_version);
+    if let Some(java_version) = version_metadata.java_version {
+        summary.push_str(&format!(", Java {}", java_version));
+    }
+    summary
+}
+
+/// Formats the outcome of verifying a jar as one line.
+fn format_jar_report(report: &verify::JarReport) -> String {
+    format!("{:<8} {:<12} {:<15} {}", report.server_type, report.version, report.integrity, report.details.join(", "))
+}
+
+/// Resolves an instance argument into the canonical directory the daemon identifies it by.
+#[cfg(unix)]
+fn canonical_instance_dir(instance: &str, mvm_dir: &Path) -> Result<PathBuf> {
+    let instance_dir = instance::get_instance_dir(instance, mvm_dir);
+    std::fs::canonicalize(&instance_dir)
+        .context(format!("No instance found at {:?}", instance_dir))
+}
+
+/// Asks a running daemon for the status of an instance.
 ///
 /// # Returns
-/// A `String` representation of the server type, either `"paper"` or `"vanilla"`.
+/// The status, or `None` if no daemon is running or the instance does not exist
+#[cfg(unix)]
+async fn supervised_status(mvm_dir: &Path, instance_dir: &Path) -> Option<InstanceStatus> {
+    if !daemon::client::is_daemon_running(mvm_dir).await {
+        return None;
+    }
+    let instance = std::fs::canonicalize(instance_dir).ok()?;
+    match daemon::client::request(mvm_dir, &Request::Status {instance: Some(instance)}).await {
+        Ok(Response::Status {instances}) => instances.into_iter().next().filter(|status| status.state != InstanceState::NotSupervised),
+        _ => None
+    }
+}
 
-fn bool_to_string(paper: bool) -> String {
-    match paper {
-        true => "paper".to_string(),
-        false => "vanilla".to_string()
+/// Formats the supervision status of an instance for display.
+#[cfg(unix)]
+fn format_supervised(status: &InstanceStatus) -> String {
+    let mut text = status.state.to_string();
+    if let Some(pid) = status.pid {
+        text.push_str(&format!(", pid {}", pid));
+    }
+    if let Some(uptime) = status.uptime_secs {
+        text.push_str(&format!(", up {}s", uptime));
     }
+    if status.restarts > 0 {
+        text.push_str(&format!(", {} restarts", status.restarts));
+    }
+    if let Some(last_exit) = &status.last_exit {
+        text.push_str(&format!(", last exit: {}", last_exit));
+    }
+    text
+}
+
+/// Prints a response of the daemon.
+#[cfg(unix)]
+fn print_daemon_response(response: Response) {
+    match response {
+        Response::Ok {message} | Response::Error {message} => {
+            if !message.is_empty() {
+                println!("{}", message);
+            }
+        }
+        Response::Status {instances} => {
+            for status in instances {
+                println!("{}: {}", status.instance.display(), format_supervised(&status));
+            }
+        }
+        Response::Output {line} => println!("{}", line)
+    }
+}
+
+/// Prints the status of every instance supervised by the daemon.
+///
+/// # Arguments
+/// - `mvm_dir`: The root directory of server installations
+/// - `json`: Whether to print the status as JSON
+#[cfg(unix)]
+async fn print_daemon_status(mvm_dir: &Path, json: bool) -> Result<()> {
+    let response = daemon::client::request(mvm_dir, &Request::Status {instance: None})
+        .await?;
+    if json {
+        if let Response::Status {instances} = &response {
+            println!("{}", serde_json::to_string_pretty(instances).context("Failed to serialize status")?);
+        }
+        return Ok(());
+    }
+    print_daemon_response(response);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn print_daemon_status(_mvm_dir: &Path, _json: bool) -> Result<()> {
+    Err(anyhow!("No target given, the daemon is only supported on Unix"))
+}
+
+/// Asks the user on the terminal whether they accept the Minecraft EULA.
+///
+/// # Returns
+/// `true` if the user answered yes, `false` if they declined or no terminal is attached.
+fn prompt_eula() -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return Ok(false);
+    }
+
+    print!("Do you accept the Minecraft EULA ({})? [y/N] ", instance::EULA_URL);
+    io::stdout().flush().context("Failed to write prompt")?;
+
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer).context("Failed to read answer")?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
 }
@@ -0,0 +1,267 @@
+//! Reads what installed server jars were built for.
+//!
+//! Server jars since 1.14 embed a `version.json` naming the game version along with its data
+//! version, network protocol version, required Java version and pack formats. mvm extracts it
+//! once per installed version into `<type>/versions/<version>/metadata.toml`, so that worlds and
+//! clients can be compared with servers without opening the jar again.
+
+use crate::server::loader::read_launch_config;
+use crate::server::server_types::ServerType;
+use crate::version_manager::get_server_jar_path;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::io::{Cursor, Read, Seek};
+use std::path::Path;
+use zip::ZipArchive;
+
+/// The file in server jars that describes the game version.
+const VERSION_FILE: &str = "version.json";
+
+/// The directory in which Paperclip jars bundle the actual server jar.
+const BUNDLED_VERSIONS_DIR: &str = "META-INF/versions/";
+
+/// The file next to `server.jar` the metadata is stored in.
+pub const METADATA_FILE: &str = "metadata.toml";
+
+/// What a server jar was built for.
+///
+/// # Fields
+/// - `name`: The name of the game version, e.g. `1.21.4`
+/// - `world_version`: The data version of the worlds the game writes
+/// - `protocol_version`: The network protocol version clients must speak
+/// - `java_version`: The major Java version the server requires, unknown before 1.17
+/// - `pack_version`: The resource and data pack formats of the game
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct VersionMetadata {
+    pub name: String,
+    pub world_version: i32,
+    pub protocol_version: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub java_version: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pack_version: Option<PackVersion>,
+}
+
+/// The resource and data pack formats of a game version, e.g. `46` or `69.0`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct PackVersion {
+    pub resource: String,
+    pub data: String,
+}
+
+impl Display for PackVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.resource == self.data {
+            write!(f, "{}", self.resource)
+        } else {
+            write!(f, "resource {}, data {}", self.resource, self.data)
+        }
+    }
+}
+
+/// The `pack_version` of `version.json`, which has changed shape over time.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawPackVersion {
+    /// Before 1.20.2 a single format shared by resource and data packs.
+    Single(i32),
+    /// Before 1.21.9 separate formats.
+    Split { resource: i32, data: i32 },
+    /// Since 1.21.9 formats with a minor version.
+    Minor { resource_major: i32, resource_minor: i32, data_major: i32, data_minor: i32 },
+}
+
+impl From<RawPackVersion> for PackVersion {
+    fn from(raw: RawPackVersion) -> Self {
+        match raw {
+            RawPackVersion::Single(version) => PackVersion { resource: version.to_string(), data: version.to_string() },
+            RawPackVersion::Split { resource, data } => PackVersion { resource: resource.to_string(), data: data.to_string() },
+            RawPackVersion::Minor { resource_major, resource_minor, data_major, data_minor } => PackVersion {
+                resource: format!("{}.{}", resource_major, resource_minor),
+                data: format!("{}.{}", data_major, data_minor),
+            },
+        }
+    }
+}
+
+/// The parts of `version.json` mvm reads.
+#[derive(Debug, Deserialize)]
+struct VersionFile {
+    name: String,
+    world_version: i32,
+    protocol_version: i32,
+    java_version: Option<u32>,
+    pack_version: Option<RawPackVersion>,
+}
+
+/// Reads the `version.json` of a jar, looking into the jar bundled by Paperclip if needed.
+fn read_version_file<R: Read + Seek>(archive: &mut ZipArchive<R>, depth: usize) -> Result<Option<VersionFile>> {
+    if let Ok(mut entry) = archive.by_name(VERSION_FILE) {
+        let mut content = String::new();
+        entry.read_to_string(&mut content)
+            .context(format!("Failed to read {}", VERSION_FILE))?;
+        let version = serde_json::from_str(&content)
+            .context(format!("Failed to parse {}", VERSION_FILE))?;
+        return Ok(Some(version));
+    }
+    if depth > 0 {
+        return Ok(None);
+    }
+
+    let bundled = archive.file_names()
+        .filter(|name| name.starts_with(BUNDLED_VERSIONS_DIR) && name.ends_with(".jar"))
+        .map(str::to_string)
+        .collect::<Vec<_>>();
+    for name in bundled {
+        let mut jar = Vec::new();
+        archive.by_name(&name)
+            .context(format!("Failed to open {}", name))?
+            .read_to_end(&mut jar)
+            .context(format!("Failed to read {}", name))?;
+        let mut bundled_archive = ZipArchive::new(Cursor::new(jar))
+            .context(format!("Failed to open {} as a jar", name))?;
+        if let Some(version) = read_version_file(&mut bundled_archive, depth + 1)? {
+            return Ok(Some(version));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Reads the metadata embedded in a server jar.
+///
+/// # Arguments
+/// - `jar_path`: The path of the server jar
+///
+/// # Returns
+/// A `Result` containing the metadata, or `None` for jars older than 1.14 which do not embed it
+pub fn read_jar_metadata(jar_path: &Path) -> Result<Option<VersionMetadata>> {
+    let file = File::open(jar_path)
+        .context(format!("Failed to open {:?}", jar_path))?;
+    let mut archive = ZipArchive::new(file)
+        .context(format!("Failed to open {:?} as a jar", jar_path))?;
+
+    let version = read_version_file(&mut archive, 0)
+        .context(format!("Failed to read the version of {:?}", jar_path))?;
+    Ok(version.map(|version| VersionMetadata {
+        name: version.name,
+        world_version: version.world_version,
+        protocol_version: version.protocol_version,
+        java_version: version.java_version,
+        pack_version: version.pack_version.map(PackVersion::from),
+    }))
+}
+
+/// Extracts the metadata of an installed server jar and stores it next to the jar.
+///
+/// # Arguments
+/// - `jar_path`: The path of the installed `server.jar`
+///
+/// # Returns
+/// A `Result` containing the metadata, or `None` if the jar does not embed it
+pub fn store_metadata(jar_path: &Path) -> Result<Option<VersionMetadata>> {
+    let metadata = read_jar_metadata(jar_path)?;
+    if let (Some(metadata), Some(version_dir)) = (&metadata, jar_path.parent()) {
+        let metadata_path = version_dir.join(METADATA_FILE);
+        let content = toml::to_string_pretty(metadata)
+            .context("Failed to serialize version metadata")?;
+        fs::write(&metadata_path, content)
+            .context(format!("Failed to write {:?}", metadata_path))?;
+    }
+
+    Ok(metadata)
+}
+
+/// Returns the metadata of an installed version, extracting it from the jar if it has not been
+/// stored yet, for example for versions installed by older releases of mvm.
+///
+/// # Arguments
+/// - `version`: The installed version
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the metadata, or `None` if the jar does not embed it
+pub fn version_metadata(version: &str, server_type: &ServerType, path: &Path) -> Result<Option<VersionMetadata>> {
+    let jar_path = get_server_jar_path(version, server_type, path);
+    if !jar_path.exists() {
+        return Err(anyhow!("Version '{}' is not installed", version));
+    }
+    // Installs of mod loaders keep only their installer, which embeds no game metadata.
+    if read_launch_config(jar_path.parent().unwrap_or(path))?.is_some() {
+        return Ok(None);
+    }
+
+    let metadata_path = jar_path.with_file_name(METADATA_FILE);
+    if let Ok(content) = fs::read_to_string(&metadata_path) {
+        if let Ok(metadata) = toml::from_str(&content) {
+            return Ok(Some(metadata));
+        }
+    }
+
+    store_metadata(&jar_path)
+}
+
+/// Lists the installed versions of a server type, ordered by their data version where known.
+///
+/// # Arguments
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing every installed version with its metadata, if the jar embeds it
+pub fn installed_versions(server_type: &ServerType, path: &Path) -> Result<Vec<(String, Option<VersionMetadata>)>> {
+    let versions_dir = path.join(server_type.to_string()).join("versions");
+    if !versions_dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut versions = Vec::new();
+    for entry in fs::read_dir(&versions_dir).context(format!("Failed to read {:?}", versions_dir))? {
+        let version = entry.context(format!("Failed to read {:?}", versions_dir))?
+            .file_name()
+            .to_string_lossy()
+            .to_string();
+        if !get_server_jar_path(&version, server_type, path).exists() {
+            continue;
+        }
+        // An unreadable jar is still installed, it is listed without metadata.
+        let metadata = version_metadata(&version, server_type, path).unwrap_or(None);
+        versions.push((version, metadata));
+    }
+
+    versions.sort_by(|(a, a_metadata), (b, b_metadata)| {
+        let a_world = a_metadata.as_ref().map(|metadata| metadata.world_version);
+        let b_world = b_metadata.as_ref().map(|metadata| metadata.world_version);
+        a_world.cmp(&b_world).then_with(|| compare_version_names(a, b))
+    });
+    Ok(versions)
+}
+
+/// Orders version names by their numbers where they differ, so that `3.10.0` comes after `3.4.0`
+/// also for versions that are not game versions.
+pub fn compare_version_names(a: &str, b: &str) -> std::cmp::Ordering {
+    let split = |name: &str| {
+        let mut parts: Vec<(bool, String)> = Vec::new();
+        for c in name.chars() {
+            match parts.last_mut() {
+                Some((digits, part)) if *digits == c.is_ascii_digit() => part.push(c),
+                _ => parts.push((c.is_ascii_digit(), c.to_string()))
+            }
+        }
+        parts
+    };
+
+    for (a_part, b_part) in split(a).into_iter().zip(split(b)) {
+        let ordering = match (&a_part, &b_part) {
+            ((true, a_number), (true, b_number)) => a_number.parse::<u64>().ok().cmp(&b_number.parse::<u64>().ok()),
+            _ => a_part.1.cmp(&b_part.1)
+        };
+        if ordering != std::cmp::Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.len().cmp(&b.len()).then_with(|| a.cmp(b))
+}
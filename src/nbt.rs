@@ -0,0 +1,198 @@
+//! Reads Minecraft's Named Binary Tag (NBT) format.
+//!
+//! Only reading is supported, which is all mvm needs to inspect files such as `level.dat`.
+//! Strings are decoded leniently, since Java's modified UTF-8 only differs from UTF-8 for
+//! characters that do not appear in the data mvm reads.
+
+use anyhow::{anyhow, Context, Result};
+use flate2::read::GzDecoder;
+use std::collections::BTreeMap;
+use std::io::Read;
+use std::path::Path;
+
+/// The deepest nesting of lists and compounds accepted, to reject malicious files early.
+const MAX_DEPTH: usize = 512;
+
+/// A single NBT value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    List(Vec<Tag>),
+    Compound(BTreeMap<String, Tag>),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+impl Tag {
+    /// Returns a child of a compound tag.
+    pub fn get(&self, key: &str) -> Option<&Tag> {
+        match self {
+            Tag::Compound(entries) => entries.get(key),
+            _ => None
+        }
+    }
+
+    /// Follows a path of keys through nested compound tags.
+    pub fn get_path(&self, path: &[&str]) -> Option<&Tag> {
+        path.iter().try_fold(self, |tag, key| tag.get(key))
+    }
+
+    /// Returns the value of an integer tag of any size.
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            Tag::Byte(value) => Some(*value as i64),
+            Tag::Short(value) => Some(*value as i64),
+            Tag::Int(value) => Some(*value as i64),
+            Tag::Long(value) => Some(*value),
+            _ => None
+        }
+    }
+
+    /// Returns the value of a string tag.
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None
+        }
+    }
+
+    /// Returns the elements of a list tag.
+    pub fn as_list(&self) -> Option<&[Tag]> {
+        match self {
+            Tag::List(values) => Some(values),
+            _ => None
+        }
+    }
+}
+
+/// Reads an uncompressed NBT document.
+///
+/// # Arguments
+/// - `reader`: The source of the document
+///
+/// # Returns
+/// A `Result` containing the name and value of the root tag if successful
+pub fn read<R: Read>(reader: &mut R) -> Result<(String, Tag)> {
+    let tag_type = read_u8(reader)?;
+    if tag_type == 0 {
+        return Err(anyhow!("The NBT document is empty"));
+    }
+    let name = read_string(reader)?;
+    let tag = read_payload(reader, tag_type, 0)?;
+
+    Ok((name, tag))
+}
+
+/// Reads a gzip compressed NBT file such as `level.dat`.
+///
+/// # Arguments
+/// - `path`: The path of the file
+///
+/// # Returns
+/// A `Result` containing the root tag if successful
+pub fn read_gzip_file(path: &Path) -> Result<Tag> {
+    let file = std::fs::File::open(path)
+        .context(format!("Failed to open {:?}", path))?;
+    let mut decoder = GzDecoder::new(std::io::BufReader::new(file));
+
+    read(&mut decoder)
+        .map(|(_, tag)| tag)
+        .context(format!("Failed to read NBT from {:?}", path))
+}
+
+/// Reads the payload of a tag of the given type.
+fn read_payload<R: Read>(reader: &mut R, tag_type: u8, depth: usize) -> Result<Tag> {
+    if depth > MAX_DEPTH {
+        return Err(anyhow!("NBT nesting is deeper than {}", MAX_DEPTH));
+    }
+
+    let tag = match tag_type {
+        1 => Tag::Byte(read_bytes::<1, R>(reader)?[0] as i8),
+        2 => Tag::Short(i16::from_be_bytes(read_bytes(reader)?)),
+        3 => Tag::Int(i32::from_be_bytes(read_bytes(reader)?)),
+        4 => Tag::Long(i64::from_be_bytes(read_bytes(reader)?)),
+        5 => Tag::Float(f32::from_be_bytes(read_bytes(reader)?)),
+        6 => Tag::Double(f64::from_be_bytes(read_bytes(reader)?)),
+        7 => {
+            let length = read_length(reader)?;
+            // Read through `take` so that a corrupt length cannot allocate gigabytes up front.
+            let mut bytes = Vec::new();
+            reader.by_ref().take(length as u64).read_to_end(&mut bytes).context("Failed to read NBT data")?;
+            if bytes.len() != length {
+                return Err(anyhow!("Unexpected end of NBT data"));
+            }
+            Tag::ByteArray(bytes.into_iter().map(|byte| byte as i8).collect())
+        }
+        8 => Tag::String(read_string(reader)?),
+        9 => {
+            let element_type = read_u8(reader)?;
+            let length = read_length(reader)?;
+            let mut values = Vec::new();
+            for _ in 0..length {
+                values.push(read_payload(reader, element_type, depth + 1)?);
+            }
+            Tag::List(values)
+        }
+        10 => {
+            let mut entries = BTreeMap::new();
+            loop {
+                let entry_type = read_u8(reader)?;
+                if entry_type == 0 {
+                    break;
+                }
+                let name = read_string(reader)?;
+                entries.insert(name, read_payload(reader, entry_type, depth + 1)?);
+            }
+            Tag::Compound(entries)
+        }
+        11 => {
+            let length = read_length(reader)?;
+            let mut values = Vec::new();
+            for _ in 0..length {
+                values.push(i32::from_be_bytes(read_bytes(reader)?));
+            }
+            Tag::IntArray(values)
+        }
+        12 => {
+            let length = read_length(reader)?;
+            let mut values = Vec::new();
+            for _ in 0..length {
+                values.push(i64::from_be_bytes(read_bytes(reader)?));
+            }
+            Tag::LongArray(values)
+        }
+        other => return Err(anyhow!("Unknown NBT tag type {}", other))
+    };
+
+    Ok(tag)
+}
+
+fn read_bytes<const N: usize, R: Read>(reader: &mut R) -> Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    reader.read_exact(&mut bytes).context("Unexpected end of NBT data")?;
+    Ok(bytes)
+}
+
+fn read_u8<R: Read>(reader: &mut R) -> Result<u8> {
+    Ok(read_bytes::<1, R>(reader)?[0])
+}
+
+/// Reads the length of an array or list, which is a signed integer in NBT.
+fn read_length<R: Read>(reader: &mut R) -> Result<usize> {
+    let length = i32::from_be_bytes(read_bytes(reader)?);
+    usize::try_from(length).map_err(|_| anyhow!("Negative NBT length {}", length))
+}
+
+fn read_string<R: Read>(reader: &mut R) -> Result<String> {
+    let length = u16::from_be_bytes(read_bytes(reader)?) as usize;
+    let mut bytes = vec![0u8; length];
+    reader.read_exact(&mut bytes).context("Unexpected end of NBT data")?;
+    Ok(String::from_utf8_lossy(&bytes).to_string())
+}
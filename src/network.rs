@@ -0,0 +1,296 @@
+//! Sets up proxy networks: a Velocity proxy in front of backend servers.
+//!
+//! `mvm network create` creates the proxy and backend instances together and writes the
+//! configuration that has to agree between them: every backend gets a port, the proxy lists the
+//! backends in its `velocity.toml`, and a shared secret for Velocity's modern forwarding is stored
+//! in the proxy's `forwarding.secret` and every backend's `config/paper-global.yml`. Backends run
+//! with `online-mode=false`, since the proxy authenticates players and forwards their identity.
+//! Every backend also gets its own RCON and query port, so that enabling them does not make the
+//! backends compete for the defaults.
+//!
+//! The proxy instance is called `<network>-proxy` and the backends `<network>-<backend>`.
+
+use crate::instance::init_instance;
+use crate::version_manager::resolve_version;
+use crate::properties::{read_properties, write_properties, ServerProperties};
+use crate::server::server_types::{is_valid_name, ServerType};
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeSet;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+/// The file in the proxy directory the forwarding secret is stored in.
+pub const SECRET_FILE: &str = "forwarding.secret";
+
+/// The Paper configuration backends read the forwarding secret from, relative to their directory.
+pub const PAPER_GLOBAL_FILE: &str = "config/paper-global.yml";
+
+/// The first port tried for the proxy unless another one is given.
+pub const DEFAULT_PROXY_PORT: u16 = 25565;
+
+/// The first port tried for backends unless another one is given.
+pub const DEFAULT_BACKEND_PORT: u16 = 30066;
+
+/// A backend server of a network.
+///
+/// # Fields
+/// - `name`: The name the proxy knows the backend by
+/// - `instance_dir`: The directory of the backend instance
+/// - `port`: The port the backend listens on
+/// - `rcon_port`: The port RCON listens on once `enable-rcon` is turned on
+/// - `query_port`: The port the query protocol listens on once `enable-query` is turned on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backend {
+    pub name: String,
+    pub instance_dir: PathBuf,
+    pub port: u16,
+    pub rcon_port: u16,
+    pub query_port: u16,
+}
+
+/// A network created by `mvm network create`.
+///
+/// # Fields
+/// - `proxy_dir`: The directory of the proxy instance
+/// - `proxy_port`: The port players connect to
+/// - `backends`: The backend servers, the first one is where players join
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    pub proxy_dir: PathBuf,
+    pub proxy_port: u16,
+    pub backends: Vec<Backend>,
+}
+
+/// Backend names that cannot be used: `try` is the key of the join order under `[servers]` in
+/// `velocity.toml`, and `proxy` would name the backend instance like the proxy instance.
+pub const RESERVED_BACKEND_NAMES: &[&str] = &["try", "proxy"];
+
+/// Generates a random forwarding secret.
+pub fn generate_secret() -> Result<String> {
+    let mut bytes = [0u8; 24];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|err| anyhow!("Failed to generate the forwarding secret: {}", err))?;
+    Ok(hex::encode(bytes))
+}
+
+/// Collects the ports the existing instances are configured to listen on: the game port of
+/// servers, their query and RCON ports where those are enabled, and the bind port of proxies.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+pub async fn used_ports(path: &Path) -> BTreeSet<u16> {
+    let mut ports = BTreeSet::new();
+    let Ok(mut entries) = fs::read_dir(path.join("instances")).await else {
+        return ports;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let instance_dir = entry.path();
+        if let Ok(properties) = read_properties(&instance_dir.join("server.properties")).await {
+            let configured = [("server-port", None, "25565"), ("query.port", Some("enable-query"), "25565"), ("rcon.port", Some("enable-rcon"), "25575")];
+            for (key, enabled_by, default) in configured {
+                if enabled_by.is_some_and(|enabled_by| properties.get(enabled_by) != Some("true")) {
+                    continue;
+                }
+                if let Ok(port) = properties.get(key).unwrap_or(default).parse() {
+                    ports.insert(port);
+                }
+            }
+        }
+        if let Ok(content) = fs::read_to_string(instance_dir.join("velocity.toml")).await {
+            let bind = toml::from_str::<toml::Table>(&content).ok()
+                .and_then(|config| config.get("bind").and_then(|bind| bind.as_str()).map(str::to_string));
+            if let Some(port) = bind.and_then(|bind| bind.rsplit_once(':').and_then(|(_, port)| port.parse().ok())) {
+                ports.insert(port);
+            }
+        }
+    }
+    ports
+}
+
+/// Picks free ports, skipping those in use by instances or by other programs on this host.
+///
+/// # Arguments
+/// - `count`: How many ports to pick
+/// - `start`: The first port to try
+/// - `used`: The ports the instances are configured with
+///
+/// # Returns
+/// A `Result` containing the ports in ascending order
+pub fn allocate_ports(count: usize, start: u16, used: &BTreeSet<u16>) -> Result<Vec<u16>> {
+    let ports = (start..=u16::MAX)
+        .filter(|port| !used.contains(port) && TcpListener::bind(("0.0.0.0", *port)).is_ok())
+        .take(count)
+        .collect::<Vec<_>>();
+    if ports.len() < count {
+        return Err(anyhow!("Not enough free ports from {}", start));
+    }
+    Ok(ports)
+}
+
+/// Renders the `velocity.toml` of the proxy.
+///
+/// # Arguments
+/// - `port`: The port players connect to
+/// - `backends`: The backends, the first one is where players join
+pub fn velocity_config(port: u16, backends: &[Backend]) -> String {
+    let mut servers = toml::Table::new();
+    for backend in backends {
+        servers.insert(backend.name.clone(), toml::Value::String(format!("127.0.0.1:{}", backend.port)));
+    }
+    let first = backends.iter().take(1).map(|backend| toml::Value::String(backend.name.clone())).collect();
+    servers.insert("try".to_string(), toml::Value::Array(first));
+
+    let mut config = toml::Table::new();
+    config.insert("config-version".to_string(), "2.7".into());
+    config.insert("bind".to_string(), format!("0.0.0.0:{}", port).into());
+    config.insert("online-mode".to_string(), true.into());
+    config.insert("player-info-forwarding-mode".to_string(), "modern".into());
+    config.insert("forwarding-secret-file".to_string(), SECRET_FILE.into());
+    config.insert("servers".to_string(), toml::Value::Table(servers));
+    config.insert("forced-hosts".to_string(), toml::Value::Table(toml::Table::new()));
+
+    format!("# Written by mvm network create.\n{}", toml::to_string(&config).unwrap_or_default())
+}
+
+/// Renders the `paper-global.yml` enabling Velocity's modern forwarding on a backend.
+/// Paper fills in its other settings when it starts.
+///
+/// # Arguments
+/// - `secret`: The forwarding secret shared with the proxy
+pub fn paper_forwarding_config(secret: &str) -> String {
+    format!("proxies:\n  velocity:\n    enabled: true\n    online-mode: true\n    secret: '{}'\n", secret)
+}
+
+/// Creates a Velocity proxy and its backend instances and connects them.
+///
+/// # Arguments
+/// - `name`: The name of the network
+/// - `proxy_version`: The Velocity version of the proxy, or "latest"
+/// - `backend_names`: The names of the backends, players join the first one
+/// - `backend_type`: The server type of the backends, which has to support modern forwarding
+/// - `backend_version`: The version of the backends, or "latest"
+/// - `proxy_port`: The port players connect to, or `None` for the first free one from
+///   `DEFAULT_PROXY_PORT`
+/// - `backend_port`: The first port tried for backends
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the created network if successful
+#[allow(clippy::too_many_arguments)]
+pub async fn create_network(name: &str, proxy_version: &str, backend_names: &[String], backend_type: &ServerType, backend_version: &str, proxy_port: Option<u16>, backend_port: u16, path: &Path) -> Result<Network> {
+    if !matches!(backend_type, ServerType::Paper | ServerType::Folia | ServerType::Purpur) {
+        return Err(anyhow!("{} servers do not support Velocity's modern forwarding, use paper, folia or purpur backends", backend_type));
+    }
+    if backend_names.is_empty() {
+        return Err(anyhow!("A network needs at least one backend"));
+    }
+    if let Some(invalid) = std::iter::once(name).chain(backend_names.iter().map(String::as_str)).find(|name| !is_valid_name(name)) {
+        return Err(anyhow!("Invalid name {:?}, use lowercase letters, digits, '-' and '_'", invalid));
+    }
+    if let Some(reserved) = backend_names.iter().find(|backend| RESERVED_BACKEND_NAMES.contains(&backend.as_str())) {
+        return Err(anyhow!("{:?} cannot be used as a backend name", reserved));
+    }
+    if backend_names.iter().collect::<BTreeSet<_>>().len() < backend_names.len() {
+        return Err(anyhow!("Backend names have to be unique"));
+    }
+
+    let instances_dir = path.join("instances");
+    let proxy_name = format!("{}-proxy", name);
+    let instance_names = std::iter::once(proxy_name.clone())
+        .chain(backend_names.iter().map(|backend| format!("{}-{}", name, backend)));
+    for instance_name in instance_names {
+        if instances_dir.join(&instance_name).exists() {
+            return Err(anyhow!("Instance '{}' already exists", instance_name));
+        }
+    }
+
+    let proxy_version = resolve_version(proxy_version, &ServerType::Velocity, path)
+        .await?;
+    let backend_version = resolve_version(backend_version, backend_type, path)
+        .await?;
+    let mut used = used_ports(path).await;
+    let proxy_port = match proxy_port {
+        Some(port) if used.contains(&port) => return Err(anyhow!("Port {} is already used by another instance", port)),
+        Some(port) => port,
+        None => allocate_ports(1, DEFAULT_PROXY_PORT, &used)?[0]
+    };
+    used.insert(proxy_port);
+    // Every backend gets a game, RCON and query port.
+    let ports = allocate_ports(backend_names.len() * 3, backend_port, &used)?;
+    let secret = generate_secret()?;
+
+    let mut created = Vec::new();
+    let result = create_instances(name, &proxy_name, &proxy_version, proxy_port, backend_names, backend_type, &backend_version, &ports, &secret, path, &mut created)
+        .await;
+    if result.is_err() {
+        // Leftover instances would make creating the network again fail as already existing.
+        for instance_dir in created {
+            let _ = fs::remove_dir_all(instance_dir).await;
+        }
+    }
+    let (proxy_dir, backends) = result?;
+
+    Ok(Network { proxy_dir, proxy_port, backends })
+}
+
+/// Creates and configures the proxy and backend instances of a network, recording each created
+/// instance in `created` so that they can be removed again if a later step fails.
+///
+/// # Returns
+/// A `Result` containing the directory of the proxy and the backends
+#[allow(clippy::too_many_arguments)]
+async fn create_instances(name: &str, proxy_name: &str, proxy_version: &str, proxy_port: u16, backend_names: &[String], backend_type: &ServerType, backend_version: &str, ports: &[u16], secret: &str, path: &Path, created: &mut Vec<PathBuf>) -> Result<(PathBuf, Vec<Backend>)> {
+    let proxy_dir = init_instance(proxy_name, proxy_version, &ServerType::Velocity, path)
+        .await?;
+    created.push(proxy_dir.clone());
+    let mut backends = Vec::new();
+    for (backend_name, ports) in backend_names.iter().zip(ports.chunks(3)) {
+        let instance_dir = init_instance(&format!("{}-{}", name, backend_name), backend_version, backend_type, path)
+            .await?;
+        created.push(instance_dir.clone());
+        let (port, rcon_port, query_port) = (ports[0], ports[1], ports[2]);
+
+        let mut properties = ServerProperties::default();
+        properties.set("server-port", &port.to_string());
+        properties.set("rcon.port", &rcon_port.to_string());
+        properties.set("query.port", &query_port.to_string());
+        properties.set("online-mode", "false");
+        write_properties(&instance_dir.join("server.properties"), &properties)
+            .await?;
+
+        let paper_global = instance_dir.join(PAPER_GLOBAL_FILE);
+        fs::create_dir_all(instance_dir.join("config"))
+            .await
+            .context(format!("Failed to create {:?}", instance_dir.join("config")))?;
+        fs::write(&paper_global, paper_forwarding_config(secret))
+            .await
+            .context(format!("Failed to write {:?}", paper_global))?;
+
+        backends.push(Backend { name: backend_name.clone(), instance_dir, port, rcon_port, query_port });
+    }
+
+    let velocity_path = proxy_dir.join("velocity.toml");
+    fs::write(&velocity_path, velocity_config(proxy_port, &backends))
+        .await
+        .context(format!("Failed to write {:?}", velocity_path))?;
+    write_secret(&proxy_dir.join(SECRET_FILE), secret)
+        .await?;
+    Ok((proxy_dir, backends))
+}
+
+/// Writes the forwarding secret readable only by the owner.
+async fn write_secret(secret_path: &Path, secret: &str) -> Result<()> {
+    fs::write(secret_path, secret)
+        .await
+        .context(format!("Failed to write {:?}", secret_path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(secret_path, std::fs::Permissions::from_mode(0o600))
+            .await
+            .context(format!("Failed to restrict the permissions of {:?}", secret_path))?;
+    }
+    Ok(())
+}
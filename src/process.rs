@@ -0,0 +1,218 @@
+//! Tracks the server processes of instances.
+//!
+//! `mvm run` records the process id of a server in `mvm.pid` and creates a named pipe,
+//! `console.pipe`, whose lines are forwarded to the server's console.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+
+/// The file inside an instance directory holding the process id of its running server.
+pub const PID_FILE: &str = "mvm.pid";
+
+/// The named pipe inside an instance directory that feeds the console of its running server.
+pub const CONSOLE_PIPE: &str = "console.pipe";
+
+/// How often a process is checked while waiting for it to exit.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Signals that can be sent to a server process.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Signal {
+    Terminate,
+    Kill,
+}
+
+/// Records the process id of a running server.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `pid`: The process id of the server
+pub async fn write_pid_file(instance_dir: &Path, pid: u32) -> Result<()> {
+    fs::write(instance_dir.join(PID_FILE), pid.to_string())
+        .await
+        .context("Failed to write to mvm.pid file")
+}
+
+/// Removes the process id record of an instance, ignoring a missing file.
+pub async fn remove_pid_file(instance_dir: &Path) -> Result<()> {
+    match fs::remove_file(instance_dir.join(PID_FILE)).await {
+        Ok(()) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err).context("Failed to remove mvm.pid file")
+    }
+}
+
+/// Finds the process id of the running server of an instance.
+///
+/// Uses `mvm.pid` if it refers to a live process. On Linux, servers started outside of mvm are
+/// found by looking for a Java process whose working directory is the instance directory.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+///
+/// # Returns
+/// The process id, or `None` if no running server was found
+pub async fn find_server_pid(instance_dir: &Path) -> Option<u32> {
+    if let Ok(content) = fs::read_to_string(instance_dir.join(PID_FILE)).await {
+        if let Ok(pid) = content.trim().parse::<u32>() {
+            if is_running(pid) {
+                return Some(pid);
+            }
+        }
+    }
+
+    find_java_process_in(instance_dir).await
+}
+
+/// Looks for a Java process running in the given directory.
+#[cfg(target_os = "linux")]
+async fn find_java_process_in(dir: &Path) -> Option<u32> {
+    let dir = fs::canonicalize(dir).await.ok()?;
+    let mut entries = fs::read_dir("/proc").await.ok()?;
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let Some(pid) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        let Ok(cwd) = fs::read_link(entry.path().join("cwd")).await else {
+            continue;
+        };
+        if cwd != dir {
+            continue;
+        }
+        let Ok(cmdline) = fs::read(entry.path().join("cmdline")).await else {
+            continue;
+        };
+        let is_java = cmdline.split(|byte| *byte == 0)
+            .next()
+            .map(|program| String::from_utf8_lossy(program).ends_with("java"))
+            .unwrap_or(false);
+        if is_java && is_running(pid) {
+            return Some(pid);
+        }
+    }
+
+    None
+}
+
+#[cfg(not(target_os = "linux"))]
+async fn find_java_process_in(_dir: &Path) -> Option<u32> {
+    None
+}
+
+/// Returns whether a process is alive. Zombie processes count as exited.
+#[cfg(unix)]
+pub fn is_running(pid: u32) -> bool {
+    // SAFETY: signal 0 only checks whether the process exists and may be signalled.
+    let alive = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0
+        || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM);
+    if !alive {
+        return false;
+    }
+
+    match std::fs::read_to_string(format!("/proc/{}/stat", pid)) {
+        Ok(stat) => stat.rsplit_once(')')
+            .map(|(_, rest)| !rest.trim_start().starts_with('Z'))
+            .unwrap_or(true),
+        Err(_) => true
+    }
+}
+
+#[cfg(not(unix))]
+pub fn is_running(_pid: u32) -> bool {
+    false
+}
+
+/// Sends a signal to a process.
+#[cfg(unix)]
+pub fn send_signal(pid: u32, signal: Signal) -> Result<()> {
+    let signal_number = match signal {
+        Signal::Terminate => libc::SIGTERM,
+        Signal::Kill => libc::SIGKILL,
+    };
+    // SAFETY: kill has no memory safety requirements.
+    if unsafe { libc::kill(pid as libc::pid_t, signal_number) } != 0 {
+        return Err(std::io::Error::last_os_error()).context(format!("Failed to send {:?} to process {}", signal, pid));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn send_signal(pid: u32, signal: Signal) -> Result<()> {
+    Err(anyhow::anyhow!("Sending {:?} to process {} is only supported on Unix", signal, pid))
+}
+
+/// Waits until a process exits.
+///
+/// # Arguments
+/// - `pid`: The process id to wait for
+/// - `timeout`: How long to wait at most
+///
+/// # Returns
+/// `true` if the process exited within the timeout
+pub async fn wait_for_exit(pid: u32, timeout: Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    while is_running(pid) {
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    true
+}
+
+/// Creates the console pipe of an instance, replacing a stale one.
+///
+/// # Returns
+/// A `Result` containing the path of the pipe if successful
+#[cfg(unix)]
+pub fn create_console_pipe(instance_dir: &Path) -> Result<PathBuf> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let pipe_path = instance_dir.join(CONSOLE_PIPE);
+    if pipe_path.exists() {
+        std::fs::remove_file(&pipe_path).context("Failed to remove stale console pipe")?;
+    }
+
+    let c_path = std::ffi::CString::new(pipe_path.as_os_str().as_bytes())
+        .context("Invalid console pipe path")?;
+    // SAFETY: c_path is a valid null-terminated string for the duration of the call.
+    if unsafe { libc::mkfifo(c_path.as_ptr(), 0o600) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("Failed to create console pipe");
+    }
+    Ok(pipe_path)
+}
+
+#[cfg(not(unix))]
+pub fn create_console_pipe(_instance_dir: &Path) -> Result<PathBuf> {
+    Err(anyhow::anyhow!("Console pipes are only supported on Unix"))
+}
+
+/// Writes a command to the console pipe of a running instance.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `command`: The console command to send
+#[cfg(unix)]
+pub fn send_to_console_pipe(instance_dir: &Path, command: &str) -> Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let pipe_path = instance_dir.join(CONSOLE_PIPE);
+    // Opening without blocking fails when nobody reads the pipe, i.e. `mvm run` is not running.
+    let mut pipe = std::fs::OpenOptions::new()
+        .write(true)
+        .custom_flags(libc::O_NONBLOCK)
+        .open(&pipe_path)
+        .context(format!("No server is reading the console pipe at {:?}", pipe_path))?;
+
+    pipe.write_all(format!("{}\n", command).as_bytes())
+        .context("Failed to write to the console pipe")
+}
+
+#[cfg(not(unix))]
+pub fn send_to_console_pipe(_instance_dir: &Path, _command: &str) -> Result<()> {
+    Err(anyhow::anyhow!("Console pipes are only supported on Unix"))
+}
@@ -0,0 +1,444 @@
+//! Reads and writes `server.properties` files.
+//!
+//! The file uses the Java properties format. Parsing keeps every comment, blank line and the order
+//! of entries so that a file written back only differs in the values that were changed. The module
+//! also knows the keys understood by the server, their value types and their defaults per game version.
+
+use anyhow::{anyhow, Context, Result};
+use std::collections::BTreeMap;
+use std::path::Path;
+use tokio::fs;
+
+/// The type of value a known property accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PropertyKind {
+    Bool,
+    Int { min: i64, max: i64 },
+    Text,
+    Choice(&'static [&'static str]),
+}
+
+/// Describes a property understood by the server.
+///
+/// # Fields
+/// - `key`: The name of the property.
+/// - `kind`: The type of value the property accepts.
+/// - `since`: The first game version knowing the property, empty if it always existed.
+/// - `defaults`: The default values, each paired with the first game version using it.
+#[derive(Debug)]
+pub struct PropertyDef {
+    pub key: &'static str,
+    pub kind: PropertyKind,
+    pub since: &'static str,
+    pub defaults: &'static [(&'static str, &'static str)],
+}
+
+const PORT: PropertyKind = PropertyKind::Int { min: 1, max: 65535 };
+const PERMISSION_LEVEL: PropertyKind = PropertyKind::Int { min: 1, max: 4 };
+const DIFFICULTIES: &[&str] = &["peaceful", "easy", "normal", "hard", "0", "1", "2", "3"];
+const GAMEMODES: &[&str] = &["survival", "creative", "adventure", "spectator", "0", "1", "2", "3"];
+const COMPRESSIONS: &[&str] = &["deflate", "lz4", "none"];
+
+/// The properties understood by the server, sorted by key.
+pub const KNOWN_PROPERTIES: &[PropertyDef] = &[
+    PropertyDef { key: "accepts-transfers", kind: PropertyKind::Bool, since: "1.20.5", defaults: &[("", "false")] },
+    PropertyDef { key: "allow-flight", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "allow-nether", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "broadcast-console-to-ops", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "broadcast-rcon-to-ops", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "bug-report-link", kind: PropertyKind::Text, since: "1.21", defaults: &[("", "")] },
+    PropertyDef { key: "difficulty", kind: PropertyKind::Choice(DIFFICULTIES), since: "", defaults: &[("", "1"), ("1.14", "easy")] },
+    PropertyDef { key: "enable-command-block", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "enable-jmx-monitoring", kind: PropertyKind::Bool, since: "1.16", defaults: &[("", "false")] },
+    PropertyDef { key: "enable-query", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "enable-rcon", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "enable-status", kind: PropertyKind::Bool, since: "1.16", defaults: &[("", "true")] },
+    PropertyDef { key: "enforce-secure-profile", kind: PropertyKind::Bool, since: "1.19", defaults: &[("", "true")] },
+    PropertyDef { key: "enforce-whitelist", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "entity-broadcast-range-percentage", kind: PropertyKind::Int { min: 10, max: 1000 }, since: "1.16", defaults: &[("", "100")] },
+    PropertyDef { key: "force-gamemode", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "function-permission-level", kind: PERMISSION_LEVEL, since: "1.14.4", defaults: &[("", "2")] },
+    PropertyDef { key: "gamemode", kind: PropertyKind::Choice(GAMEMODES), since: "", defaults: &[("", "0"), ("1.14", "survival")] },
+    PropertyDef { key: "generate-structures", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "generator-settings", kind: PropertyKind::Text, since: "", defaults: &[("", ""), ("1.19", "{}")] },
+    PropertyDef { key: "hardcore", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "hide-online-players", kind: PropertyKind::Bool, since: "1.18", defaults: &[("", "false")] },
+    PropertyDef { key: "initial-disabled-packs", kind: PropertyKind::Text, since: "1.19.3", defaults: &[("", "")] },
+    PropertyDef { key: "initial-enabled-packs", kind: PropertyKind::Text, since: "1.19.3", defaults: &[("", "vanilla")] },
+    PropertyDef { key: "level-name", kind: PropertyKind::Text, since: "", defaults: &[("", "world")] },
+    PropertyDef { key: "level-seed", kind: PropertyKind::Text, since: "", defaults: &[("", "")] },
+    PropertyDef { key: "level-type", kind: PropertyKind::Text, since: "", defaults: &[("", "default"), ("1.19", "minecraft:normal")] },
+    PropertyDef { key: "log-ips", kind: PropertyKind::Bool, since: "1.20.2", defaults: &[("", "true")] },
+    PropertyDef { key: "max-chained-neighbor-updates", kind: PropertyKind::Int { min: i32::MIN as i64, max: i32::MAX as i64 }, since: "1.19", defaults: &[("", "1000000")] },
+    PropertyDef { key: "max-players", kind: PropertyKind::Int { min: 0, max: i32::MAX as i64 }, since: "", defaults: &[("", "20")] },
+    PropertyDef { key: "max-tick-time", kind: PropertyKind::Int { min: -1, max: i64::MAX }, since: "", defaults: &[("", "60000")] },
+    PropertyDef { key: "max-world-size", kind: PropertyKind::Int { min: 1, max: 29999984 }, since: "", defaults: &[("", "29999984")] },
+    PropertyDef { key: "motd", kind: PropertyKind::Text, since: "", defaults: &[("", "A Minecraft Server")] },
+    PropertyDef { key: "network-compression-threshold", kind: PropertyKind::Int { min: -1, max: i32::MAX as i64 }, since: "", defaults: &[("", "256")] },
+    PropertyDef { key: "online-mode", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "op-permission-level", kind: PERMISSION_LEVEL, since: "", defaults: &[("", "4")] },
+    PropertyDef { key: "player-idle-timeout", kind: PropertyKind::Int { min: 0, max: i32::MAX as i64 }, since: "", defaults: &[("", "0")] },
+    PropertyDef { key: "prevent-proxy-connections", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "pvp", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "query.port", kind: PORT, since: "", defaults: &[("", "25565")] },
+    PropertyDef { key: "rate-limit", kind: PropertyKind::Int { min: 0, max: i32::MAX as i64 }, since: "", defaults: &[("", "0")] },
+    PropertyDef { key: "rcon.password", kind: PropertyKind::Text, since: "", defaults: &[("", "")] },
+    PropertyDef { key: "rcon.port", kind: PORT, since: "", defaults: &[("", "25575")] },
+    PropertyDef { key: "region-file-compression", kind: PropertyKind::Choice(COMPRESSIONS), since: "1.20.5", defaults: &[("", "deflate")] },
+    PropertyDef { key: "require-resource-pack", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+    PropertyDef { key: "resource-pack", kind: PropertyKind::Text, since: "", defaults: &[("", "")] },
+    PropertyDef { key: "resource-pack-id", kind: PropertyKind::Text, since: "1.20.3", defaults: &[("", "")] },
+    PropertyDef { key: "resource-pack-prompt", kind: PropertyKind::Text, since: "", defaults: &[("", "")] },
+    PropertyDef { key: "resource-pack-sha1", kind: PropertyKind::Text, since: "", defaults: &[("", "")] },
+    PropertyDef { key: "server-ip", kind: PropertyKind::Text, since: "", defaults: &[("", "")] },
+    PropertyDef { key: "server-port", kind: PORT, since: "", defaults: &[("", "25565")] },
+    PropertyDef { key: "simulation-distance", kind: PropertyKind::Int { min: 3, max: 32 }, since: "1.18", defaults: &[("", "10")] },
+    PropertyDef { key: "spawn-animals", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "spawn-monsters", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "spawn-npcs", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "spawn-protection", kind: PropertyKind::Int { min: 0, max: i32::MAX as i64 }, since: "", defaults: &[("", "16")] },
+    PropertyDef { key: "sync-chunk-writes", kind: PropertyKind::Bool, since: "1.16", defaults: &[("", "true")] },
+    PropertyDef { key: "text-filtering-config", kind: PropertyKind::Text, since: "1.16.4", defaults: &[("", "")] },
+    PropertyDef { key: "use-native-transport", kind: PropertyKind::Bool, since: "", defaults: &[("", "true")] },
+    PropertyDef { key: "view-distance", kind: PropertyKind::Int { min: 2, max: 32 }, since: "", defaults: &[("", "10")] },
+    PropertyDef { key: "white-list", kind: PropertyKind::Bool, since: "", defaults: &[("", "false")] },
+];
+
+/// A single line of a properties file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Line {
+    /// A comment or blank line, kept verbatim.
+    Verbatim(String),
+    /// A key and its value. `raw` holds the original text until the value is changed.
+    Entry { key: String, value: String, raw: Option<String> },
+}
+
+/// A parsed `server.properties` file.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ServerProperties {
+    lines: Vec<Line>,
+}
+
+/// A key whose value differs between two sets of properties.
+///
+/// # Fields
+/// - `key`: The name of the property.
+/// - `left`: The value on the left side, `None` if the key is missing.
+/// - `right`: The value on the right side, `None` if the key is missing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PropertyDiff {
+    pub key: String,
+    pub left: Option<String>,
+    pub right: Option<String>,
+}
+
+impl ServerProperties {
+    /// Parses the content of a properties file.
+    pub fn parse(content: &str) -> Self {
+        let mut lines = Vec::new();
+        let mut physical = content.lines();
+
+        while let Some(line) = physical.next() {
+            let trimmed = line.trim_start();
+            if trimmed.is_empty() || trimmed.starts_with('#') || trimmed.starts_with('!') {
+                lines.push(Line::Verbatim(line.to_string()));
+                continue;
+            }
+
+            let mut raw = line.to_string();
+            let mut logical = trimmed.to_string();
+            while ends_with_continuation(&logical) {
+                logical.pop();
+                match physical.next() {
+                    Some(next) => {
+                        raw.push('\n');
+                        raw.push_str(next);
+                        logical.push_str(next.trim_start());
+                    }
+                    None => break
+                }
+            }
+
+            let (key, value) = split_entry(&logical);
+            lines.push(Line::Entry { key, value, raw: Some(raw) });
+        }
+
+        ServerProperties { lines }
+    }
+
+    /// Returns the value of a key, if present.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.lines.iter().rev().find_map(|line| match line {
+            Line::Entry { key: entry_key, value, .. } if entry_key == key => Some(value.as_str()),
+            _ => None
+        })
+    }
+
+    /// Sets the value of a key. Existing keys keep their position, new keys are appended.
+    pub fn set(&mut self, key: &str, value: &str) {
+        let existing = self.lines.iter_mut().rev().find_map(|line| match line {
+            Line::Entry { key: entry_key, value, raw } if entry_key == key => Some((value, raw)),
+            _ => None
+        });
+
+        match existing {
+            Some((existing_value, raw)) => {
+                if existing_value != value {
+                    *existing_value = value.to_string();
+                    *raw = None;
+                }
+            }
+            None => self.lines.push(Line::Entry { key: key.to_string(), value: value.to_string(), raw: None })
+        }
+    }
+
+    /// Returns the host to reach the server at from this machine: its `server-ip`, or the loopback
+    /// address if it is bound to every interface.
+    pub fn local_host(&self) -> &str {
+        match self.get("server-ip") {
+            Some(ip) if !ip.is_empty() && ip != "0.0.0.0" && ip != "::" => ip,
+            _ => "127.0.0.1"
+        }
+    }
+
+    /// Returns all entries in file order.
+    pub fn entries(&self) -> Vec<(&str, &str)> {
+        self.lines.iter().filter_map(|line| match line {
+            Line::Entry { key, value, .. } => Some((key.as_str(), value.as_str())),
+            Line::Verbatim(_) => None
+        }).collect()
+    }
+
+    /// Returns all entries as a map, later duplicates winning like they do in Java.
+    pub fn to_map(&self) -> BTreeMap<String, String> {
+        self.entries().into_iter().map(|(key, value)| (key.to_string(), value.to_string())).collect()
+    }
+}
+
+impl std::fmt::Display for ServerProperties {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for line in &self.lines {
+            match line {
+                Line::Verbatim(text) => writeln!(f, "{}", text)?,
+                Line::Entry { raw: Some(raw), .. } => writeln!(f, "{}", raw)?,
+                Line::Entry { key, value, raw: None } => writeln!(f, "{}={}", escape(key, true), escape(value, false))?
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns whether a logical line continues on the next physical line,
+/// which is the case when it ends with an odd number of backslashes.
+fn ends_with_continuation(line: &str) -> bool {
+    line.chars().rev().take_while(|c| *c == '\\').count() % 2 == 1
+}
+
+/// Splits a logical line into its unescaped key and value.
+fn split_entry(line: &str) -> (String, String) {
+    let mut key_end = line.len();
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '=' || c == ':' || c.is_whitespace() {
+            key_end = index;
+            break;
+        }
+    }
+
+    let key = &line[..key_end];
+    let mut rest = line[key_end..].trim_start_matches(|c: char| c.is_whitespace());
+    if rest.starts_with('=') || rest.starts_with(':') {
+        rest = rest[1..].trim_start_matches(|c: char| c.is_whitespace());
+    }
+
+    (unescape(key), unescape(rest))
+}
+
+/// Resolves the escape sequences of the properties format.
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => result.push('\t'),
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => {
+                let code: String = chars.by_ref().take(4).collect();
+                match u32::from_str_radix(&code, 16).ok().and_then(char::from_u32) {
+                    Some(decoded) => result.push(decoded),
+                    None => {
+                        result.push_str("\\u");
+                        result.push_str(&code);
+                    }
+                }
+            }
+            Some(other) => result.push(other),
+            None => {}
+        }
+    }
+    result
+}
+
+/// Escapes a key or value the way Java's `Properties.store` does.
+fn escape(text: &str, is_key: bool) -> String {
+    let mut result = String::with_capacity(text.len());
+    for (index, c) in text.chars().enumerate() {
+        match c {
+            '\\' => result.push_str("\\\\"),
+            '\t' => result.push_str("\\t"),
+            '\n' => result.push_str("\\n"),
+            '\r' => result.push_str("\\r"),
+            '\u{c}' => result.push_str("\\f"),
+            '=' | ':' | '#' | '!' => {
+                result.push('\\');
+                result.push(c);
+            }
+            ' ' if is_key || index == 0 => result.push_str("\\ "),
+            c if (c as u32) < 0x20 || (c as u32) > 0x7e => {
+                let mut units = [0u16; 2];
+                for unit in c.encode_utf16(&mut units) {
+                    result.push_str(&format!("\\u{:04X}", unit));
+                }
+            }
+            c => result.push(c)
+        }
+    }
+    result
+}
+
+/// Parses a release version such as `1.20.4` into its numeric components.
+/// Returns `None` for snapshots and other ids that are not release versions.
+fn parse_game_version(version: &str) -> Option<Vec<u32>> {
+    version.split('.').map(|part| part.parse::<u32>().ok()).collect()
+}
+
+/// Returns whether `version` is at least `since`. Unknown versions are treated as newer than
+/// every release so that snapshots get the most recent defaults.
+fn version_at_least(version: Option<&str>, since: &str) -> bool {
+    if since.is_empty() {
+        return true;
+    }
+    match (version.and_then(parse_game_version), parse_game_version(since)) {
+        (Some(version), Some(since)) => version >= since,
+        _ => true
+    }
+}
+
+/// Looks up a known property by key.
+pub fn find_property(key: &str) -> Option<&'static PropertyDef> {
+    KNOWN_PROPERTIES.iter().find(|def| def.key == key)
+}
+
+/// Returns the default value of a known property for a game version.
+///
+/// # Arguments
+/// - `key`: The name of the property
+/// - `version`: The game version, `None` for the most recent defaults
+///
+/// # Returns
+/// The default value, or `None` if the key is unknown or does not exist in that version
+pub fn default_value(key: &str, version: Option<&str>) -> Option<&'static str> {
+    let def = find_property(key)?;
+    if !version_at_least(version, def.since) {
+        return None;
+    }
+
+    def.defaults.iter()
+        .rev()
+        .find(|(since, _)| version_at_least(version, since))
+        .map(|(_, value)| *value)
+}
+
+/// Returns the default properties a server of the given game version generates.
+///
+/// # Arguments
+/// - `version`: The game version, `None` for the most recent defaults
+pub fn default_properties(version: Option<&str>) -> BTreeMap<String, String> {
+    KNOWN_PROPERTIES.iter()
+        .filter_map(|def| default_value(def.key, version).map(|value| (def.key.to_string(), value.to_string())))
+        .collect()
+}
+
+/// Validates a value for a property. Unknown keys accept any value.
+///
+/// # Arguments
+/// - `key`: The name of the property
+/// - `value`: The value to validate
+pub fn validate(key: &str, value: &str) -> Result<()> {
+    let Some(def) = find_property(key) else {
+        return Ok(());
+    };
+
+    match def.kind {
+        PropertyKind::Bool => {
+            if value != "true" && value != "false" {
+                return Err(anyhow!("'{}' expects true or false, got '{}'", key, value));
+            }
+        }
+        PropertyKind::Int { min, max } => {
+            let number = value.parse::<i64>()
+                .map_err(|_| anyhow!("'{}' expects a whole number, got '{}'", key, value))?;
+            if number < min || number > max {
+                return Err(anyhow!("'{}' expects a number between {} and {}, got {}", key, min, max, number));
+            }
+        }
+        PropertyKind::Choice(choices) => {
+            if !choices.contains(&value) {
+                return Err(anyhow!("'{}' expects one of {}, got '{}'", key, choices.join(", "), value));
+            }
+        }
+        PropertyKind::Text => {}
+    }
+    Ok(())
+}
+
+/// Compares two sets of properties and returns every key whose value differs, sorted by key.
+pub fn diff_properties(left: &BTreeMap<String, String>, right: &BTreeMap<String, String>) -> Vec<PropertyDiff> {
+    let mut keys: Vec<&String> = left.keys().chain(right.keys()).collect();
+    keys.sort();
+    keys.dedup();
+
+    keys.into_iter()
+        .filter(|key| left.get(*key) != right.get(*key))
+        .map(|key| PropertyDiff {
+            key: key.clone(),
+            left: left.get(key).cloned(),
+            right: right.get(key).cloned(),
+        })
+        .collect()
+}
+
+/// Reads a `server.properties` file.
+///
+/// # Arguments
+/// - `path`: The path of the properties file
+///
+/// # Returns
+/// A `Result` containing the parsed properties if successful
+pub async fn read_properties(path: &Path) -> Result<ServerProperties> {
+    let content = fs::read_to_string(path)
+        .await
+        .context(format!("Failed to read {:?}", path))?;
+    Ok(ServerProperties::parse(&content))
+}
+
+/// Writes a `server.properties` file.
+///
+/// # Arguments
+/// - `path`: The path of the properties file
+/// - `properties`: The properties to write
+pub async fn write_properties(path: &Path, properties: &ServerProperties) -> Result<()> {
+    fs::write(path, properties.to_string())
+        .await
+        .context(format!("Failed to write to {:?}", path))
+}
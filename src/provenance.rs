@@ -0,0 +1,225 @@
+//! Records where installed server jars came from.
+//!
+//! Every install writes an `install.toml` next to its `server.jar` recording the download URL,
+//! the resolved version and Paper or Purpur build, the hashes and size of the jar, when it was installed and
+//! by which version of mvm. The hashes let mvm notice jars that were changed after the install.
+
+use crate::server::server_types::ServerType;
+use crate::version_manager::get_server_jar_path;
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::Read;
+use std::path::Path;
+
+/// The file next to `server.jar` the provenance is stored in.
+pub const INSTALL_FILE: &str = "install.toml";
+
+/// Where an installed server jar came from.
+///
+/// # Fields
+/// - `source`: The URL the jar was downloaded from
+/// - `server_type`: The type of server
+/// - `version`: The version the jar was installed as, with "latest" resolved
+/// - `build`: The Paper or Purpur build of the jar
+/// - `sha1`: The hex encoded SHA-1 hash of the jar
+/// - `sha256`: The hex encoded SHA-256 hash of the jar
+/// - `size`: The size of the jar in bytes
+/// - `installed`: When the jar was installed
+/// - `mvm_version`: The version of mvm that installed the jar
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct InstallInfo {
+    pub source: String,
+    pub server_type: ServerType,
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build: Option<u32>,
+    pub sha1: String,
+    pub sha256: String,
+    pub size: u64,
+    pub installed: DateTime<Local>,
+    pub mvm_version: String,
+}
+
+/// The result of comparing an installed jar with its recorded provenance.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Integrity {
+    /// The jar matches the recorded hashes.
+    Intact,
+    /// The jar differs from what was installed.
+    Modified,
+    /// The jar has no recorded provenance, it was not installed by mvm or before mvm recorded it.
+    UnknownOrigin,
+    /// The `server.jar` does not exist.
+    Missing,
+}
+
+impl std::fmt::Display for Integrity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let str = match self {
+            Integrity::Intact => "intact",
+            Integrity::Modified => "modified",
+            Integrity::UnknownOrigin => "unknown origin",
+            Integrity::Missing => "missing"
+        };
+        write!(f, "{}", str)
+    }
+}
+
+/// The hashes and size of a file.
+///
+/// # Fields
+/// - `sha1`: The hex encoded SHA-1 hash
+/// - `sha256`: The hex encoded SHA-256 hash
+/// - `md5`: The hex encoded MD5 hash
+/// - `size`: The size in bytes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileHashes {
+    pub sha1: String,
+    pub sha256: String,
+    pub md5: String,
+    pub size: u64,
+}
+
+/// Hashes a file with SHA-1, which Mojang publishes, SHA-256, which Paper publishes, and MD5,
+/// which Purpur publishes.
+///
+/// # Arguments
+/// - `file_path`: The path of the file
+///
+/// # Returns
+/// A `Result` containing the hashes and size of the file if it can be read
+pub fn hash_file(file_path: &Path) -> Result<FileHashes> {
+    let mut file = File::open(file_path)
+        .context(format!("Failed to open {:?}", file_path))?;
+    let mut sha1 = Sha1::new();
+    let mut sha256 = Sha256::new();
+    let mut md5 = Md5::new();
+    let mut size = 0;
+    let mut buffer = vec![0; 64 * 1024];
+    loop {
+        let read = file.read(&mut buffer)
+            .context(format!("Failed to read {:?}", file_path))?;
+        if read == 0 {
+            break;
+        }
+        sha1.update(&buffer[..read]);
+        sha256.update(&buffer[..read]);
+        md5.update(&buffer[..read]);
+        size += read as u64;
+    }
+
+    Ok(FileHashes { sha1: hex::encode(sha1.finalize()), sha256: hex::encode(sha256.finalize()), md5: hex::encode(md5.finalize()), size })
+}
+
+/// Extracts the build number from a download URL of PaperMC's API such as
+/// `.../versions/1.21.4/builds/232/downloads/paper-1.21.4-232.jar`.
+pub fn paper_build(url: &str) -> Option<u32> {
+    let mut parts = url.split('/');
+    parts.by_ref().find(|part| *part == "builds")?;
+    parts.next()?.parse().ok()
+}
+
+/// Extracts the build number from a Purpur download URL such as
+/// `.../v2/purpur/1.21.4/2416/download`.
+pub fn purpur_build(url: &str) -> Option<u32> {
+    let mut parts = url.split('/');
+    parts.by_ref().find(|part| *part == "purpur")?;
+    parts.nth(1)?.parse().ok()
+}
+
+/// Records the provenance of a freshly installed jar in the `install.toml` next to it.
+///
+/// # Arguments
+/// - `jar_path`: The path of the installed `server.jar`
+/// - `source`: Where the jar came from
+/// - `server_type`: The type of server
+/// - `version`: The version the jar is installed as
+/// - `build`: The Paper or Purpur build of the jar, if known
+///
+/// # Returns
+/// A `Result` containing the recorded provenance if successful
+pub fn record_install(jar_path: &Path, source: &str, server_type: &ServerType, version: &str, build: Option<u32>) -> Result<InstallInfo> {
+    let hashes = hash_file(jar_path)?;
+    let info = InstallInfo {
+        source: source.to_string(),
+        server_type: server_type.clone(),
+        version: version.to_string(),
+        build,
+        sha1: hashes.sha1,
+        sha256: hashes.sha256,
+        size: hashes.size,
+        installed: Local::now(),
+        mvm_version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let install_path = jar_path.with_file_name(INSTALL_FILE);
+    let content = toml::to_string_pretty(&info)
+        .context("Failed to serialize install record")?;
+    fs::write(&install_path, content)
+        .context(format!("Failed to write {:?}", install_path))?;
+
+    Ok(info)
+}
+
+/// Reads the recorded provenance of an installed version.
+///
+/// # Arguments
+/// - `version`: The installed version
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the provenance, or `None` if none was recorded
+pub fn read_install_info(version: &str, server_type: &ServerType, path: &Path) -> Result<Option<InstallInfo>> {
+    let install_path = get_server_jar_path(version, server_type, path).with_file_name(INSTALL_FILE);
+    let content = match fs::read_to_string(&install_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).context(format!("Failed to read {:?}", install_path))
+    };
+
+    let info = toml::from_str(&content)
+        .context(format!("Failed to parse {:?}", install_path))?;
+    Ok(Some(info))
+}
+
+/// Checks an installed jar against its recorded provenance.
+///
+/// # Arguments
+/// - `version`: The installed version
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+/// - `quick`: Whether to only compare the size instead of hashing the jar
+///
+/// # Returns
+/// A `Result` containing the integrity of the jar
+pub fn check_integrity(version: &str, server_type: &ServerType, path: &Path, quick: bool) -> Result<Integrity> {
+    let jar_path = get_server_jar_path(version, server_type, path);
+    if !jar_path.exists() {
+        return Ok(Integrity::Missing);
+    }
+    let Some(info) = read_install_info(version, server_type, path)? else {
+        return Ok(Integrity::UnknownOrigin);
+    };
+
+    let size = fs::metadata(&jar_path)
+        .context(format!("Failed to read {:?}", jar_path))?
+        .len();
+    if size != info.size {
+        return Ok(Integrity::Modified);
+    }
+    if quick {
+        return Ok(Integrity::Intact);
+    }
+
+    let hashes = hash_file(&jar_path)?;
+    if hashes.sha1 != info.sha1 || hashes.sha256 != info.sha256 {
+        return Ok(Integrity::Modified);
+    }
+    Ok(Integrity::Intact)
+}
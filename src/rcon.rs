@@ -0,0 +1,216 @@
+//! A client for the Source RCON protocol used by Minecraft servers for remote console access.
+//!
+//! Connection settings are read from the `enable-rcon`, `rcon.port`, `rcon.password` and
+//! `server-ip` entries of an instance's `server.properties`.
+
+use crate::properties::read_properties;
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Packet type of a command response.
+pub const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+/// Packet type of a command request. Shares its value with the authentication response.
+pub const SERVERDATA_EXECCOMMAND: i32 = 2;
+/// Packet type of an authentication response.
+pub const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+/// Packet type of an authentication request.
+pub const SERVERDATA_AUTH: i32 = 3;
+
+/// The largest command body the Minecraft server accepts.
+const MAX_COMMAND_LENGTH: usize = 1446;
+/// The largest packet accepted from the server, guarding against corrupt length fields.
+const MAX_PACKET_LENGTH: i32 = 1024 * 1024;
+
+/// A single RCON packet.
+///
+/// # Fields
+/// - `id`: The request id chosen by the client and echoed by the server.
+/// - `packet_type`: The type of the packet.
+/// - `body`: The command or response text.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconPacket {
+    pub id: i32,
+    pub packet_type: i32,
+    pub body: String,
+}
+
+impl RconPacket {
+    /// Encodes the packet into its wire format: a little-endian length, id and type followed by
+    /// the null-terminated body and an empty null-terminated string.
+    pub fn encode(&self) -> Vec<u8> {
+        let length = 4 + 4 + self.body.len() + 2;
+        let mut bytes = Vec::with_capacity(4 + length);
+        bytes.extend_from_slice(&(length as i32).to_le_bytes());
+        bytes.extend_from_slice(&self.id.to_le_bytes());
+        bytes.extend_from_slice(&self.packet_type.to_le_bytes());
+        bytes.extend_from_slice(self.body.as_bytes());
+        bytes.extend_from_slice(&[0, 0]);
+        bytes
+    }
+
+    /// Reads a single packet from a stream.
+    pub async fn read_from<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Self> {
+        let length = reader.read_i32_le()
+            .await
+            .context("Failed to read RCON packet length")?;
+        if !(10..=MAX_PACKET_LENGTH).contains(&length) {
+            return Err(anyhow!("Invalid RCON packet length {}", length));
+        }
+
+        let mut payload = vec![0u8; length as usize];
+        reader.read_exact(&mut payload)
+            .await
+            .context("Failed to read RCON packet")?;
+
+        let id = i32::from_le_bytes([payload[0], payload[1], payload[2], payload[3]]);
+        let packet_type = i32::from_le_bytes([payload[4], payload[5], payload[6], payload[7]]);
+        let body = &payload[8..];
+        let body_end = body.iter().position(|byte| *byte == 0).unwrap_or(body.len());
+
+        Ok(RconPacket {
+            id,
+            packet_type,
+            body: String::from_utf8_lossy(&body[..body_end]).into_owned(),
+        })
+    }
+}
+
+/// An authenticated RCON connection.
+pub struct RconClient {
+    stream: TcpStream,
+    next_id: i32,
+}
+
+impl RconClient {
+    /// Connects to an RCON server and authenticates with the given password.
+    ///
+    /// # Arguments
+    /// - `address`: The `host:port` of the RCON server
+    /// - `password`: The RCON password
+    ///
+    /// # Returns
+    /// A `Result` containing the authenticated client if successful
+    pub async fn connect(address: &str, password: &str) -> Result<Self> {
+        let stream = TcpStream::connect(address)
+            .await
+            .context(format!("Failed to connect to RCON at {}", address))?;
+        let mut client = RconClient { stream, next_id: 1 };
+
+        let id = client.send(SERVERDATA_AUTH, password).await?;
+        loop {
+            let packet = RconPacket::read_from(&mut client.stream).await?;
+            if packet.packet_type != SERVERDATA_AUTH_RESPONSE {
+                continue;
+            }
+            if packet.id == -1 {
+                return Err(anyhow!("RCON authentication failed, check rcon.password"));
+            }
+            if packet.id == id {
+                return Ok(client);
+            }
+        }
+    }
+
+    /// Runs a command and returns its full response.
+    ///
+    /// Responses longer than a single packet are split by the server, so an extra request is sent
+    /// after the command and response packets are collected until the reply to it arrives.
+    ///
+    /// # Arguments
+    /// - `command`: The command to run, without a leading slash
+    ///
+    /// # Returns
+    /// A `Result` containing the response text if successful
+    pub async fn command(&mut self, command: &str) -> Result<String> {
+        if command.len() > MAX_COMMAND_LENGTH {
+            return Err(anyhow!("RCON commands are limited to {} bytes", MAX_COMMAND_LENGTH));
+        }
+
+        let id = self.send(SERVERDATA_EXECCOMMAND, command).await?;
+        let sentinel = self.send(SERVERDATA_RESPONSE_VALUE, "").await?;
+
+        let mut response = String::new();
+        loop {
+            let packet = RconPacket::read_from(&mut self.stream).await?;
+            if packet.id == sentinel {
+                return Ok(response);
+            }
+            if packet.id == id {
+                response.push_str(&packet.body);
+            }
+        }
+    }
+
+    /// Sends a packet and returns the id it was sent with.
+    async fn send(&mut self, packet_type: i32, body: &str) -> Result<i32> {
+        let id = self.next_id;
+        self.next_id = self.next_id.wrapping_add(1).max(1);
+
+        let packet = RconPacket { id, packet_type, body: body.to_string() };
+        self.stream.write_all(&packet.encode())
+            .await
+            .context("Failed to send RCON packet")?;
+        Ok(id)
+    }
+}
+
+/// Represents the RCON settings of a server.
+///
+/// # Fields
+/// - `address`: The `host:port` RCON listens on.
+/// - `password`: The RCON password.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RconSettings {
+    pub address: String,
+    pub password: String,
+}
+
+/// Reads the RCON settings from the `server.properties` of an instance.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+///
+/// # Returns
+/// A `Result` containing the settings, or an error if RCON is disabled or has no password
+pub async fn read_rcon_settings(instance_dir: &Path) -> Result<RconSettings> {
+    let properties = read_properties(&instance_dir.join("server.properties"))
+        .await?;
+
+    if properties.get("enable-rcon") != Some("true") {
+        return Err(anyhow!("RCON is not enabled, set enable-rcon=true in server.properties"));
+    }
+
+    let password = properties.get("rcon.password").unwrap_or_default();
+    if password.is_empty() {
+        return Err(anyhow!("No RCON password set, set rcon.password in server.properties"));
+    }
+
+    let host = properties.local_host();
+    let port = properties.get("rcon.port").unwrap_or("25575");
+    let address = if host.contains(':') {
+        format!("[{}]:{}", host, port)
+    } else {
+        format!("{}:{}", host, port)
+    };
+
+    Ok(RconSettings {
+        address,
+        password: password.to_string(),
+    })
+}
+
+/// Connects to the RCON server of an instance using its `server.properties`.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+///
+/// # Returns
+/// A `Result` containing the authenticated client if successful
+pub async fn connect_instance(instance_dir: &Path) -> Result<RconClient> {
+    let settings = read_rcon_settings(instance_dir)
+        .await?;
+    RconClient::connect(&settings.address, &settings.password)
+        .await
+}
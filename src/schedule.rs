@@ -0,0 +1,331 @@
+//! Scheduled maintenance tasks of instances.
+//!
+//! Tasks are configured as `[[schedule]]` tables in `instance.toml` and run by the daemon:
+//!
+//! ```toml
+//! [[schedule]]
+//! name = "nightly-restart"
+//! cron = "0 4 * * *"
+//! action = "restart"
+//! countdown = 300
+//!
+//! [[schedule]]
+//! cron = "*/30 * * * *"
+//! action = "command"
+//! command = "say Remember to vote!"
+//! ```
+//!
+//! Schedules use the five field cron syntax (minute, hour, day of month, month, day of week)
+//! in local time, including lists, ranges, steps, month and weekday names and the `@daily` style
+//! shortcuts.
+
+use crate::instance::InstanceConfig;
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Datelike, Duration, LocalResult, NaiveDateTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// How many days ahead the next run of a schedule is searched for. Covers schedules that only
+/// match on the 29th of February of a particular weekday.
+const SEARCH_DAYS: i64 = 366 * 28;
+
+const MONTH_NAMES: &[&str] = &["jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec"];
+const WEEKDAY_NAMES: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A parsed cron expression.
+///
+/// Each field is stored as a bit set of the values it matches. Like in Vixie cron, a day matches
+/// if either the day of month or the day of week matches when both fields are restricted.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct CronSchedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+impl CronSchedule {
+    /// Parses a cron expression.
+    ///
+    /// # Arguments
+    /// - `expression`: Five whitespace separated fields, or a shortcut such as `@daily`
+    ///
+    /// # Returns
+    /// A `Result` containing the schedule if the expression is valid
+    pub fn parse(expression: &str) -> Result<Self> {
+        let expanded = match expression.trim() {
+            "@yearly" | "@annually" => "0 0 1 1 *",
+            "@monthly" => "0 0 1 * *",
+            "@weekly" => "0 0 * * 0",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@hourly" => "0 * * * *",
+            other => other,
+        };
+
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minutes, hours, days, months, weekdays] = fields[..] else {
+            return Err(anyhow!("Invalid cron expression {:?}: expected 5 fields, found {}", expression, fields.len()));
+        };
+
+        let parse = |field: &str, min: u32, max: u32, names: &[&str], name_offset: u32| {
+            parse_field(field, min, max, names, name_offset)
+                .context(format!("Invalid cron expression {:?}", expression))
+        };
+
+        let mut weekday_bits = parse(weekdays, 0, 7, WEEKDAY_NAMES, 0)?;
+        // Both 0 and 7 stand for Sunday.
+        if weekday_bits & (1 << 7) != 0 {
+            weekday_bits = (weekday_bits | 1) & !(1 << 7);
+        }
+
+        Ok(CronSchedule {
+            expression: expression.trim().to_string(),
+            minutes: parse(minutes, 0, 59, &[], 0)?,
+            hours: parse(hours, 0, 23, &[], 0)?,
+            days: parse(days, 1, 31, &[], 0)?,
+            months: parse(months, 1, 12, MONTH_NAMES, 1)?,
+            weekdays: weekday_bits,
+            days_restricted: !days.starts_with('*'),
+            weekdays_restricted: !weekdays.starts_with('*'),
+        })
+    }
+
+    /// Returns whether the schedule matches the minute of a date and time.
+    pub fn matches(&self, time: &NaiveDateTime) -> bool {
+        self.matches_day(time) && has_bit(self.hours, time.hour()) && has_bit(self.minutes, time.minute())
+    }
+
+    /// Returns whether the schedule runs at any time of the day of a date.
+    fn matches_day(&self, time: &NaiveDateTime) -> bool {
+        if !has_bit(self.months, time.month()) {
+            return false;
+        }
+
+        let day = has_bit(self.days, time.day());
+        let weekday = has_bit(self.weekdays, time.weekday().num_days_from_sunday());
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            (true, false) => day,
+            (false, true) => weekday,
+            (false, false) => true,
+        }
+    }
+
+    /// Returns the first time after the given one at which the schedule runs.
+    ///
+    /// Times skipped by a daylight saving change are skipped, and times that occur twice run the
+    /// first time.
+    ///
+    /// # Arguments
+    /// - `after`: The time to search from, exclusive
+    ///
+    /// # Returns
+    /// The next run, or `None` if the schedule never runs (such as on the 31st of February)
+    pub fn next_after<Tz: TimeZone>(&self, after: &DateTime<Tz>) -> Option<DateTime<Tz>> {
+        let timezone = after.timezone();
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let start_day = start.date().and_hms_opt(0, 0, 0)?;
+
+        for day_offset in 0..SEARCH_DAYS {
+            let day = start_day + Duration::days(day_offset);
+            if !self.matches_day(&day) {
+                continue;
+            }
+
+            for hour in (0..24).filter(|hour| has_bit(self.hours, *hour)) {
+                for minute in (0..60).filter(|minute| has_bit(self.minutes, *minute)) {
+                    let candidate = day.with_hour(hour)?.with_minute(minute)?;
+                    if candidate < start {
+                        continue;
+                    }
+                    match timezone.from_local_datetime(&candidate) {
+                        LocalResult::Single(time) | LocalResult::Ambiguous(time, _) if time > *after => return Some(time),
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        None
+    }
+}
+
+impl TryFrom<String> for CronSchedule {
+    type Error = anyhow::Error;
+
+    fn try_from(expression: String) -> Result<Self> {
+        CronSchedule::parse(&expression)
+    }
+}
+
+impl From<CronSchedule> for String {
+    fn from(schedule: CronSchedule) -> Self {
+        schedule.expression
+    }
+}
+
+impl Display for CronSchedule {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+/// Returns whether a bit is set in a field of a schedule.
+fn has_bit(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+/// Parses a comma separated cron field into a bit set.
+///
+/// # Arguments
+/// - `field`: The field, for example `*/15`, `1-5` or `mon,wed,fri`
+/// - `min`: The smallest allowed value
+/// - `max`: The largest allowed value
+/// - `names`: Names that may be used instead of numbers
+/// - `name_offset`: The value of the first name
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], name_offset: u32) -> Result<u64> {
+    let parse_value = |value: &str| -> Result<u32> {
+        let lowercase = value.to_lowercase();
+        let number = match names.iter().position(|name| *name == lowercase) {
+            Some(index) => index as u32 + name_offset,
+            None => value.parse::<u32>().map_err(|_| anyhow!("Invalid value {:?}", value))?,
+        };
+        if number < min || number > max {
+            return Err(anyhow!("Value {} is out of range {}-{}", number, min, max));
+        }
+        Ok(number)
+    };
+
+    let mut bits = 0u64;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().map_err(|_| anyhow!("Invalid step {:?}", step))?;
+                if step == 0 {
+                    return Err(anyhow!("Step must not be 0 in {:?}", item));
+                }
+                (range, Some(step))
+            }
+            None => (item, None),
+        };
+
+        let (start, end) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((start, end)) => (parse_value(start)?, parse_value(end)?),
+            // `5/15` is short for `5-<max>/15`.
+            None if step.is_some() => (parse_value(range)?, max),
+            None => {
+                let value = parse_value(range)?;
+                (value, value)
+            }
+        };
+        if start > end {
+            return Err(anyhow!("Invalid range {:?}", range));
+        }
+
+        for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// What a scheduled task does.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum TaskAction {
+    /// Restarts the server with a countdown, if it is running under the daemon.
+    Restart {
+        #[serde(default = "default_restart_countdown")]
+        countdown: u64,
+        #[serde(default = "default_restart_message")]
+        message: String,
+    },
+    /// Backs up the worlds of the instance.
+    Backup,
+    /// Runs a console command on the running server.
+    Command { command: String },
+    /// Checks whether a newer server version is available.
+    UpdateCheck,
+}
+
+impl Display for TaskAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TaskAction::Restart { .. } => write!(f, "restart"),
+            TaskAction::Backup => write!(f, "backup"),
+            TaskAction::Command { command } => write!(f, "command: {}", command),
+            TaskAction::UpdateCheck => write!(f, "update-check"),
+        }
+    }
+}
+
+fn default_restart_countdown() -> u64 {
+    60
+}
+
+fn default_restart_message() -> String {
+    "Scheduled restart in {seconds} seconds".to_string()
+}
+
+/// A task of an instance that runs on a cron schedule.
+///
+/// # Fields
+/// - `name`: An optional name shown in logs and listings
+/// - `cron`: When the task runs
+/// - `action`: What the task does
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ScheduledTask {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub cron: CronSchedule,
+    #[serde(flatten)]
+    pub action: TaskAction,
+}
+
+impl ScheduledTask {
+    /// Returns the name of the task, or a description of its action if it has no name.
+    pub fn label(&self) -> String {
+        self.name.clone().unwrap_or_else(|| self.action.to_string())
+    }
+}
+
+/// A single upcoming run of a scheduled task.
+#[derive(Debug, Clone)]
+pub struct ScheduledRun<Tz: TimeZone> {
+    pub instance: PathBuf,
+    pub task: ScheduledTask,
+    pub time: DateTime<Tz>,
+}
+
+/// Returns the upcoming runs of the tasks of the given instances in chronological order.
+///
+/// # Arguments
+/// - `instances`: The instance directories and their configuration
+/// - `after`: The time to search from, exclusive
+/// - `runs_per_task`: How many runs of each task are returned at most
+pub fn upcoming_runs<Tz: TimeZone>(instances: &[(PathBuf, InstanceConfig)], after: &DateTime<Tz>, runs_per_task: usize) -> Vec<ScheduledRun<Tz>> {
+    let mut runs = Vec::new();
+    for (instance_dir, config) in instances {
+        for task in &config.schedule {
+            let mut time = after.clone();
+            for _ in 0..runs_per_task {
+                let Some(next) = task.cron.next_after(&time) else {
+                    break;
+                };
+                runs.push(ScheduledRun { instance: instance_dir.clone(), task: task.clone(), time: next.clone() });
+                time = next;
+            }
+        }
+    }
+
+    runs.sort_by(|a, b| a.time.cmp(&b.time).then_with(|| a.instance.cmp(&b.instance)));
+    runs
+}
@@ -0,0 +1,237 @@
+//! Server types declared in `config.toml` instead of built into mvm.
+//!
+//! Forks that publish their versions as JSON can be managed without a new release of mvm by
+//! describing where the versions are listed and where jars are downloaded from:
+//!
+//! ```toml
+//! [server_types.leaf]
+//! versions_url = "https://api.leafmc.one/v2/projects/leaf"
+//! # JSON pointer to the array of versions, the whole document if omitted.
+//! versions_pointer = "/versions"
+//! # Optional, JSON pointer to the id within each element of the array.
+//! version_pointer = ""
+//! # Optional, JSON pointer into the versions document, the last version if omitted.
+//! latest_pointer = "/versions/-"
+//! # Optional, where the builds of a version are listed and the pointer to the latest one.
+//! builds_url = "https://api.leafmc.one/v2/projects/leaf/versions/{version}"
+//! build_pointer = "/builds/-"
+//! download_url = "https://api.leafmc.one/v2/projects/leaf/versions/{version}/builds/{build}/downloads/leaf-{version}-{build}.jar"
+//! ```
+//!
+//! Pointers follow RFC 6901, with `-` referring to the last element of an array.
+
+use crate::server::server_types::ServerType;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// How to list the versions of a server type declared in `config.toml` and download its jars.
+///
+/// # Fields
+/// - `versions_url`: The URL of the JSON document listing the versions
+/// - `versions_pointer`: The JSON pointer to the array of versions in the document
+/// - `version_pointer`: The JSON pointer to the id within each element of the array, if the
+///   elements are not the ids themselves
+/// - `latest_pointer`: The JSON pointer to the latest version in the document, the last element
+///   of the array if not given
+/// - `builds_url`: The URL of the JSON document listing the builds of a version, with `{version}`
+/// - `build_pointer`: The JSON pointer to the build to download in the builds document
+/// - `download_url`: The URL of the jar, with `{version}` and `{build}`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ServerTypeDefinition {
+    pub versions_url: String,
+    #[serde(default)]
+    pub versions_pointer: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version_pointer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latest_pointer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub builds_url: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_pointer: Option<String>,
+    pub download_url: String,
+}
+
+/// The parts of `config.toml` that declare server types.
+#[derive(Debug, Default, Deserialize)]
+struct ConfigFile {
+    #[serde(default)]
+    server_types: BTreeMap<String, ServerTypeDefinition>,
+}
+
+/// Reads the server types declared in `config.toml`.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the declarations by name, empty if there is no `config.toml`
+pub fn read_definitions(path: &Path) -> Result<BTreeMap<String, ServerTypeDefinition>> {
+    let config_path = path.join("config.toml");
+    if !config_path.exists() {
+        return Ok(BTreeMap::new());
+    }
+
+    let content = fs::read_to_string(&config_path)
+        .context(format!("Failed to read {:?}", config_path))?;
+    let config = toml::from_str::<ConfigFile>(&content)
+        .context(format!("Failed to parse the [server_types] settings in {:?}", config_path))?;
+    for name in config.server_types.keys() {
+        if !matches!(ServerType::from_string(name.clone()), Ok(ServerType::Defined(_))) {
+            return Err(anyhow!("Cannot declare server type '{}' in {:?}, the name is reserved or not lowercase letters, digits, '-' and '_'", name, config_path));
+        }
+    }
+    Ok(config.server_types)
+}
+
+/// Reads the declaration of a server type from `config.toml`.
+///
+/// # Arguments
+/// - `name`: The name of the server type
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the declaration, or an error if the type is not declared
+pub fn read_definition(name: &str, path: &Path) -> Result<ServerTypeDefinition> {
+    read_definitions(path)?
+        .remove(name)
+        .ok_or_else(|| anyhow!("Unknown server type '{}', declare it under [server_types.{}] in config.toml", name, name))
+}
+
+/// Looks up a JSON pointer in a document, where `-` refers to the last element of an array.
+///
+/// # Arguments
+/// - `document`: The JSON document
+/// - `pointer`: The pointer, empty for the whole document
+///
+/// # Returns
+/// The value the pointer refers to, or `None` if there is none
+pub fn resolve_pointer<'a>(document: &'a Value, pointer: &str) -> Option<&'a Value> {
+    if pointer.is_empty() {
+        return Some(document);
+    }
+    let tokens = pointer.strip_prefix('/')?;
+
+    let mut value = document;
+    for token in tokens.split('/') {
+        let token = token.replace("~1", "/").replace("~0", "~");
+        value = match value {
+            Value::Object(map) => map.get(&token)?,
+            Value::Array(array) if token == "-" => array.last()?,
+            Value::Array(array) => array.get(token.parse::<usize>().ok()?)?,
+            _ => return None
+        };
+    }
+    Some(value)
+}
+
+/// Converts a version id or build found in a document into a string.
+fn value_to_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(number) => Some(number.to_string()),
+        _ => None
+    }
+}
+
+/// Fills `{version}` and `{build}` into a URL template.
+fn fill_template(template: &str, version: &str, build: Option<&str>) -> String {
+    let url = template.replace("{version}", version);
+    match build {
+        Some(build) => url.replace("{build}", build),
+        None => url
+    }
+}
+
+/// Fetches a JSON document.
+async fn fetch_json(url: &str) -> Result<Value> {
+    reqwest::get(url)
+        .await
+        .context(format!("Failed to fetch {}", url))?
+        .error_for_status()
+        .context(format!("Failed to fetch {}", url))?
+        .json::<Value>()
+        .await
+        .context(format!("Failed to parse the JSON of {}", url))
+}
+
+impl ServerTypeDefinition {
+    /// Extracts the version ids from a versions document.
+    pub fn parse_versions(&self, document: &Value) -> Result<Vec<String>> {
+        let list = resolve_pointer(document, &self.versions_pointer)
+            .and_then(Value::as_array)
+            .ok_or_else(|| anyhow!("versions_pointer {:?} does not point to an array", self.versions_pointer))?;
+
+        list.iter()
+            .map(|element| {
+                let pointer = self.version_pointer.as_deref().unwrap_or_default();
+                resolve_pointer(element, pointer)
+                    .and_then(value_to_id)
+                    .ok_or_else(|| anyhow!("version_pointer {:?} does not point to a version id in {}", pointer, element))
+            })
+            .collect()
+    }
+
+    /// Extracts the latest version id from a versions document.
+    pub fn parse_latest(&self, document: &Value) -> Result<String> {
+        match &self.latest_pointer {
+            Some(pointer) => resolve_pointer(document, pointer)
+                .and_then(value_to_id)
+                .ok_or_else(|| anyhow!("latest_pointer {:?} does not point to a version id", pointer)),
+            None => self.parse_versions(document)?
+                .pop()
+                .ok_or_else(|| anyhow!("{} lists no versions", self.versions_url))
+        }
+    }
+
+    /// Fetches the version ids.
+    pub async fn fetch_versions(&self) -> Result<Vec<String>> {
+        self.parse_versions(&fetch_json(&self.versions_url).await?)
+    }
+
+    /// Fetches the latest version id.
+    pub async fn fetch_latest(&self) -> Result<String> {
+        self.parse_latest(&fetch_json(&self.versions_url).await?)
+    }
+
+    /// Returns the download URL of the jar of a version, fetching its latest build if the template
+    /// needs one.
+    ///
+    /// # Arguments
+    /// - `version`: The version id, "latest" for the latest version
+    ///
+    /// # Returns
+    /// A `Result` containing the download URL if the version is listed
+    pub async fn download_url(&self, version: &str) -> Result<String> {
+        let versions_document = fetch_json(&self.versions_url).await?;
+        let version = if version == "latest" {
+            self.parse_latest(&versions_document)?
+        } else {
+            if !self.parse_versions(&versions_document)?.iter().any(|id| id == version) {
+                return Err(anyhow!("Version {} not found!", version));
+            }
+            version.to_string()
+        };
+
+        let build = match (&self.builds_url, &self.build_pointer) {
+            (Some(builds_url), Some(pointer)) => {
+                let builds_document = fetch_json(&fill_template(builds_url, &version, None)).await?;
+                let build = resolve_pointer(&builds_document, pointer)
+                    .and_then(value_to_id)
+                    .ok_or_else(|| anyhow!("build_pointer {:?} does not point to a build of version {}", pointer, version))?;
+                Some(build)
+            }
+            (Some(_), None) | (None, Some(_)) => return Err(anyhow!("builds_url and build_pointer have to be declared together")),
+            (None, None) => None
+        };
+        if build.is_none() && self.download_url.contains("{build}") {
+            return Err(anyhow!("download_url uses {{build}}, declare builds_url and build_pointer"));
+        }
+
+        Ok(fill_template(&self.download_url, &version, build.as_deref()))
+    }
+}
@@ -0,0 +1,46 @@
+//! Resolves Forge versions from the Maven repository of MinecraftForge.
+//!
+//! Forge versions are named `<minecraft>-<forge>`, e.g. `1.21.4-54.0.16`, and every Forge version
+//! is built for exactly one Minecraft version.
+
+use crate::server::loader::{fetch_maven_versions, resolve_loader_version};
+use anyhow::Result;
+
+/// The Maven metadata listing every Forge version.
+pub const FORGE_METADATA_URL: &str = "https://maven.minecraftforge.net/net/minecraftforge/forge/maven-metadata.xml";
+
+/// The arguments that make the Forge installer install a server into its working directory.
+pub const FORGE_INSTALLER_ARGS: &[&str] = &["--installServer"];
+
+/// The jars older Forge installers start the server with instead of a launch script.
+pub const FORGE_SERVER_JARS: &[&str] = &["forge-"];
+
+/// Returns the Minecraft version a Forge version is built for.
+pub fn forge_game_version(version: &str) -> &str {
+    version.split('-').next().unwrap_or(version)
+}
+
+/// Resolves a Forge version from the versions listed in the Maven metadata.
+///
+/// # Arguments
+/// - `versions`: The listed Forge versions
+/// - `version_to_find`: "latest" for the newest Forge of the newest Minecraft version, a
+///   Minecraft version for its newest Forge, or a Forge version
+///
+/// # Returns
+/// A `Result` containing the Forge version, or an error if none matches
+pub fn resolve_forge_version(versions: &[String], version_to_find: &str) -> Result<String> {
+    resolve_loader_version(versions, version_to_find, |version| forge_game_version(version).to_string(), "1.21.4-54.0.16")
+}
+
+/// Fetches the Forge versions and resolves one of them, see `resolve_forge_version`.
+pub async fn get_forge_version(version_to_find: &str) -> Result<String> {
+    let versions = fetch_maven_versions(FORGE_METADATA_URL)
+        .await?;
+    resolve_forge_version(&versions, version_to_find)
+}
+
+/// Returns the download URL of the installer of a Forge version.
+pub fn forge_installer_url(version: &str) -> String {
+    format!("https://maven.minecraftforge.net/net/minecraftforge/forge/{0}/forge-{0}-installer.jar", version)
+}
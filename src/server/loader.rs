@@ -0,0 +1,378 @@
+//! Installs servers of mod loaders, which are set up by running an installer rather than by
+//! downloading a single jar.
+//!
+//! The installer is downloaded into a sandbox directory next to the installed versions and run
+//! there headlessly with the Java mvm launches servers with. It leaves a tree of libraries and a
+//! launch script behind, from which mvm reads the arguments that start the server and records them
+//! in a `launch.toml`. The sandbox is moved into place only once the installer succeeded, so a
+//! failed install leaves nothing half installed behind.
+
+use crate::instance::java_binary;
+use crate::metadata::compare_version_names;
+use crate::provenance::record_install;
+use crate::server::server_types::ServerType;
+use anyhow::{anyhow, Context, Result};
+use futures_util::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+/// The file in the version directory the launch arguments are recorded in.
+pub const LAUNCH_FILE: &str = "launch.toml";
+
+/// The name the installer is kept under in the version directory.
+pub const INSTALLER_FILE: &str = "installer.jar";
+
+/// The file the output of the installer is written to.
+pub const INSTALLER_LOG: &str = "installer.log";
+
+/// How to start a server installed by a mod loader's installer.
+///
+/// # Fields
+/// - `game_version`: The Minecraft version the loader runs
+/// - `args`: The arguments passed to Java after the JVM arguments of the instance, with paths
+///   into the version directory made absolute
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct LaunchConfig {
+    pub game_version: String,
+    pub args: Vec<String>,
+}
+
+/// Extracts the versions listed in a Maven `maven-metadata.xml`.
+pub fn parse_maven_versions(xml: &str) -> Vec<String> {
+    xml.split("<version>")
+        .skip(1)
+        .filter_map(|rest| rest.split_once("</version>"))
+        .map(|(version, _)| version.trim().to_string())
+        .collect()
+}
+
+/// Fetches the versions listed in a Maven `maven-metadata.xml`.
+///
+/// # Arguments
+/// - `url`: The URL of the metadata
+pub async fn fetch_maven_versions(url: &str) -> Result<Vec<String>> {
+    let xml = reqwest::get(url)
+        .await
+        .context(format!("Failed to fetch {}", url))?
+        .error_for_status()
+        .context(format!("Failed to fetch {}", url))?
+        .text()
+        .await
+        .context(format!("Failed to read {}", url))?;
+    Ok(parse_maven_versions(&xml))
+}
+
+/// Returns the newest of some versions, comparing their numbers.
+pub fn newest<'a>(versions: impl IntoIterator<Item = &'a String>) -> Option<String> {
+    versions.into_iter()
+        .max_by(|a, b| compare_version_names(a, b))
+        .cloned()
+}
+
+/// Returns whether a loader version is a beta or alpha build.
+fn is_prerelease(version: &str) -> bool {
+    version.contains("-beta") || version.contains("-alpha")
+}
+
+/// Returns the newest stable of some loader versions, or the newest prerelease if none is stable.
+pub fn newest_stable<'a>(versions: impl IntoIterator<Item = &'a String>) -> Option<String> {
+    let versions = versions.into_iter().collect::<Vec<_>>();
+    newest(versions.iter().copied().filter(|version| !is_prerelease(version)))
+        .or_else(|| newest(versions))
+}
+
+/// Resolves a loader version from the versions listed for the loader.
+///
+/// Only prerelease builds are picked when a game version has no stable ones.
+///
+/// # Arguments
+/// - `versions`: The listed loader versions
+/// - `version_to_find`: "latest" for the newest loader of the newest game version, a game version
+///   for its newest loader, or a loader version
+/// - `game_version`: Returns the game version a loader version is built for
+/// - `example`: An example of a loader version, shown when none matches
+///
+/// # Returns
+/// A `Result` containing the loader version, or an error if none matches
+pub fn resolve_loader_version(versions: &[String], version_to_find: &str, game_version: impl Fn(&str) -> String, example: &str) -> Result<String> {
+    if versions.iter().any(|version| version == version_to_find) {
+        return Ok(version_to_find.to_string());
+    }
+
+    let game = if version_to_find == "latest" {
+        newest(versions.iter().map(|version| game_version(version)).collect::<Vec<_>>().iter())
+            .ok_or_else(|| anyhow!("No versions are listed"))?
+    } else {
+        version_to_find.to_string()
+    };
+    newest_stable(versions.iter().filter(|version| game_version(version) == game))
+        .ok_or_else(|| anyhow!("Version {} not found! Versions are named like {}", version_to_find, example))
+}
+
+/// Fetches the SHA-1 hash a Maven repository publishes next to an installer.
+///
+/// # Arguments
+/// - `installer_url`: The URL of the installer
+pub async fn get_installer_sha1(installer_url: &str) -> Result<String> {
+    let url = format!("{}.sha1", installer_url);
+    let sha1 = reqwest::get(&url)
+        .await
+        .context(format!("Failed to fetch {}", url))?
+        .error_for_status()
+        .context(format!("Failed to fetch {}", url))?
+        .text()
+        .await
+        .context(format!("Failed to read {}", url))?;
+    Ok(sha1.trim().to_lowercase())
+}
+
+/// Reads the launch arguments recorded for an installed version.
+///
+/// # Arguments
+/// - `version_dir`: The directory of the installed version
+///
+/// # Returns
+/// A `Result` containing the launch configuration, or `None` if the version was installed as a
+/// single jar
+pub fn read_launch_config(version_dir: &Path) -> Result<Option<LaunchConfig>> {
+    let launch_path = version_dir.join(LAUNCH_FILE);
+    let content = match std::fs::read_to_string(&launch_path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(err).context(format!("Failed to read {:?}", launch_path))
+    };
+    let config = toml::from_str(&content)
+        .context(format!("Failed to parse {:?}", launch_path))?;
+    Ok(Some(config))
+}
+
+/// Splits the content of a Java argument file or the `java` line of a launch script into
+/// arguments, honouring double quotes.
+fn split_args(content: &str) -> Vec<String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut started = false;
+    for c in content.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                started = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if started {
+                    args.push(std::mem::take(&mut current));
+                    started = false;
+                }
+            }
+            c => {
+                current.push(c);
+                started = true;
+            }
+        }
+    }
+    if started {
+        args.push(current);
+    }
+    args
+}
+
+/// Makes the paths in an argument that exist in the sandbox absolute paths into the version
+/// directory, also within `key=value` arguments and classpath lists.
+fn absolutize(arg: &str, sandbox: &Path, version_dir: &Path) -> String {
+    let separator = if cfg!(windows) { ';' } else { ':' };
+    let absolutize_path = |part: &str| {
+        if !part.is_empty() && !part.starts_with('-') && Path::new(part).is_relative() && sandbox.join(part).exists() {
+            version_dir.join(part).to_string_lossy().to_string()
+        } else {
+            part.to_string()
+        }
+    };
+    let absolutize_list = |list: &str| list.split(separator)
+        .map(absolutize_path)
+        .collect::<Vec<_>>()
+        .join(&separator.to_string());
+
+    match arg.split_once('=') {
+        Some((key, value)) if key.starts_with("-D") => format!("{}={}", key, absolutize_list(value)),
+        _ => absolutize_list(arg)
+    }
+}
+
+/// Reads the arguments that start an installed server from the launch script the installer
+/// wrote, or finds the jar to start with `-jar` for installers that do not write one.
+///
+/// `@user_jvm_args.txt` is left out, the JVM arguments of instances take its place. Other argument
+/// files are expanded, so that the server can be started from the directory of any instance.
+///
+/// # Arguments
+/// - `sandbox`: The directory the installer ran in
+/// - `version_dir`: The directory the installation will be moved to
+/// - `jar_prefixes`: The prefixes of the names of jars that start the server without a script
+///
+/// # Returns
+/// A `Result` containing the arguments passed to Java
+pub fn read_launch_args(sandbox: &Path, version_dir: &Path, jar_prefixes: &[&str]) -> Result<Vec<String>> {
+    let script = if cfg!(windows) { "run.bat" } else { "run.sh" };
+    if let Ok(content) = std::fs::read_to_string(sandbox.join(script)) {
+        let java_line = content.lines()
+            .map(str::trim)
+            .find(|line| line.starts_with("java "))
+            .ok_or_else(|| anyhow!("The {} written by the installer does not start java", script))?;
+
+        let mut args = Vec::new();
+        for arg in split_args(java_line).into_iter().skip(1) {
+            match arg.strip_prefix('@') {
+                Some("user_jvm_args.txt") => {}
+                Some(arg_file) => {
+                    let content = std::fs::read_to_string(sandbox.join(arg_file))
+                        .context(format!("Failed to read the argument file {}", arg_file))?;
+                    args.extend(split_args(&content).iter().map(|arg| absolutize(arg, sandbox, version_dir)));
+                }
+                None if arg == "$@" || arg == "%*" || arg == "nogui" => {}
+                None => args.push(absolutize(&arg, sandbox, version_dir))
+            }
+        }
+        return Ok(args);
+    }
+
+    let mut jars = Vec::new();
+    for entry in std::fs::read_dir(sandbox).context(format!("Failed to read {:?}", sandbox))? {
+        let name = entry.context(format!("Failed to read {:?}", sandbox))?.file_name().to_string_lossy().to_string();
+        if name.ends_with(".jar") && name != INSTALLER_FILE && !name.contains("installer") && jar_prefixes.iter().any(|prefix| name.starts_with(prefix)) {
+            jars.push(name);
+        }
+    }
+    jars.sort();
+    match jars.as_slice() {
+        [jar] => Ok(vec!["-jar".to_string(), version_dir.join(jar).to_string_lossy().to_string()]),
+        [] => Err(anyhow!("The installer wrote neither a {} nor a server jar", script)),
+        _ => Err(anyhow!("The installer wrote several server jars: {:?}", jars))
+    }
+}
+
+/// Downloads a file, failing on error responses.
+async fn download(url: &str, file_path: &Path) -> Result<()> {
+    let response = reqwest::get(url)
+        .await
+        .context(format!("Failed to download {}", url))?
+        .error_for_status()
+        .context(format!("Failed to download {}", url))?;
+    let mut file = File::create(file_path)
+        .await
+        .context(format!("Failed to create {:?}", file_path))?;
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.context("Failed to read chunk from response")?;
+        file.write_all(&chunk)
+            .await
+            .context(format!("Failed to write {:?}", file_path))?;
+    }
+    file.flush()
+        .await
+        .context(format!("Failed to write {:?}", file_path))
+}
+
+/// Runs an installer headlessly in a directory, writing its output to `installer.log`.
+///
+/// # Arguments
+/// - `sandbox`: The directory containing `installer.jar`, which the server is installed into
+/// - `installer_args`: The arguments that make the installer install a server
+pub async fn run_installer(sandbox: &Path, installer_args: &[String]) -> Result<()> {
+    let log_path = sandbox.join(INSTALLER_LOG);
+    let log = std::fs::File::create(&log_path)
+        .context(format!("Failed to create {:?}", log_path))?;
+    let status = Command::new(java_binary())
+        .arg("-jar")
+        .arg(INSTALLER_FILE)
+        .args(installer_args)
+        .current_dir(sandbox)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone().context(format!("Failed to open {:?}", log_path))?)
+        .stderr(log)
+        .status()
+        .await
+        .context("Failed to start the installer, is Java installed?")?;
+
+    if !status.success() {
+        let output = std::fs::read_to_string(&log_path).unwrap_or_default();
+        let tail = output.lines().rev().take(10).collect::<Vec<_>>().into_iter().rev().collect::<Vec<_>>().join("\n");
+        return Err(anyhow!("The installer exited with {}:\n{}", status, tail));
+    }
+    Ok(())
+}
+
+/// Installs a server by downloading and running its installer.
+///
+/// # Arguments
+/// - `installer_url`: Where to download the installer from
+/// - `version`: The version to install the server as
+/// - `game_version`: The Minecraft version the loader runs
+/// - `server_type`: The type of server
+/// - `installer_args`: The arguments that make the installer install a server
+/// - `jvm_args`: JVM arguments the server is started with, before the ones read from the
+///   installation; paths in them are made absolute like in those
+/// - `jar_prefixes`: The prefixes of the names of jars that start the server without a script
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the directory of the installed version
+#[allow(clippy::too_many_arguments)]
+pub async fn install_server(installer_url: &str, version: &str, game_version: &str, server_type: &ServerType, installer_args: &[String], jvm_args: &[String], jar_prefixes: &[&str], path: &Path) -> Result<PathBuf> {
+    let type_dir = path.join(server_type.to_string());
+    let sandbox = type_dir.join("installing").join(version);
+    let version_dir = type_dir.join("versions").join(version);
+    if sandbox.exists() {
+        fs::remove_dir_all(&sandbox)
+            .await
+            .context(format!("Failed to clean up {:?}", sandbox))?;
+    }
+    fs::create_dir_all(&sandbox)
+        .await
+        .context(format!("Failed to create {:?}", sandbox))?;
+
+    download(installer_url, &sandbox.join(INSTALLER_FILE))
+        .await?;
+    println!("Running the {} installer, see {:?}", server_type, sandbox.join(INSTALLER_LOG));
+    run_installer(&sandbox, installer_args)
+        .await
+        .context(format!("Failed to install {} {}, the output is kept in {:?}", server_type, version, sandbox.join(INSTALLER_LOG)))?;
+
+    let launch = LaunchConfig {
+        game_version: game_version.to_string(),
+        args: jvm_args.iter()
+            .map(|arg| absolutize(arg, &sandbox, &version_dir))
+            .chain(read_launch_args(&sandbox, &version_dir, jar_prefixes)?)
+            .collect(),
+    };
+    let content = toml::to_string_pretty(&launch)
+        .context("Failed to serialize the launch arguments")?;
+    fs::write(sandbox.join(LAUNCH_FILE), content)
+        .await
+        .context(format!("Failed to write {:?}", sandbox.join(LAUNCH_FILE)))?;
+
+    if version_dir.exists() {
+        fs::remove_dir_all(&version_dir)
+            .await
+            .context(format!("Failed to replace {:?}", version_dir))?;
+    }
+    fs::create_dir_all(type_dir.join("versions"))
+        .await
+        .context(format!("Failed to create directory for the version, path: {:?}", version_dir))?;
+    fs::rename(&sandbox, &version_dir)
+        .await
+        .context(format!("Failed to move {:?} to {:?}", sandbox, version_dir))?;
+    let _ = fs::remove_dir(type_dir.join("installing")).await;
+
+    let (installer_path, source, record_type, record_version) = (version_dir.join(INSTALLER_FILE), installer_url.to_string(), server_type.clone(), version.to_string());
+    tokio::task::spawn_blocking(move || record_install(&installer_path, &source, &record_type, &record_version, None))
+        .await
+        .context("Failed to record the install")??;
+
+    println!("Installed {} {} to {:?}", server_type, version, version_dir);
+    Ok(version_dir)
+}
@@ -2,6 +2,12 @@
 
 pub mod vanilla;
 pub mod paper;
+pub mod purpur;
 pub mod server_types;
 pub mod toml_config;
+pub mod defined;
+pub mod loader;
+pub mod forge;
+pub mod neoforge;
+pub mod quilt;
 
@@ -0,0 +1,61 @@
+//! Resolves NeoForge versions from the Maven repository of NeoForged.
+//!
+//! NeoForge versions drop the leading `1.` of the Minecraft version they are built for, e.g.
+//! `21.4.111-beta` is built for Minecraft 1.21.4 and `20.2.86` for 1.20.2. Versions for Minecraft's
+//! year based releases add a fourth number, e.g. `26.1.0.5` is built for 26.1.
+
+use crate::server::loader::{fetch_maven_versions, resolve_loader_version};
+use anyhow::Result;
+
+/// The Maven metadata listing every NeoForge version.
+pub const NEOFORGE_METADATA_URL: &str = "https://maven.neoforged.net/releases/net/neoforged/neoforge/maven-metadata.xml";
+
+/// The arguments that make the NeoForge installer install a server into its working directory.
+pub const NEOFORGE_INSTALLER_ARGS: &[&str] = &["--installServer"];
+
+/// NeoForge installers always write a launch script, so there is no jar to fall back to.
+pub const NEOFORGE_SERVER_JARS: &[&str] = &[];
+
+/// Returns the Minecraft version a NeoForge version is built for.
+pub fn neoforge_game_version(version: &str) -> String {
+    let numbers = version.split('-')
+        .next()
+        .unwrap_or(version)
+        .split('.')
+        .collect::<Vec<_>>();
+    let (game, patch) = match numbers.as_slice() {
+        [major, minor, patch, _] => (format!("{}.{}", major, minor), *patch),
+        [major, minor, _] => (format!("1.{}", major), *minor),
+        _ => return version.to_string()
+    };
+    if patch == "0" {
+        game
+    } else {
+        format!("{}.{}", game, patch)
+    }
+}
+
+/// Resolves a NeoForge version from the versions listed in the Maven metadata.
+///
+/// # Arguments
+/// - `versions`: The listed NeoForge versions
+/// - `version_to_find`: "latest" for the newest NeoForge of the newest Minecraft version, a
+///   Minecraft version for its newest NeoForge, or a NeoForge version
+///
+/// # Returns
+/// A `Result` containing the NeoForge version, or an error if none matches
+pub fn resolve_neoforge_version(versions: &[String], version_to_find: &str) -> Result<String> {
+    resolve_loader_version(versions, version_to_find, neoforge_game_version, "21.4.111-beta")
+}
+
+/// Fetches the NeoForge versions and resolves one of them, see `resolve_neoforge_version`.
+pub async fn get_neoforge_version(version_to_find: &str) -> Result<String> {
+    let versions = fetch_maven_versions(NEOFORGE_METADATA_URL)
+        .await?;
+    resolve_neoforge_version(&versions, version_to_find)
+}
+
+/// Returns the download URL of the installer of a NeoForge version.
+pub fn neoforge_installer_url(version: &str) -> String {
+    format!("https://maven.neoforged.net/releases/net/neoforged/neoforge/{0}/neoforge-{0}-installer.jar", version)
+}
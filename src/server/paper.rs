@@ -1,7 +1,13 @@
 //! This submodule provides structures and types for parsing JSON responses from PaperMC's Downloads API.
+//!
+//! The API serves every PaperMC project, such as `paper` and `folia`, with the same structures
+//! under `<PAPERMC_API>/<project>`.
 
 use serde::Deserialize;
 
+/// The base URL of the projects of PaperMC's Downloads API.
+pub const PAPERMC_API: &str = "https://api.papermc.io/v2/projects";
+
 #[derive(Deserialize, Debug)]
 pub struct PaperVersions {
     pub versions: Vec<String>,
@@ -12,6 +18,28 @@ pub struct PaperVersionBuilds {
     pub builds: Vec<u32>
 }
 
+#[derive(Deserialize, Debug)]
+pub struct PaperBuilds {
+    pub builds: Vec<PaperBuild>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PaperBuild {
+    pub build: u32,
+    pub downloads: PaperBuildDownloads
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PaperBuildDownloads {
+    pub application: PaperDownload
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PaperDownload {
+    pub name: String,
+    pub sha256: String
+}
+
 
 pub type PaperVersion = String;
 
@@ -0,0 +1,30 @@
+//! This submodule provides structures and types for parsing JSON responses from Purpur's Downloads API.
+
+use serde::Deserialize;
+
+#[derive(Deserialize, Debug)]
+pub struct PurpurVersions {
+    pub versions: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PurpurVersionBuilds {
+    pub builds: PurpurBuildList
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PurpurBuildList {
+    pub latest: String,
+    pub all: Vec<String>
+}
+
+#[derive(Deserialize, Debug)]
+pub struct PurpurBuild {
+    pub build: String,
+    pub md5: String
+}
+
+
+pub type PurpurVersion = String;
+
+pub type PurpurDownloadLink = String;
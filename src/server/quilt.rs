@@ -0,0 +1,139 @@
+//! Resolves Quilt versions from Quilt's meta API and describes how its installer sets up servers.
+//!
+//! mvm names Quilt versions `<minecraft>-<loader>`, e.g. `1.21.4-0.27.1`, since a loader version
+//! runs many Minecraft versions. The installer is versioned independently of both and the newest
+//! one installs any combination.
+
+use crate::server::loader::{fetch_maven_versions, newest, newest_stable};
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+
+/// The base URL of Quilt's meta API.
+pub const QUILT_META_API: &str = "https://meta.quiltmc.org/v3/versions";
+
+/// The Maven repository Quilt's installer is published to.
+pub const QUILT_INSTALLER_MAVEN: &str = "https://maven.quiltmc.org/repository/release/org/quiltmc/quilt-installer";
+
+/// The jar the installer writes that starts the server.
+pub const QUILT_SERVER_JARS: &[&str] = &["quilt-server-launch"];
+
+/// Points the server launcher at the Minecraft server the installer downloaded, rather than at
+/// `server.jar` in the directory of the instance.
+pub const QUILT_JVM_ARGS: &[&str] = &["-Dloader.gameJarPath=server.jar"];
+
+#[derive(Deserialize, Debug)]
+pub struct QuiltGameVersion {
+    pub version: String,
+    pub stable: bool
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QuiltLoaderEntry {
+    pub loader: QuiltLoader
+}
+
+#[derive(Deserialize, Debug)]
+pub struct QuiltLoader {
+    pub version: String
+}
+
+/// Splits a Quilt version into the Minecraft version and the loader version. Loader versions
+/// all start with `0.`, while Minecraft versions such as `1.21-pre1` may contain dashes too.
+pub fn split_quilt_version(version: &str) -> Result<(&str, &str)> {
+    version.rfind("-0.")
+        .map(|index| (&version[..index], &version[index + 1..]))
+        .ok_or_else(|| anyhow!("Invalid Quilt version {}! Quilt versions are named like 1.21.4-0.27.1", version))
+}
+
+/// Returns the newest stable Minecraft version Quilt supports.
+pub fn latest_quilt_game_version(game_versions: &[QuiltGameVersion]) -> Option<String> {
+    game_versions.iter()
+        .find(|game_version| game_version.stable)
+        .map(|game_version| game_version.version.clone())
+}
+
+/// Returns the Quilt version of the newest stable loader for a Minecraft version.
+pub fn latest_quilt_version(game_version: &str, loaders: &[QuiltLoaderEntry]) -> Option<String> {
+    let versions = loaders.iter().map(|entry| entry.loader.version.clone()).collect::<Vec<_>>();
+    newest_stable(versions.iter())
+        .map(|loader| format!("{}-{}", game_version, loader))
+}
+
+/// Fetches JSON from Quilt's meta API.
+async fn fetch_meta<T: for<'de> Deserialize<'de>>(url: &str) -> Result<T> {
+    reqwest::get(url)
+        .await
+        .context(format!("Failed to fetch {}", url))?
+        .error_for_status()
+        .context(format!("Failed to fetch {}", url))?
+        .json::<T>()
+        .await
+        .context(format!("Failed to parse {}", url))
+}
+
+/// Fetches the loaders Quilt supports for a Minecraft version, none if it does not know it.
+async fn fetch_quilt_loaders(game_version: &str) -> Result<Vec<QuiltLoaderEntry>> {
+    let url = format!("{}/loader/{}", QUILT_META_API, game_version);
+    let response = reqwest::get(&url)
+        .await
+        .context(format!("Failed to fetch {}", url))?;
+    if response.status().is_client_error() {
+        return Ok(Vec::new());
+    }
+    response.error_for_status()
+        .context(format!("Failed to fetch {}", url))?
+        .json()
+        .await
+        .context(format!("Failed to parse {}", url))
+}
+
+/// Resolves a Quilt version.
+///
+/// # Arguments
+/// - `version_to_find`: "latest" for the newest loader of the newest stable Minecraft version, a
+///   Minecraft version for its newest loader, or a Quilt version
+///
+/// # Returns
+/// A `Result` containing the Quilt version, or an error if none matches
+pub async fn get_quilt_version(version_to_find: &str) -> Result<String> {
+    let game_version = if version_to_find == "latest" {
+        let game_versions = fetch_meta::<Vec<QuiltGameVersion>>(&format!("{}/game", QUILT_META_API))
+            .await?;
+        latest_quilt_game_version(&game_versions)
+            .ok_or_else(|| anyhow!("Quilt supports no stable Minecraft version"))?
+    } else {
+        version_to_find.to_string()
+    };
+    if let Some(version) = latest_quilt_version(&game_version, &fetch_quilt_loaders(&game_version).await?) {
+        return Ok(version);
+    }
+
+    let (game_version, loader_version) = split_quilt_version(version_to_find)?;
+    if fetch_quilt_loaders(game_version).await?.iter().any(|entry| entry.loader.version == loader_version) {
+        return Ok(version_to_find.to_string());
+    }
+    Err(anyhow!("Version {} not found! Quilt versions are named like 1.21.4-0.27.1", version_to_find))
+}
+
+/// Fetches the download URL of the newest Quilt installer.
+pub async fn get_quilt_installer_url() -> Result<String> {
+    let versions = fetch_maven_versions(&format!("{}/maven-metadata.xml", QUILT_INSTALLER_MAVEN))
+        .await?;
+    let installer = newest(versions.iter())
+        .ok_or_else(|| anyhow!("No Quilt installer is published"))?;
+    Ok(format!("{0}/{1}/quilt-installer-{1}.jar", QUILT_INSTALLER_MAVEN, installer))
+}
+
+/// Returns the arguments that make the Quilt installer install a server for a Quilt version into
+/// its working directory, along with the Minecraft server it runs.
+pub fn quilt_installer_args(version: &str) -> Result<Vec<String>> {
+    let (game_version, loader_version) = split_quilt_version(version)?;
+    Ok(vec![
+        "install".to_string(),
+        "server".to_string(),
+        game_version.to_string(),
+        loader_version.to_string(),
+        "--download-server".to_string(),
+        "--install-dir=.".to_string(),
+    ])
+}
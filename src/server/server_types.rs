@@ -2,16 +2,38 @@
 
 use std::fmt::Display;
 use anyhow::{anyhow, Result};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Names that cannot be used for server types declared in `config.toml`, since they are built in
+/// or name other directories and settings of mvm.
+pub const RESERVED_NAMES: &[&str] = &["vanilla", "paper", "folia", "purpur", "velocity", "forge", "neoforge", "quilt", "custom", "instances", "backups", "server_types", "backup"];
 
 /// Represents the type of Minecraft server.
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ServerType {
     Vanilla,
-    Paper
+    Paper,
+    Folia,
+    Purpur,
+    /// The Velocity proxy, whose versions are its own releases rather than Minecraft versions.
+    Velocity,
+    /// Forge, installed by running its installer, which leaves libraries and a launch script
+    /// rather than a single jar.
+    Forge,
+    /// NeoForge, installed by running its installer like Forge.
+    NeoForge,
+    /// Quilt, installed by running its installer, which also downloads the Minecraft server.
+    Quilt,
+    /// Jars no upstream API knows about, installed with `mvm install --from`.
+    Custom,
+    /// A server type declared under `[server_types.<name>]` in `config.toml`.
+    Defined(String)
 }
 
 /// Converts a string into a `ServerType` enum.
 ///
+/// Names other than the built in ones are taken as server types declared in `config.toml`, which
+/// is only checked once the declaration is needed.
 ///
 /// # Arguments
 /// - `server_type_string`: A `String` representing the type of server.
@@ -23,9 +45,34 @@ impl ServerType {
         match server_type_string.as_str() {
             "paper" => Ok(ServerType::Paper),
             "vanilla" => Ok(ServerType::Vanilla),
+            "folia" => Ok(ServerType::Folia),
+            "purpur" => Ok(ServerType::Purpur),
+            "velocity" => Ok(ServerType::Velocity),
+            "forge" => Ok(ServerType::Forge),
+            "neoforge" => Ok(ServerType::NeoForge),
+            "quilt" => Ok(ServerType::Quilt),
+            "custom" => Ok(ServerType::Custom),
+            name if is_valid_name(name) && !RESERVED_NAMES.contains(&name) => Ok(ServerType::Defined(name.to_string())),
             _ => Err(anyhow!("Invalid server type: {}", server_type_string)),
         }
     }
+
+    /// Returns whether the server type is a proxy. Proxies have no worlds, do not need the
+    /// Minecraft EULA and are versioned independently of the game.
+    pub fn is_proxy(&self) -> bool {
+        matches!(self, ServerType::Velocity)
+    }
+
+    /// Returns whether the server type is a mod loader installed by running an installer, whose
+    /// installs are started with recorded launch arguments instead of `-jar server.jar`.
+    pub fn is_loader(&self) -> bool {
+        matches!(self, ServerType::Forge | ServerType::NeoForge | ServerType::Quilt)
+    }
+}
+
+/// Returns whether a name can be used for a server type: lowercase letters, digits, `-` and `_`.
+pub fn is_valid_name(name: &str) -> bool {
+    !name.is_empty() && name.bytes().all(|byte| byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'-' || byte == b'_')
 }
 
 /// Converts a `ServerType` into a displayable string.
@@ -33,8 +80,29 @@ impl Display for ServerType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let str = match self {
             ServerType::Vanilla => "vanilla".to_string(),
-            ServerType::Paper => "paper".to_string()
+            ServerType::Paper => "paper".to_string(),
+            ServerType::Folia => "folia".to_string(),
+            ServerType::Purpur => "purpur".to_string(),
+            ServerType::Velocity => "velocity".to_string(),
+            ServerType::Forge => "forge".to_string(),
+            ServerType::NeoForge => "neoforge".to_string(),
+            ServerType::Quilt => "quilt".to_string(),
+            ServerType::Custom => "custom".to_string(),
+            ServerType::Defined(name) => name.clone()
         };
         write!(f, "{}", str)
     }
-}
\ No newline at end of file
+}
+
+impl Serialize for ServerType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_string())
+    }
+}
+
+impl<'de> Deserialize<'de> for ServerType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        ServerType::from_string(name).map_err(serde::de::Error::custom)
+    }
+}
@@ -1,5 +1,6 @@
 //! This submodule is primarily used to serialize and deserialize configuration data stored in a TOML file.
 
+use crate::server::server_types::ServerType;
 use serde::{Deserialize, Serialize};
 
 
@@ -8,8 +9,39 @@ use serde::{Deserialize, Serialize};
 /// # Fields
 /// - `vanilla`: A `String` representing the current Vanilla server version.
 /// - `paper`: A `String` representing the current Paper server version.
-#[derive(Debug, Deserialize, Serialize)]
+/// - `other`: The remaining settings of `config.toml`, such as `[backup]` and the current versions
+///   of other server types, kept as they are when the versions are written.
+#[derive(Debug, Default, Deserialize, Serialize)]
 pub struct VersionConfig {
+    #[serde(default)]
     pub vanilla: String,
-    pub paper: String
-}
\ No newline at end of file
+    #[serde(default)]
+    pub paper: String,
+    #[serde(flatten)]
+    pub other: toml::Table
+}
+
+impl VersionConfig {
+    /// Returns the current version of a server type, empty if none has been set.
+    pub fn get(&self, server_type: &ServerType) -> String {
+        match server_type {
+            ServerType::Vanilla => self.vanilla.clone(),
+            ServerType::Paper => self.paper.clone(),
+            _ => self.other.get(&server_type.to_string())
+                .and_then(|version| version.as_str())
+                .unwrap_or_default()
+                .to_string()
+        }
+    }
+
+    /// Sets the current version of a server type.
+    pub fn set(&mut self, server_type: &ServerType, version: &str) {
+        match server_type {
+            ServerType::Vanilla => self.vanilla = version.to_string(),
+            ServerType::Paper => self.paper = version.to_string(),
+            _ => {
+                self.other.insert(server_type.to_string(), toml::Value::String(version.to_string()));
+            }
+        }
+    }
+}
@@ -23,7 +23,8 @@ pub type VanillaDownloadLink = String;
 
 #[derive(Deserialize, Debug)]
 pub struct VanillaDownloadInfo {
-    pub url: VanillaDownloadLink
+    pub url: VanillaDownloadLink,
+    pub sha1: String
 }
 #[derive(Deserialize, Debug)]
 pub struct ServerDownload {
@@ -0,0 +1,358 @@
+//! Queries the status of a running server using the Server List Ping protocol.
+//!
+//! The modern handshake (Minecraft 1.7 and newer) is tried first. Servers that do not answer it
+//! are queried with the legacy ping used by 1.6 and older.
+
+use crate::instance::get_instance_dir;
+use crate::properties::read_properties;
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::path::Path;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// The port servers listen on when none is given.
+pub const DEFAULT_PORT: u16 = 25565;
+
+/// How long a single status query may take.
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The largest status response accepted, guarding against corrupt length fields.
+const MAX_PACKET_LENGTH: i32 = 2 * 1024 * 1024;
+
+/// Represents the status reported by a server.
+///
+/// # Fields
+/// - `motd`: The message of the day as plain text.
+/// - `protocol`: The protocol version the server speaks.
+/// - `version_name`: The version name the server reports, e.g. `Paper 1.21`.
+/// - `players_online`: The number of players online.
+/// - `players_max`: The maximum number of players.
+/// - `latency_ms`: The round trip time of a ping in milliseconds.
+/// - `legacy`: Whether the server only answered the legacy ping.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ServerStatus {
+    pub motd: String,
+    pub protocol: i32,
+    pub version_name: String,
+    pub players_online: i64,
+    pub players_max: i64,
+    pub latency_ms: u64,
+    pub legacy: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusVersion {
+    name: String,
+    protocol: i32,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusPlayers {
+    max: i64,
+    online: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct StatusResponse {
+    version: StatusVersion,
+    players: Option<StatusPlayers>,
+    #[serde(default)]
+    description: Value,
+}
+
+/// Appends a VarInt to a buffer.
+pub fn write_varint(buffer: &mut Vec<u8>, value: i32) {
+    let mut value = value as u32;
+    loop {
+        if value & !0x7f == 0 {
+            buffer.push(value as u8);
+            return;
+        }
+        buffer.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+}
+
+/// Reads a VarInt from a stream.
+pub async fn read_varint<R: AsyncRead + Unpin>(reader: &mut R) -> Result<i32> {
+    let mut value: u32 = 0;
+    for position in 0..5 {
+        let byte = reader.read_u8()
+            .await
+            .context("Failed to read VarInt")?;
+        value |= ((byte & 0x7f) as u32) << (7 * position);
+        if byte & 0x80 == 0 {
+            return Ok(value as i32);
+        }
+    }
+    Err(anyhow!("VarInt is too long"))
+}
+
+/// Prefixes a packet id and payload with their length.
+fn frame_packet(packet_id: i32, payload: &[u8]) -> Vec<u8> {
+    let mut body = Vec::with_capacity(payload.len() + 5);
+    write_varint(&mut body, packet_id);
+    body.extend_from_slice(payload);
+
+    let mut packet = Vec::with_capacity(body.len() + 5);
+    write_varint(&mut packet, body.len() as i32);
+    packet.extend_from_slice(&body);
+    packet
+}
+
+/// Reads a packet and returns its id and payload.
+async fn read_packet<R: AsyncRead + Unpin>(reader: &mut R) -> Result<(i32, Vec<u8>)> {
+    let length = read_varint(reader).await?;
+    if !(1..=MAX_PACKET_LENGTH).contains(&length) {
+        return Err(anyhow!("Invalid packet length {}", length));
+    }
+
+    let mut body = vec![0u8; length as usize];
+    reader.read_exact(&mut body)
+        .await
+        .context("Failed to read packet")?;
+
+    let mut cursor = body.as_slice();
+    let packet_id = read_varint(&mut cursor).await?;
+    Ok((packet_id, cursor.to_vec()))
+}
+
+/// Converts a chat component, or a plain string, into plain text.
+pub fn chat_to_plain_text(component: &Value) -> String {
+    match component {
+        Value::String(text) => text.clone(),
+        Value::Array(parts) => parts.iter().map(chat_to_plain_text).collect(),
+        Value::Object(object) => {
+            let mut text = object.get("text").and_then(Value::as_str).unwrap_or_default().to_string();
+            if let Some(Value::Array(extra)) = object.get("extra") {
+                for part in extra {
+                    text.push_str(&chat_to_plain_text(part));
+                }
+            }
+            text
+        }
+        _ => String::new()
+    }
+}
+
+/// Queries a server with the modern Server List Ping.
+///
+/// # Arguments
+/// - `host`: The host name or address of the server
+/// - `port`: The port of the server
+///
+/// # Returns
+/// A `Result` containing the status if successful
+pub async fn ping_modern(host: &str, port: u16) -> Result<ServerStatus> {
+    let mut stream = TcpStream::connect((host, port))
+        .await
+        .context(format!("Failed to connect to {}:{}", host, port))?;
+
+    let mut handshake = Vec::new();
+    write_varint(&mut handshake, -1);
+    write_varint(&mut handshake, host.len() as i32);
+    handshake.extend_from_slice(host.as_bytes());
+    handshake.extend_from_slice(&port.to_be_bytes());
+    write_varint(&mut handshake, 1);
+
+    stream.write_all(&frame_packet(0x00, &handshake))
+        .await
+        .context("Failed to send handshake")?;
+    stream.write_all(&frame_packet(0x00, &[]))
+        .await
+        .context("Failed to send status request")?;
+
+    let (packet_id, payload) = read_packet(&mut stream).await?;
+    if packet_id != 0x00 {
+        return Err(anyhow!("Unexpected status response packet {:#x}", packet_id));
+    }
+    let mut cursor = payload.as_slice();
+    let json_length = read_varint(&mut cursor).await? as usize;
+    let json = cursor.get(..json_length)
+        .ok_or_else(|| anyhow!("Status response is truncated"))?;
+    let response = serde_json::from_slice::<StatusResponse>(json)
+        .context("Failed to parse the status response JSON")?;
+
+    let started = Instant::now();
+    let payload = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as i64;
+    stream.write_all(&frame_packet(0x01, &payload.to_be_bytes()))
+        .await
+        .context("Failed to send ping")?;
+    // Some proxies close the connection instead of answering the ping, the status is still valid then.
+    let _pong = read_packet(&mut stream).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let (players_online, players_max) = response.players
+        .map(|players| (players.online, players.max))
+        .unwrap_or((0, 0));
+
+    Ok(ServerStatus {
+        motd: chat_to_plain_text(&response.description),
+        protocol: response.version.protocol,
+        version_name: response.version.name,
+        players_online,
+        players_max,
+        latency_ms,
+        legacy: false,
+    })
+}
+
+/// Queries a server with the legacy ping understood by Minecraft 1.6 and older.
+///
+/// # Arguments
+/// - `host`: The host name or address of the server
+/// - `port`: The port of the server
+///
+/// # Returns
+/// A `Result` containing the status if successful
+pub async fn ping_legacy(host: &str, port: u16) -> Result<ServerStatus> {
+    let mut stream = TcpStream::connect((host, port))
+        .await
+        .context(format!("Failed to connect to {}:{}", host, port))?;
+    let started = Instant::now();
+
+    let channel: Vec<u16> = "MC|PingHost".encode_utf16().collect();
+    let host_utf16: Vec<u16> = host.encode_utf16().collect();
+
+    let mut request = vec![0xfe, 0x01, 0xfa];
+    request.extend_from_slice(&(channel.len() as u16).to_be_bytes());
+    channel.iter().for_each(|unit| request.extend_from_slice(&unit.to_be_bytes()));
+    request.extend_from_slice(&((7 + 2 * host_utf16.len()) as u16).to_be_bytes());
+    request.push(74);
+    request.extend_from_slice(&(host_utf16.len() as u16).to_be_bytes());
+    host_utf16.iter().for_each(|unit| request.extend_from_slice(&unit.to_be_bytes()));
+    request.extend_from_slice(&(port as i32).to_be_bytes());
+
+    stream.write_all(&request)
+        .await
+        .context("Failed to send legacy ping")?;
+
+    let kick = stream.read_u8().await.context("Failed to read legacy ping response")?;
+    if kick != 0xff {
+        return Err(anyhow!("Unexpected legacy ping response {:#x}", kick));
+    }
+    let length = stream.read_u16().await.context("Failed to read legacy ping response")?;
+    let mut units = Vec::with_capacity(length as usize);
+    for _ in 0..length {
+        units.push(stream.read_u16().await.context("Failed to read legacy ping response")?);
+    }
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let mut status = parse_legacy_response(&String::from_utf16_lossy(&units))?;
+    status.latency_ms = latency_ms;
+    Ok(status)
+}
+
+/// Parses the kick message a server sends in response to a legacy ping.
+///
+/// 1.4 to 1.6 answer with `§1\0protocol\0version\0motd\0online\0max`, older servers with
+/// `motd§online§max`.
+pub fn parse_legacy_response(response: &str) -> Result<ServerStatus> {
+    let invalid = || anyhow!("Invalid legacy ping response");
+
+    if let Some(fields) = response.strip_prefix("\u{a7}1\0") {
+        let fields: Vec<&str> = fields.split('\0').collect();
+        if fields.len() < 5 {
+            return Err(invalid());
+        }
+        return Ok(ServerStatus {
+            motd: fields[2].to_string(),
+            protocol: fields[0].parse().map_err(|_| invalid())?,
+            version_name: fields[1].to_string(),
+            players_online: fields[3].parse().map_err(|_| invalid())?,
+            players_max: fields[4].parse().map_err(|_| invalid())?,
+            latency_ms: 0,
+            legacy: true,
+        });
+    }
+
+    let mut fields = response.rsplitn(3, '\u{a7}');
+    let players_max = fields.next().and_then(|max| max.parse().ok()).ok_or_else(invalid)?;
+    let players_online = fields.next().and_then(|online| online.parse().ok()).ok_or_else(invalid)?;
+    let motd = fields.next().ok_or_else(invalid)?;
+
+    Ok(ServerStatus {
+        motd: motd.to_string(),
+        protocol: -1,
+        version_name: String::new(),
+        players_online,
+        players_max,
+        latency_ms: 0,
+        legacy: true,
+    })
+}
+
+/// Queries a server, falling back to the legacy ping if the modern handshake fails.
+///
+/// # Arguments
+/// - `host`: The host name or address of the server
+/// - `port`: The port of the server
+///
+/// # Returns
+/// A `Result` containing the status if successful
+pub async fn query_status(host: &str, port: u16) -> Result<ServerStatus> {
+    let modern = tokio::time::timeout(QUERY_TIMEOUT, ping_modern(host, port))
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("Timed out")));
+
+    match modern {
+        Ok(status) => Ok(status),
+        Err(modern_error) => tokio::time::timeout(QUERY_TIMEOUT, ping_legacy(host, port))
+            .await
+            .unwrap_or_else(|_| Err(anyhow!("Timed out")))
+            .map_err(|_| modern_error.context(format!("Server at {}:{} did not respond to a status query", host, port)))
+    }
+}
+
+/// Splits a `host[:port]` target into host and port. IPv6 hosts are written in brackets when
+/// followed by a port, as in `[::1]:25565`.
+pub fn parse_address(address: &str) -> Result<(String, u16)> {
+    if let Some(bracketed) = address.strip_prefix('[') {
+        let (host, rest) = bracketed.split_once(']')
+            .ok_or_else(|| anyhow!("Missing ] in {}", address))?;
+        let port = match rest.strip_prefix(':') {
+            Some(port) => port.parse::<u16>().context(format!("Invalid port in {}", address))?,
+            None if rest.is_empty() => DEFAULT_PORT,
+            None => return Err(anyhow!("Invalid address {}", address))
+        };
+        return Ok((host.to_string(), port));
+    }
+
+    match address.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') => {
+            let port = port.parse::<u16>()
+                .context(format!("Invalid port in {}", address))?;
+            Ok((host.to_string(), port))
+        }
+        _ => Ok((address.to_string(), DEFAULT_PORT))
+    }
+}
+
+/// Resolves a status target into a host and port. The target is either an instance, whose
+/// `server-ip` and `server-port` are read from its `server.properties`, or a `host[:port]` address.
+///
+/// # Arguments
+/// - `target`: The instance or address to query
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the host and port if successful
+pub async fn resolve_target(target: &str, path: &Path) -> Result<(String, u16)> {
+    let properties_path = get_instance_dir(target, path).join("server.properties");
+    if !properties_path.exists() {
+        return parse_address(target);
+    }
+
+    let properties = read_properties(&properties_path)
+        .await?;
+    let host = properties.local_host().to_string();
+    let port = match properties.get("server-port") {
+        Some(port) => port.parse::<u16>().context("Invalid server-port in server.properties")?,
+        None => DEFAULT_PORT
+    };
+
+    Ok((host, port))
+}
@@ -0,0 +1,158 @@
+//! Stops running servers gracefully.
+//!
+//! Players are warned with a countdown, the world is saved and the server is asked to stop through
+//! its console. Signals are only used when the server does not exit in time or has no reachable console.
+
+use crate::process::{self, Signal};
+use crate::rcon::{self, RconClient};
+use anyhow::{anyhow, Result};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedSender;
+
+/// The points of a countdown, in seconds before the stop, at which players are warned.
+const COUNTDOWN_MARKS: &[u64] = &[600, 300, 120, 60, 30, 10, 5, 4, 3, 2, 1];
+
+/// How long a server may take to exit after SIGTERM before it is killed.
+const TERMINATE_GRACE: Duration = Duration::from_secs(30);
+
+/// Represents the options of a graceful stop.
+///
+/// # Fields
+/// - `countdown`: Seconds between the first warning and the stop, 0 to stop immediately.
+/// - `message`: The warning broadcast to players, `{seconds}` is replaced with the remaining time.
+/// - `timeout`: How long to wait for the server to exit after the stop command.
+#[derive(Debug, Clone)]
+pub struct StopOptions {
+    pub countdown: u64,
+    pub message: String,
+    pub timeout: Duration,
+}
+
+/// A way of sending commands to the console of a running server.
+pub enum ConsoleChannel {
+    Rcon(RconClient),
+    Pipe(PathBuf),
+    Stdin(UnboundedSender<String>),
+}
+
+impl ConsoleChannel {
+    /// Sends a console command to the server.
+    pub async fn send(&mut self, command: &str) -> Result<()> {
+        match self {
+            ConsoleChannel::Rcon(client) => client.command(command).await.map(|_| ()),
+            ConsoleChannel::Pipe(instance_dir) => process::send_to_console_pipe(instance_dir, command),
+            ConsoleChannel::Stdin(sender) => sender.send(command.to_string())
+                .map_err(|_| anyhow!("The server console is closed")),
+        }
+    }
+}
+
+/// Opens a console channel to the running server of an instance. RCON is preferred, the console
+/// pipe of `mvm run` is used otherwise.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+///
+/// # Returns
+/// The channel, or `None` if the console cannot be reached
+pub async fn open_console_channel(instance_dir: &Path) -> Option<ConsoleChannel> {
+    if let Ok(client) = rcon::connect_instance(instance_dir).await {
+        return Some(ConsoleChannel::Rcon(client));
+    }
+
+    if instance_dir.join(process::CONSOLE_PIPE).exists() {
+        return Some(ConsoleChannel::Pipe(instance_dir.to_path_buf()));
+    }
+
+    None
+}
+
+/// Returns the seconds before the stop at which players are warned, in descending order.
+/// The full countdown is always announced first.
+pub fn countdown_marks(countdown: u64) -> Vec<u64> {
+    if countdown == 0 {
+        return Vec::new();
+    }
+
+    let mut marks = vec![countdown];
+    marks.extend(COUNTDOWN_MARKS.iter().copied().filter(|mark| *mark < countdown));
+    marks
+}
+
+/// Broadcasts the countdown, saves the world and stops the server of an instance, then waits for
+/// it to exit. Escalates to SIGTERM and finally SIGKILL if the server does not exit in time.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `options`: The countdown and timeout to use
+pub async fn stop_instance(instance_dir: &Path, options: &StopOptions) -> Result<()> {
+    let pid = process::find_server_pid(instance_dir).await;
+    let channel = open_console_channel(instance_dir).await;
+
+    stop_process(channel, pid, options)
+        .await
+}
+
+/// Stops a server through the given console channel and waits for its process to exit,
+/// escalating to signals as described for [`stop_instance`].
+///
+/// # Arguments
+/// - `channel`: The console of the server, `None` to go straight to SIGTERM
+/// - `pid`: The process id of the server, if known
+/// - `options`: The countdown and timeout to use
+pub async fn stop_process(channel: Option<ConsoleChannel>, pid: Option<u32>, options: &StopOptions) -> Result<()> {
+    match channel {
+        Some(mut channel) => {
+            let marks = countdown_marks(options.countdown);
+            for (index, seconds) in marks.iter().enumerate() {
+                let message = options.message.replace("{seconds}", &seconds.to_string());
+                channel.send(&format!("say {}", message)).await?;
+                println!("{}", message);
+
+                let next = marks.get(index + 1).copied().unwrap_or(0);
+                tokio::time::sleep(Duration::from_secs(seconds - next)).await;
+            }
+
+            channel.send("save-all").await?;
+            // The server closes the connection while handling `stop`, so a failed reply is expected.
+            if let Err(err) = channel.send("stop").await {
+                if pid.is_none() {
+                    return Err(err.context("Failed to send the stop command"));
+                }
+            }
+            println!("Stop command sent");
+        }
+        None => {
+            let pid = pid.ok_or_else(|| anyhow!("The server of this instance is not running"))?;
+            println!("No console available, sending SIGTERM to process {}", pid);
+            process::send_signal(pid, Signal::Terminate)?;
+        }
+    }
+
+    let Some(pid) = pid else {
+        println!("Process id unknown, unable to confirm that the server exited");
+        return Ok(());
+    };
+
+    if process::wait_for_exit(pid, options.timeout).await {
+        println!("Server stopped");
+        return Ok(());
+    }
+
+    println!("Server did not exit within {} seconds, sending SIGTERM", options.timeout.as_secs());
+    process::send_signal(pid, Signal::Terminate)?;
+    if process::wait_for_exit(pid, TERMINATE_GRACE).await {
+        println!("Server stopped");
+        return Ok(());
+    }
+
+    println!("Server did not exit after SIGTERM, sending SIGKILL");
+    process::send_signal(pid, Signal::Kill)?;
+    if process::wait_for_exit(pid, TERMINATE_GRACE).await {
+        println!("Server killed");
+        return Ok(());
+    }
+
+    Err(anyhow!("Process {} is still running after SIGKILL", pid))
+}
@@ -0,0 +1,237 @@
+//! Audits installed server jars for corruption and tampering.
+//!
+//! Every installed `server.jar` is hashed again and compared with the hashes recorded in its
+//! `install.toml` and, unless checking offline, with the hashes the upstream project publishes:
+//! the SHA-1 of Mojang's version details, the SHA-256 of Paper's and Folia's builds and the MD5 of Purpur's.
+//! Forge, NeoForge and Quilt installs keep their installer in place of a jar, which is compared with
+//! the SHA-1 published next to it on their Maven repository.
+
+use crate::provenance::{hash_file, read_install_info, FileHashes, Integrity};
+use crate::server::forge::forge_installer_url;
+use crate::server::loader::get_installer_sha1;
+use crate::server::neoforge::neoforge_installer_url;
+use crate::server::server_types::ServerType;
+use crate::version_manager::{download_server_jar, get_latest_purpur_build, get_paper_build_hashes, get_purpur_build_md5, get_server_jar_path, get_download_url, get_vanilla_server_sha1};
+use anyhow::{Context, Result};
+use std::path::Path;
+use tokio::fs;
+
+/// The outcome of verifying one installed jar.
+///
+/// # Fields
+/// - `server_type`: The type of server
+/// - `version`: The installed version
+/// - `integrity`: Whether the jar is intact, modified, missing or of unknown origin
+/// - `details`: What the jar was compared with and the outcome of each comparison
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JarReport {
+    pub server_type: ServerType,
+    pub version: String,
+    pub integrity: Integrity,
+    pub details: Vec<String>,
+}
+
+impl JarReport {
+    /// Returns whether the jar is modified or missing and should be downloaded again.
+    pub fn is_failure(&self) -> bool {
+        matches!(self.integrity, Integrity::Modified | Integrity::Missing)
+    }
+}
+
+/// Lists the server types that have versions installed.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+pub async fn installed_server_types(path: &Path) -> Vec<ServerType> {
+    let mut server_types = Vec::new();
+    let Ok(mut entries) = fs::read_dir(path).await else {
+        return server_types;
+    };
+
+    while let Ok(Some(entry)) = entries.next_entry().await {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if let Ok(server_type) = ServerType::from_string(name) {
+            if entry.path().join("versions").is_dir() {
+                server_types.push(server_type);
+            }
+        }
+    }
+    server_types.sort_by_key(|server_type| server_type.to_string());
+    server_types
+}
+
+/// Compares a jar with the hashes its upstream project publishes.
+///
+/// # Returns
+/// A `Result` containing whether the jar matches and what it was compared with, or `None` if
+/// upstream does not know the jar
+async fn check_upstream(version: &str, server_type: &ServerType, hashes: &FileHashes, build: Option<u32>, source: Option<&str>) -> Result<Option<(bool, String)>> {
+    match server_type {
+        ServerType::Vanilla => {
+            let sha1 = get_vanilla_server_sha1(version)
+                .await?;
+            Ok(Some((sha1 == hashes.sha1, "Mojang's SHA-1".to_string())))
+        }
+        ServerType::Paper | ServerType::Folia | ServerType::Velocity => {
+            let project = server_type.to_string();
+            let title = match server_type {
+                ServerType::Folia => "Folia",
+                ServerType::Velocity => "Velocity",
+                _ => "Paper"
+            };
+            let builds = get_paper_build_hashes(&project, version)
+                .await?;
+            let verdict = match build {
+                Some(build) => builds.into_iter()
+                    .find(|(number, _)| *number == build)
+                    .map(|(_, sha256)| (sha256 == hashes.sha256, format!("{} build {}", title, build))),
+                None => builds.into_iter()
+                    .find(|(_, sha256)| *sha256 == hashes.sha256)
+                    .map(|(number, _)| (true, format!("{} build {}", title, number)))
+            };
+            Ok(verdict)
+        }
+        ServerType::Purpur => {
+            // Purpur publishes hashes per build, so a jar without a recorded build is only
+            // compared with the latest one.
+            let (build, recorded) = match build {
+                Some(build) => (build.to_string(), true),
+                None => (get_latest_purpur_build(version).await?, false)
+            };
+            let matches = get_purpur_build_md5(version, &build).await? == hashes.md5;
+            if !matches && !recorded {
+                return Ok(None);
+            }
+            Ok(Some((matches, format!("Purpur build {}", build))))
+        }
+        ServerType::Forge | ServerType::NeoForge => {
+            let (installer_url, title) = match server_type {
+                ServerType::NeoForge => (neoforge_installer_url(version), "NeoForge"),
+                _ => (forge_installer_url(version), "Forge")
+            };
+            let sha1 = get_installer_sha1(&installer_url)
+                .await?;
+            Ok(Some((sha1 == hashes.sha1, format!("{}'s SHA-1 of the installer", title))))
+        }
+        // Quilt versions are installed by whichever installer was the newest, so the recorded one is checked.
+        ServerType::Quilt => match source {
+            Some(source) if source.starts_with("http") => {
+                let sha1 = get_installer_sha1(source)
+                    .await?;
+                Ok(Some((sha1 == hashes.sha1, "Quilt's SHA-1 of the installer".to_string())))
+            }
+            _ => Ok(None)
+        },
+        ServerType::Custom | ServerType::Defined(_) => Ok(None)
+    }
+}
+
+/// Verifies one installed jar.
+///
+/// # Arguments
+/// - `version`: The installed version
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+/// - `upstream`: Whether to also compare with the hashes published upstream
+///
+/// # Returns
+/// A `Result` containing the report if the jar could be checked
+pub async fn verify_jar(version: &str, server_type: &ServerType, path: &Path, upstream: bool) -> Result<JarReport> {
+    let mut report = JarReport {
+        server_type: server_type.clone(),
+        version: version.to_string(),
+        integrity: Integrity::Missing,
+        details: Vec::new(),
+    };
+    let jar_path = get_server_jar_path(version, server_type, path);
+    if !jar_path.exists() {
+        report.details.push("server.jar is missing".to_string());
+        return Ok(report);
+    }
+
+    let (hash_path, info_version, info_type, info_dir) = (jar_path.clone(), version.to_string(), server_type.clone(), path.to_path_buf());
+    let (hashes, info) = tokio::task::spawn_blocking(move || -> Result<_> {
+        Ok((hash_file(&hash_path)?, read_install_info(&info_version, &info_type, &info_dir)?))
+    })
+    .await
+    .context("Failed to hash the jar")??;
+
+    let mut verdicts = Vec::new();
+    let mut notes = Vec::new();
+    match &info {
+        Some(info) => {
+            let matches = info.sha1 == hashes.sha1 && info.sha256 == hashes.sha256 && info.size == hashes.size;
+            verdicts.push((matches, "install.toml".to_string()));
+        }
+        None => notes.push("no install.toml".to_string())
+    }
+    if upstream {
+        match check_upstream(version, server_type, &hashes, info.as_ref().and_then(|info| info.build), info.as_ref().map(|info| info.source.as_str())).await {
+            Ok(Some(verdict)) => verdicts.push(verdict),
+            Ok(None) => notes.push(format!("not published by {}", server_type)),
+            Err(err) => notes.push(format!("upstream unavailable: {}", err))
+        }
+    }
+
+    report.integrity = if verdicts.iter().any(|(matches, _)| !matches) {
+        Integrity::Modified
+    } else if !verdicts.is_empty() {
+        Integrity::Intact
+    } else {
+        Integrity::UnknownOrigin
+    };
+    report.details = verdicts.into_iter()
+        .map(|(matches, against)| format!("{} {}", if matches { "matches" } else { "differs from" }, against))
+        .chain(notes)
+        .collect();
+
+    Ok(report)
+}
+
+/// Verifies every installed jar of every server type.
+///
+/// # Arguments
+/// - `path`: The root directory of server installations
+/// - `upstream`: Whether to also compare with the hashes published upstream
+///
+/// # Returns
+/// A `Result` containing a report per installed version
+pub async fn verify_jars(path: &Path, upstream: bool) -> Result<Vec<JarReport>> {
+    let mut reports = Vec::new();
+    for server_type in installed_server_types(path).await {
+        let versions_dir = path.join(server_type.to_string()).join("versions");
+        let mut entries = fs::read_dir(&versions_dir)
+            .await
+            .context(format!("Failed to read {:?}", versions_dir))?;
+        let mut versions = Vec::new();
+        while let Some(entry) = entries.next_entry().await.context(format!("Failed to read {:?}", versions_dir))? {
+            if entry.path().is_dir() {
+                versions.push(entry.file_name().to_string_lossy().to_string());
+            }
+        }
+        versions.sort();
+
+        for version in versions {
+            reports.push(verify_jar(&version, &server_type, path, upstream).await?);
+        }
+    }
+
+    Ok(reports)
+}
+
+/// Downloads a jar that failed verification again, from the URL it was installed from if that is
+/// recorded.
+///
+/// # Arguments
+/// - `report`: The report of the jar
+/// - `path`: The root directory of server installations
+pub async fn redownload(report: &JarReport, path: &Path) -> Result<()> {
+    let source = match read_install_info(&report.version, &report.server_type, path)? {
+        Some(info) if info.source.starts_with("http") => info.source,
+        _ => get_download_url(&report.version, &report.server_type, path).await?
+    };
+
+    download_server_jar(source, &report.version, &report.server_type, path)
+        .await
+        .context(format!("Failed to download {} {} again", report.server_type, report.version))
+}
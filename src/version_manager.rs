@@ -1,19 +1,28 @@
 //! Handles version management for Minecraft servers.
 //! It provides utilities for retrieving and processing server versions.
 
-use crate::server::vanilla::{VanillaDownloadLink, Latest, VersionDownloads, VanillaVersions};
-use crate::server::paper::{PaperVersions, PaperVersion, PaperVersionBuilds, PaperDownloadLink};
+use crate::server::vanilla::{VanillaDownloadInfo, VanillaDownloadLink, Latest, VersionDownloads, VanillaVersions};
+use crate::server::paper::{PaperBuilds, PaperVersions, PaperVersion, PaperVersionBuilds, PaperDownloadLink, PAPERMC_API};
+use crate::server::purpur::{PurpurBuild, PurpurDownloadLink, PurpurVersion, PurpurVersionBuilds, PurpurVersions};
+use crate::server::defined::read_definition;
+use crate::server::forge::{forge_game_version, forge_installer_url, get_forge_version, FORGE_INSTALLER_ARGS, FORGE_SERVER_JARS};
+use crate::server::neoforge::{get_neoforge_version, neoforge_game_version, neoforge_installer_url, NEOFORGE_INSTALLER_ARGS, NEOFORGE_SERVER_JARS};
+use crate::server::quilt::{get_quilt_installer_url, get_quilt_version, quilt_installer_args, split_quilt_version, QUILT_JVM_ARGS, QUILT_SERVER_JARS};
+use crate::server::loader::{install_server, INSTALLER_FILE, LAUNCH_FILE};
 use crate::server::server_types::ServerType;
 use crate::server::toml_config::VersionConfig;
+use crate::metadata::store_metadata;
+use crate::provenance::{hash_file, paper_build, purpur_build, record_install};
 use anyhow::{anyhow, Context, Result};
 use futures_util::stream::StreamExt;
 use reqwest;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use tokio::fs::{self, File};
 use tokio::io::AsyncWriteExt;
 use toml;
 
-/// Fetches the download link for a specified version of the given type of minecraft server
+/// Fetches the download link for a specified version of the given type of minecraft server.
+/// Types declared in `config.toml` are looked up with `get_download_url`.
 ///
 /// # Arguments
 /// - `version_to_find`: A reference to the version string to fetch the download link
@@ -23,8 +32,38 @@ use toml;
 /// A `Result` containing the download link as a String if successful
 pub async fn get_version_download(version_to_find: &str, server_type: &ServerType) -> Result<String> {
     match server_type {
-        ServerType::Vanilla => get_vanilla_download_url(&version_to_find).await,
-        ServerType::Paper => get_paper_download_url(&version_to_find).await,
+        ServerType::Vanilla => get_vanilla_download_url(version_to_find).await,
+        ServerType::Paper | ServerType::Folia | ServerType::Velocity => get_paper_download_url(&server_type.to_string(), version_to_find).await,
+        ServerType::Purpur => get_purpur_download_url(version_to_find).await,
+        ServerType::Forge => Ok(forge_installer_url(&get_forge_version(version_to_find).await?)),
+        ServerType::NeoForge => Ok(neoforge_installer_url(&get_neoforge_version(version_to_find).await?)),
+        // The newest installer installs any Quilt version, which is only checked to exist.
+        ServerType::Quilt => {
+            get_quilt_version(version_to_find)
+                .await?;
+            get_quilt_installer_url().await
+        }
+        ServerType::Custom => Err(anyhow!("Custom versions have no upstream, install them with --from")),
+        ServerType::Defined(name) => Err(anyhow!("The server type {} is declared in config.toml, look it up with get_download_url", name)),
+    }
+}
+
+/// Fetches the download link for a version like `get_version_download`, reading the declarations
+/// of server types from the `config.toml` in the given directory.
+///
+/// # Arguments
+/// - `version_to_find`: A reference to the version string to fetch the download link
+/// - `server_type`: The type of server for the requested download link
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the download link as a String if successful
+pub async fn get_download_url(version_to_find: &str, server_type: &ServerType, path: &Path) -> Result<String> {
+    match server_type {
+        ServerType::Defined(name) => read_definition(name, path)?
+            .download_url(version_to_find)
+            .await,
+        _ => get_version_download(version_to_find, server_type).await
     }
 }
 
@@ -52,6 +91,22 @@ pub async fn get_latest_vanilla_version() -> Result<Latest> {
 /// # Returns
 /// A `Result` containing the download link for the vanilla Minecraft server version as a String if successful
 pub async fn get_vanilla_download_url(version_to_find: &str) -> Result<VanillaDownloadLink> {
+    Ok(get_vanilla_server_download(version_to_find).await?.url)
+}
+
+/// Fetches the SHA-1 hash Mojang publishes for the server jar of a vanilla version.
+///
+/// # Arguments
+/// - `version_to_find`: A reference to the version string
+///
+/// # Returns
+/// A `Result` containing the hex encoded hash if successful
+pub async fn get_vanilla_server_sha1(version_to_find: &str) -> Result<String> {
+    Ok(get_vanilla_server_download(version_to_find).await?.sha1)
+}
+
+/// Fetches the download of the server jar of a vanilla version from its version details.
+async fn get_vanilla_server_download(version_to_find: &str) -> Result<VanillaDownloadInfo> {
     let version_id = if version_to_find == "latest" {
         let latest_version = get_latest_vanilla_version()
             .await
@@ -78,67 +133,203 @@ pub async fn get_vanilla_download_url(version_to_find: &str) -> Result<VanillaDo
             .await
             .context("Failed to parse version details JSON")?;
 
-        let download_url = version_info.downloads.server.url;
-
-        return Ok(download_url)
+        return Ok(version_info.downloads.server)
     }
 
     Err(anyhow!("Version {} not found!", &version_id))
 }
 
 
-/// Returns a Result containing the latest Paper server version as a String if successful
-pub async fn get_latest_paper_version() -> Result<PaperVersion> {
-    let mut response = reqwest::get("https://api.papermc.io/v2/projects/paper")
+/// Returns a Result containing the latest version of a PaperMC project as a String if successful
+///
+/// # Arguments
+/// - `project`: The PaperMC project, such as `paper` or `folia`
+pub async fn get_latest_paper_version(project: &str) -> Result<PaperVersion> {
+    let mut response = reqwest::get(format!("{}/{}", PAPERMC_API, project))
         .await
-        .context("Error fetching the latest paper version")?
+        .context(format!("Error fetching the latest {} version", project))?
         .json::<PaperVersions>()
         .await
-        .context("Failed to parse the latest paper version JSON")?;
+        .context(format!("Failed to parse the latest {} version JSON", project))?;
 
     if let Some(latest) = response.versions.pop() {
         Ok(latest)
     } else {
-        Err(anyhow!("Failed to retrieve the latest paper version from array."))
+        Err(anyhow!("Failed to retrieve the latest {} version from array.", project))
     }
 
 }
 
-/// Fetches the download link for a specific Paper Minecraft server given the version
+/// Fetches the SHA-256 hashes a PaperMC project publishes for every build of a version.
+///
+/// # Arguments
+/// - `project`: The PaperMC project, such as `paper` or `folia`
+/// - `version`: A reference to the version of the project
+///
+/// # Returns
+/// A `Result` containing the build numbers with the hex encoded hashes of their jars if successful
+pub async fn get_paper_build_hashes(project: &str, version: &str) -> Result<Vec<(u32, String)>> {
+    let response = reqwest::get(format!("{}/{}/versions/{}/builds", PAPERMC_API, project, version))
+        .await
+        .context(format!("Failed to fetch the builds of {} version {}", project, version))?
+        .json::<PaperBuilds>()
+        .await
+        .context(format!("Failed to parse the {} builds JSON", project))?;
+
+    Ok(response.builds
+        .into_iter()
+        .map(|build| (build.build, build.downloads.application.sha256))
+        .collect())
+}
+
+/// Fetches the download link for the latest build of a version of a PaperMC project
 /// If the version to find is "latest", it retrieves the most recent version automatically
 ///
 /// # Arguments
+/// - `project`: The PaperMC project, such as `paper` or `folia`
 /// - `version_to_find`: A reference to the version string to fetch the download link
 ///
 /// # Returns
-/// A `Result` containing the download link for the vanilla Minecraft server version as a String if successful
-pub async fn get_paper_download_url(version_to_find: &str) -> Result<PaperDownloadLink> {
+/// A `Result` containing the download link for the server version as a String if successful
+pub async fn get_paper_download_url(project: &str, version_to_find: &str) -> Result<PaperDownloadLink> {
     let version_id = if version_to_find == "latest" {
-        let latest_version = get_latest_paper_version()
+        let latest_version = get_latest_paper_version(project)
             .await?;
         latest_version
     } else {
         version_to_find.to_string()
     };
 
-    let response = reqwest::get(format!("https://api.papermc.io/v2/projects/paper/versions/{}", version_id))
+    let response = reqwest::get(format!("{}/{}/versions/{}", PAPERMC_API, project, version_id))
         .await
         .context("Version not found!")?
         .json::<PaperVersionBuilds>()
         .await
-        .context("Failed to parse the paper version builds JSON")?;
+        .context(format!("Failed to parse the {} version builds JSON", project))?;
 
     if let Some(latest_build) = response.builds.last() {
-        let jar_name = format!("paper-{}-{}.jar", version_id, latest_build);
-        let download_url = format!("https://api.papermc.io/v2/projects/paper/versions/{}/builds/{}/downloads/{}", version_id, latest_build, jar_name);
+        let jar_name = format!("{}-{}-{}.jar", project, version_id, latest_build);
+        let download_url = format!("{}/{}/versions/{}/builds/{}/downloads/{}", PAPERMC_API, project, version_id, latest_build, jar_name);
         Ok(download_url)
     } else {
-        Err(anyhow!(format!("Failed to reteive the latest build for paper version {}", version_id)))
+        Err(anyhow!(format!("Failed to reteive the latest build for {} version {}", project, version_id)))
     }
 
 
 }
 
+/// Returns a Result containing the latest Purpur server version as a String if successful
+pub async fn get_latest_purpur_version() -> Result<PurpurVersion> {
+    let mut response = reqwest::get("https://api.purpurmc.org/v2/purpur")
+        .await
+        .context("Error fetching the latest purpur version")?
+        .json::<PurpurVersions>()
+        .await
+        .context("Failed to parse the latest purpur version JSON")?;
+
+    response.versions
+        .pop()
+        .ok_or_else(|| anyhow!("Failed to retrieve the latest purpur version from array."))
+}
+
+/// Fetches the latest build of a Purpur version.
+///
+/// # Arguments
+/// - `version`: A reference to the Purpur version
+///
+/// # Returns
+/// A `Result` containing the build number as a String if successful
+pub async fn get_latest_purpur_build(version: &str) -> Result<String> {
+    let response = reqwest::get(format!("https://api.purpurmc.org/v2/purpur/{}", version))
+        .await
+        .context(format!("Failed to fetch the builds of purpur version {}", version))?
+        .json::<PurpurVersionBuilds>()
+        .await
+        .context(format!("Version {} not found!", version))?;
+
+    if response.builds.all.is_empty() {
+        return Err(anyhow!("Failed to retrieve the latest build for purpur version {}", version));
+    }
+    Ok(response.builds.latest)
+}
+
+/// Fetches the MD5 hash Purpur publishes for a build of a version.
+///
+/// # Arguments
+/// - `version`: A reference to the Purpur version
+/// - `build`: The build number
+///
+/// # Returns
+/// A `Result` containing the hex encoded hash if successful
+pub async fn get_purpur_build_md5(version: &str, build: &str) -> Result<String> {
+    let response = reqwest::get(format!("https://api.purpurmc.org/v2/purpur/{}/{}", version, build))
+        .await
+        .context(format!("Failed to fetch build {} of purpur version {}", build, version))?
+        .json::<PurpurBuild>()
+        .await
+        .context("Failed to parse the purpur build JSON")?;
+
+    Ok(response.md5)
+}
+
+/// Fetches the download link for the latest build of a specific Purpur server given the version
+/// If the version to find is "latest", it retrieves the most recent version automatically
+///
+/// # Arguments
+/// - `version_to_find`: A reference to the version string to fetch the download link
+///
+/// # Returns
+/// A `Result` containing the download link for the Purpur server version as a String if successful
+pub async fn get_purpur_download_url(version_to_find: &str) -> Result<PurpurDownloadLink> {
+    let version_id = if version_to_find == "latest" {
+        get_latest_purpur_version()
+            .await?
+    } else {
+        version_to_find.to_string()
+    };
+
+    let latest_build = get_latest_purpur_build(&version_id)
+        .await?;
+    Ok(format!("https://api.purpurmc.org/v2/purpur/{}/{}/download", version_id, latest_build))
+}
+
+/// Fetches the download link for a specific build of a Purpur version.
+///
+/// # Arguments
+/// - `version`: A reference to the Purpur version
+/// - `build`: The build number to pin
+///
+/// # Returns
+/// A `Result` containing the download link if the build exists
+pub async fn get_purpur_build_url(version: &str, build: u32) -> Result<PurpurDownloadLink> {
+    let response = reqwest::get(format!("https://api.purpurmc.org/v2/purpur/{}", version))
+        .await
+        .context(format!("Failed to fetch the builds of purpur version {}", version))?
+        .json::<PurpurVersionBuilds>()
+        .await
+        .context(format!("Version {} not found!", version))?;
+
+    if !response.builds.all.contains(&build.to_string()) {
+        return Err(anyhow!("Purpur version {} has no build {}", version, build));
+    }
+    Ok(format!("https://api.purpurmc.org/v2/purpur/{}/{}/download", version, build))
+}
+
+/// Compares a downloaded Purpur jar with the MD5 Purpur publishes for its build.
+async fn check_purpur_md5(jar_path: &Path, version: &str, build: u32) -> Result<()> {
+    let expected = get_purpur_build_md5(version, &build.to_string())
+        .await?;
+    let hashed_path = jar_path.to_path_buf();
+    let actual = tokio::task::spawn_blocking(move || hash_file(&hashed_path))
+        .await
+        .context("Failed to hash the server jar")??
+        .md5;
+    if !actual.eq_ignore_ascii_case(&expected) {
+        return Err(anyhow!("The downloaded jar has the MD5 {}, but Purpur publishes {} for build {} of {}", actual, expected, build, version));
+    }
+    Ok(())
+}
+
 /// Retrieves the path to the specified server version's 'server.jar' file
 /// If the version is set to "recent," it fetches the version from the config file.
 ///
@@ -148,7 +339,7 @@ pub async fn get_paper_download_url(version_to_find: &str) -> Result<PaperDownlo
 /// - `path`: The root directory of server installations
 /// # Returns
 /// A result containing the path as a String if successful
-pub async fn get_version(version_to_find: &str, server_type: &ServerType, path: &PathBuf) -> Result<String> {
+pub async fn get_version(version_to_find: &str, server_type: &ServerType, path: &Path) -> Result<String> {
     let mvm_dir = path;
     let config_path = mvm_dir.join("config.toml");
     if !config_path.exists() {
@@ -161,16 +352,12 @@ pub async fn get_version(version_to_find: &str, server_type: &ServerType, path:
             .context("Failed to read config.toml")?;
         let version_config = toml::from_str::<VersionConfig>(&toml_content)
             .context("Failed to deserialize version config")?;
-        match server_type {
-            ServerType::Vanilla => version_config.vanilla,
-            ServerType::Paper => version_config.paper
-        }
+        version_config.get(server_type)
     } else {
         version_to_find.to_string()
     };
 
-    let server_type_dir = mvm_dir.join(server_type.to_string());
-    let version_path = server_type_dir.join("versions").join(&version).join("server.jar");
+    let version_path = get_server_jar_path(&version, server_type, mvm_dir);
 
     if !version_path.exists() {
         return Err(anyhow!("Version '{}' not found", &version));
@@ -183,33 +370,33 @@ pub async fn get_version(version_to_find: &str, server_type: &ServerType, path:
 }
 
 
-///Fetches the download link for a specific vanilla Minecraft server given the version
-///
-/// If the version to find is "latest", it retrieves the most recent version automatically
+///Downloads a server jar and installs it as the given version
 ///
 ///# Arguments
 /// - `file_url`: Represents the URL from where the JAR file should be downloaded from
-/// - `version`: A reference to the version of the minecraft server that it is downloading.
+/// - `version`: The version the jar is installed as, already resolved with `resolve_version`, so
+///   that it is the version `file_url` was built for
 /// - `server_type`: The type of server for the requested version
 /// - `path`: The root directory of server installations
+pub async fn download_server_jar(file_url: String, version: &str, server_type: &ServerType, path: &Path) -> Result<()> {
+    check_version_name(version)?;
+    if server_type.is_loader() {
+        return install_loader(&file_url, version, server_type, path)
+            .await;
+    }
 
-pub async fn download_server_jar(file_url: String, version_to_download: &str, server_type: &ServerType, path: &PathBuf) -> Result<()> {
     let response = reqwest::get(&file_url)
         .await
         .context(format!("Failed to send request to download server jar! Download link: {}", &file_url))?;
+    if !response.status().is_success() {
+        return Err(anyhow!("Failed to download server jar, the server responded with {}", response.status()));
+    }
 
-    let version = if version_to_download == "latest" {
-        let latest_version = get_latest_paper_version()
-            .await?;
-        latest_version
-    } else {
-        version_to_download.to_string()
-    };
-
+    let version = version.to_string();
     let mvm_dir = path;
 
     let server_type_dir = mvm_dir.join(server_type.to_string());
-    let version_dir = server_type_dir.join("versions").join(version);
+    let version_dir = server_type_dir.join("versions").join(&version);
 
     if !version_dir.exists() {
         fs::create_dir_all(&version_dir)
@@ -219,24 +406,133 @@ pub async fn download_server_jar(file_url: String, version_to_download: &str, se
 
     let server_jar_path = version_dir.join("server.jar");
 
-    if response.status().is_success() {
-        let mut file = File::create(&server_jar_path)
+    let mut file = File::create(&server_jar_path)
+        .await
+        .context("Failed to create server.jar file")?;
+    let mut stream = response.bytes_stream();
+
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.context("Failed to read chunk from response")?;
+        file.write_all(&chunk)
             .await
-            .context("Failed to create server.jar file")?;
-        let mut stream = response.bytes_stream();
+            .context("Failed to write chunk for server.jar file")?;
+    }
 
-        while let Some(chunk) = stream.next().await {
-            let chunk = chunk.context("Failed to read chunk from response")?;
-            file.write_all(&chunk)
-                .await
-                .context("Failed to write chunk for server.jar file")?;
+    file.flush()
+        .await
+        .context("Failed to write server.jar file")?;
+    println!("File downloaded to {:?}", &server_jar_path);
+
+    let build = match server_type {
+        ServerType::Paper | ServerType::Folia | ServerType::Velocity => paper_build(&file_url),
+        ServerType::Purpur => purpur_build(&file_url),
+        _ => None
+    };
+    if let (ServerType::Purpur, Some(build)) = (server_type, build) {
+        if let Err(err) = check_purpur_md5(&server_jar_path, &version, build).await {
+            let _ = fs::remove_file(&server_jar_path).await;
+            return Err(err);
         }
+    }
+
+    let (jar_path, source, record_type, record_version) = (server_jar_path.clone(), file_url.clone(), server_type.clone(), version.clone());
+    tokio::task::spawn_blocking(move || record_install(&jar_path, &source, &record_type, &record_version, build))
+        .await
+        .context("Failed to record the install")??;
+
+    let jar_path = server_jar_path.clone();
+    match tokio::task::spawn_blocking(move || store_metadata(&jar_path)).await {
+        Ok(Ok(Some(metadata))) => println!("Data version {}, protocol version {}", metadata.world_version, metadata.protocol_version),
+        Ok(Ok(None)) => {}
+        Ok(Err(err)) => eprintln!("Failed to read the version metadata: {:#}", err),
+        Err(err) => eprintln!("Failed to read the version metadata: {}", err)
+    }
+    Ok(())
+}
+
+
+/// Installs a version of a mod loader by running its installer.
+///
+/// # Arguments
+/// - `installer_url`: Where to download the installer from
+/// - `version`: The resolved version of the loader
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+async fn install_loader(installer_url: &str, version: &str, server_type: &ServerType, path: &Path) -> Result<()> {
+    let to_args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+    let (game_version, installer_args, jvm_args, jar_prefixes) = match server_type {
+        ServerType::Forge => (forge_game_version(version).to_string(), to_args(FORGE_INSTALLER_ARGS), Vec::new(), FORGE_SERVER_JARS),
+        ServerType::NeoForge => (neoforge_game_version(version), to_args(NEOFORGE_INSTALLER_ARGS), Vec::new(), NEOFORGE_SERVER_JARS),
+        ServerType::Quilt => (split_quilt_version(version)?.0.to_string(), quilt_installer_args(version)?, to_args(QUILT_JVM_ARGS), QUILT_SERVER_JARS),
+        _ => return Err(anyhow!("{} is not installed by an installer", server_type))
+    };
+    install_server(installer_url, version, &game_version, server_type, &installer_args, &jvm_args, jar_prefixes, path)
+        .await?;
+    Ok(())
+}
 
-        println!("File downloaded to {:?}", &server_jar_path);
+/// Checks that a version can be used as the name of a version directory: it must not be empty,
+/// contain path separators, be `.` or `..`, or be one of the names `latest` and `recent`.
+///
+/// # Arguments
+/// - `version`: The version to check
+pub fn check_version_name(version: &str) -> Result<()> {
+    if version.is_empty() || version.contains(['/', '\\']) || version == "." || version == ".." || version == "latest" || version == "recent" {
+        return Err(anyhow!("Invalid version name {:?}", version));
     }
     Ok(())
 }
 
+/// Installs a server jar from a URL or a local file instead of an upstream API, for example a
+/// patched build. The jar is stored and recorded like a downloaded one.
+///
+/// # Arguments
+/// - `source`: An `http(s)` URL or the path of a local jar
+/// - `version`: The name to install the jar as
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the path of the installed `server.jar` if successful
+pub async fn install_from(source: &str, version: &str, server_type: &ServerType, path: &Path) -> Result<PathBuf> {
+    check_version_name(version)?;
+    let server_jar_path = get_server_jar_path(version, server_type, path);
+    if server_jar_path.exists() {
+        return Err(anyhow!("{} {} is already installed, uninstall it first", server_type, version));
+    }
+
+    if source.starts_with("http://") || source.starts_with("https://") {
+        download_server_jar(source.to_string(), version, server_type, path)
+            .await?;
+        return Ok(server_jar_path);
+    }
+
+    let source_path = fs::canonicalize(source)
+        .await
+        .context(format!("Failed to find {:?}", source))?;
+    let version_dir = path.join(server_type.to_string()).join("versions").join(version);
+    fs::create_dir_all(&version_dir)
+        .await
+        .context(format!("Failed to create directory for the version, path: {:?}", &version_dir))?;
+    fs::copy(&source_path, &server_jar_path)
+        .await
+        .context(format!("Failed to copy {:?} to {:?}", source_path, server_jar_path))?;
+
+    let (jar_path, record_source, record_type, record_version) = (server_jar_path.clone(), format!("file://{}", source_path.display()), server_type.clone(), version.to_string());
+    tokio::task::spawn_blocking(move || -> Result<()> {
+        record_install(&jar_path, &record_source, &record_type, &record_version, None)?;
+        // Custom jars need not be Minecraft servers built by Mojang, so missing metadata is fine.
+        if let Err(err) = store_metadata(&jar_path) {
+            eprintln!("Failed to read the version metadata: {:#}", err);
+        }
+        Ok(())
+    })
+    .await
+    .context("Failed to record the install")??;
+
+    println!("Installed {:?} as {} {}", source_path, server_type, version);
+    Ok(server_jar_path)
+}
 
 /// Deletes the server JAR for a specified Minecraft server version
 ///
@@ -244,8 +540,7 @@ pub async fn download_server_jar(file_url: String, version_to_download: &str, se
 /// - `version`: A reference to the version of the minecraft server to delete
 /// - `server_type`: The type of server
 /// - `path`: The root directory of server installations
-
-pub async fn delete_server_jar(version: &str, server_type: &ServerType, path: &PathBuf) -> Result<()> {
+pub async fn delete_server_jar(version: &str, server_type: &ServerType, path: &Path) -> Result<()> {
     let mvm_dir = path;
     let server_type_dir = mvm_dir.join(server_type.to_string());
     let version_dir = server_type_dir.join("versions").join(version);
@@ -274,20 +569,14 @@ pub async fn delete_server_jar(version: &str, server_type: &ServerType, path: &P
 /// # Notes
 /// - If the server jar for the specified version does not exist, it is downloaded automatically.
 /// - Updates the `config.toml` file to the new current version.
-pub async fn use_version(version: &str, server_type: &ServerType, path: &PathBuf) -> Result<()> {
+pub async fn use_version(version: &str, server_type: &ServerType, path: &Path) -> Result<()> {
     let mvm_dir = path;
-    let server_type_dir = mvm_dir.join(server_type.to_string());
-    let version_dir = server_type_dir.join("versions").join(version);
-    let server_jar_path = version_dir.join("server.jar");
-
-    if !server_jar_path.exists() {
-        let download_info = get_version_download(version, server_type)
-           .await?;
-        println!("Found version, downloading...");
-        download_server_jar(download_info, version, server_type, &mvm_dir)
-            .await
-            .context("Failed to download server jar")?;
-    }
+    // Resolved once, so that the jar is installed under the version its download is for. Loaders
+    // are installed under their full version, which a game version only resolves to.
+    let version = &resolve_version(version, server_type, mvm_dir)
+        .await?;
+    ensure_installed(version, server_type, mvm_dir)
+        .await?;
 
     let config_path = mvm_dir.join("config.toml");
 
@@ -298,16 +587,10 @@ pub async fn use_version(version: &str, server_type: &ServerType, path: &PathBuf
         toml::from_str::<VersionConfig>(&toml_content)
             .context("Failed to deserialize version config")?
     } else {
-        VersionConfig {
-            vanilla: "".to_string(),
-            paper: "".to_string()
-        }
+        VersionConfig::default()
     };
 
-    match server_type {
-        ServerType::Vanilla => versions.vanilla = version.to_string(),
-        ServerType::Paper => versions.paper = version.to_string()
-    }
+    versions.set(server_type, version);
 
     let toml_string = toml::to_string_pretty(&versions)
         .context("Failed to serialize version config")?;
@@ -320,5 +603,96 @@ pub async fn use_version(version: &str, server_type: &ServerType, path: &PathBuf
     Ok(())
 }
 
+/// Returns the path of the `server.jar` for a version of the given server type.
+/// The file is not required to exist.
+///
+/// Versions installed by a mod loader's installer have no single jar, for them this is the
+/// installer kept in the version directory, which their install is recorded and verified by.
+///
+/// # Arguments
+/// - `version`: A reference to the version of the minecraft server
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+pub fn get_server_jar_path(version: &str, server_type: &ServerType, path: &Path) -> PathBuf {
+    let version_dir = path.join(server_type.to_string())
+        .join("versions")
+        .join(version);
+    if version_dir.join(LAUNCH_FILE).exists() {
+        version_dir.join(INSTALLER_FILE)
+    } else {
+        version_dir.join("server.jar")
+    }
+}
 
+/// Resolves "latest" and "recent" into the concrete version they refer to.
+/// Any other version string is returned unchanged.
+///
+/// # Arguments
+/// - `version`: A reference to the version string to resolve
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the resolved version if successful
+pub async fn resolve_version(version: &str, server_type: &ServerType, path: &Path) -> Result<String> {
+    match version {
+        "latest" => match server_type {
+            ServerType::Vanilla => Ok(get_latest_vanilla_version().await?.release),
+            ServerType::Paper | ServerType::Folia | ServerType::Velocity => get_latest_paper_version(&server_type.to_string()).await,
+            ServerType::Purpur => get_latest_purpur_version().await,
+            ServerType::Forge | ServerType::NeoForge | ServerType::Quilt => resolve_loader(version, server_type).await,
+            ServerType::Custom => Err(anyhow!("Custom versions have no latest version, name one")),
+            ServerType::Defined(name) => read_definition(name, path)?.fetch_latest().await,
+        },
+        "recent" => {
+            let config_path = path.join("config.toml");
+            let toml_content = fs::read_to_string(&config_path)
+                .await
+                .context(format!("No version has been set! path: {:?}", config_path))?;
+            let version_config = toml::from_str::<VersionConfig>(&toml_content)
+                .context("Failed to deserialize version config")?;
+            let recent = version_config.get(server_type);
+            if recent.is_empty() {
+                return Err(anyhow!("No {} version has been set!", server_type));
+            }
+            Ok(recent)
+        }
+        // A game version stands for the newest loader built for it, unless installed under that name.
+        _ if server_type.is_loader() && !path.join(server_type.to_string()).join("versions").join(version).exists() => resolve_loader(version, server_type).await,
+        _ => Ok(version.to_string())
+    }
+}
+
+/// Resolves a version of a mod loader from its upstream listing.
+async fn resolve_loader(version: &str, server_type: &ServerType) -> Result<String> {
+    match server_type {
+        ServerType::Forge => get_forge_version(version).await,
+        ServerType::NeoForge => get_neoforge_version(version).await,
+        ServerType::Quilt => get_quilt_version(version).await,
+        _ => Ok(version.to_string())
+    }
+}
 
+/// Downloads the server jar for a version unless it is already installed.
+///
+/// # Arguments
+/// - `version`: A reference to the version of the minecraft server
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` containing the path to the installed `server.jar` if successful
+pub async fn ensure_installed(version: &str, server_type: &ServerType, path: &Path) -> Result<PathBuf> {
+    let server_jar_path = get_server_jar_path(version, server_type, path);
+
+    if !server_jar_path.exists() {
+        let download_info = get_download_url(version, server_type, path)
+           .await?;
+        println!("Found version, downloading...");
+        download_server_jar(download_info, version, server_type, path)
+            .await
+            .context("Failed to download server jar")?;
+    }
+
+    Ok(server_jar_path)
+}
@@ -0,0 +1,162 @@
+//! Inspects the worlds of instances.
+//!
+//! Every world records in its `level.dat` which game version saved it last. A server that is
+//! older than that does not understand what the newer version wrote and corrupts the world when
+//! it saves it, so mvm refuses to start an instance on such a version unless it is forced to.
+
+use crate::backup;
+use crate::metadata::version_metadata;
+use crate::nbt;
+use crate::server::loader::read_launch_config;
+use crate::server::server_types::ServerType;
+use crate::version_manager::get_server_jar_path;
+use anyhow::{anyhow, Context, Result};
+use std::path::Path;
+
+/// What a world's `level.dat` records about the game that saved it last.
+///
+/// # Fields
+/// - `version_name`: The name of the game version, e.g. `1.21.4`, from `Data.Version.Name`
+/// - `data_version`: The data version of the game, from `Data.DataVersion`
+/// - `last_brand`: The server brand that saved the world last, e.g. `vanilla` or `Paper`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LevelInfo {
+    pub version_name: Option<String>,
+    pub data_version: Option<i32>,
+    pub last_brand: Option<String>,
+}
+
+impl LevelInfo {
+    /// Describes the version for messages, e.g. `1.21.4 (data version 4189, Paper)`.
+    pub fn describe(&self) -> String {
+        let details = self.data_version.map(|data_version| format!("data version {}", data_version))
+            .into_iter()
+            .chain(self.last_brand.clone())
+            .collect::<Vec<_>>();
+        let name = self.version_name.clone().unwrap_or_else(|| "an unknown version".to_string());
+        if details.is_empty() { name } else { format!("{} ({})", name, details.join(", ")) }
+    }
+}
+
+/// Reads what a world's `level.dat` records about the game version that saved it last.
+///
+/// # Arguments
+/// - `world_dir`: The directory of the world
+///
+/// # Returns
+/// A `Result` containing the level information if the world has a readable `level.dat`
+pub fn read_level_info(world_dir: &Path) -> Result<LevelInfo> {
+    let level = nbt::read_gzip_file(&world_dir.join("level.dat"))?;
+    let data = level.get("Data")
+        .ok_or_else(|| anyhow!("{:?} has no Data compound", world_dir.join("level.dat")))?;
+
+    let data_version = data.get("DataVersion")
+        .or_else(|| data.get_path(&["Version", "Id"]))
+        .and_then(|tag| tag.as_i64())
+        .and_then(|version| i32::try_from(version).ok());
+    let last_brand = data.get("ServerBrands")
+        .and_then(|tag| tag.as_list())
+        .and_then(|brands| brands.last())
+        .and_then(|brand| brand.as_str())
+        .map(str::to_string);
+
+    Ok(LevelInfo {
+        version_name: data.get_path(&["Version", "Name"]).and_then(|tag| tag.as_str()).map(str::to_string),
+        data_version,
+        last_brand,
+    })
+}
+
+/// Splits a release version name such as `1.20.4` into its numbers.
+/// Returns `None` for snapshots, pre-releases and other names that cannot be ordered this way.
+fn release_numbers(name: &str) -> Option<Vec<u32>> {
+    name.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// Returns whether a server jar is older than the version that saved a world last.
+///
+/// Compares data versions, or release names for jars that do not record their data version.
+///
+/// # Arguments
+/// - `level`: What the world records about the version that saved it
+/// - `game_version`: The game version the jar runs
+/// - `jar_data_version`: The data version of the jar, if it records it
+///
+/// # Returns
+/// Whether the jar is older, or `None` if the order cannot be determined
+pub fn is_downgrade(level: &LevelInfo, game_version: &str, jar_data_version: Option<i32>) -> Option<bool> {
+    match (level.data_version, jar_data_version) {
+        (Some(world_data_version), Some(jar_data_version)) => Some(jar_data_version < world_data_version),
+        _ => match (level.version_name.as_deref().and_then(release_numbers), release_numbers(game_version)) {
+            (Some(world_release), Some(release)) => Some(release < world_release),
+            _ => None
+        }
+    }
+}
+
+/// Checks that an installed version is not older than any world of an instance.
+///
+/// Versions installed by a mod loader are compared by the game version in their `launch.toml`,
+/// other versions by the game version their jar records, falling back to the version name.
+///
+/// # Arguments
+/// - `instance_dir`: The directory of the instance
+/// - `version`: The installed version the instance would run
+/// - `server_type`: The type of server
+/// - `path`: The root directory of server installations
+///
+/// # Returns
+/// A `Result` that is an error naming the world if starting the version would downgrade it, or if
+/// the versions cannot be compared
+pub async fn check_downgrade(instance_dir: &Path, version: &str, server_type: &ServerType, path: &Path) -> Result<()> {
+    // Proxy versions are not game versions and proxies do not load worlds.
+    if server_type.is_proxy() {
+        return Ok(());
+    }
+    let worlds = backup::world_dirs(instance_dir).await;
+    let instance_dir = instance_dir.to_path_buf();
+    let version = version.to_string();
+    let server_type = server_type.clone();
+    let path = path.to_path_buf();
+
+    tokio::task::spawn_blocking(move || {
+        let levels = worlds.into_iter()
+            .filter(|world| instance_dir.join(world).join("level.dat").exists())
+            .map(|world| read_level_info(&instance_dir.join(&world)).map(|level| (world, level)))
+            .collect::<Result<Vec<_>>>()?;
+        if levels.is_empty() {
+            return Ok(());
+        }
+
+        let metadata = version_metadata(&version, &server_type, &path)?;
+        let jar_path = get_server_jar_path(&version, &server_type, &path);
+        let game_version = match read_launch_config(jar_path.parent().unwrap_or(&path))? {
+            Some(launch) => launch.game_version,
+            None => metadata.as_ref().map_or_else(|| version.clone(), |metadata| metadata.name.clone())
+        };
+        let jar_data_version = metadata.map(|metadata| metadata.world_version);
+
+        let details = Some(game_version.clone()).filter(|game_version| *game_version != version)
+            .into_iter()
+            .chain(jar_data_version.map(|data_version| format!("data version {}", data_version)))
+            .collect::<Vec<_>>();
+        let target = if details.is_empty() { version.clone() } else { format!("{} ({})", version, details.join(", ")) };
+        for (world, level) in levels {
+            match is_downgrade(&level, &game_version, jar_data_version) {
+                Some(false) => {}
+                Some(true) => return Err(anyhow!(
+                    "World {:?} was last saved by {}, which is newer than {}. Running an older server corrupts the world, use --force to start it anyway",
+                    world, level.describe(), target
+                )),
+                None => return Err(anyhow!(
+                    "Cannot tell whether {} is older than {}, which saved world {:?} last. Running an older server corrupts the world, use --force to start it anyway",
+                    target, level.describe(), world
+                ))
+            }
+        }
+
+        Ok(())
+    })
+    .await
+    .context("Failed to check the worlds")?
+}
@@ -0,0 +1,57 @@
+#![cfg(unix)]
+
+use mvm::attach::*;
+use mvm::logs::RotatingLog;
+use anyhow::Result;
+use std::io::Write;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_lines() -> Result<()> {
+        let log_dir = tempfile::tempdir()?;
+        let log_path = log_dir.path().join("console.log");
+        std::fs::write(&log_path, "one\ntwo\nthree\n")?;
+
+        assert_eq!(last_lines(&log_path, 2), vec!["two", "three"]);
+        assert_eq!(last_lines(&log_path, 10), vec!["one", "two", "three"]);
+        assert!(last_lines(&log_dir.path().join("missing.log"), 10).is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_follow_log() -> Result<()> {
+        let log_dir = tempfile::tempdir()?;
+        let log_path = log_dir.path().join("console.log");
+        std::fs::write(&log_path, "before attaching\n")?;
+
+        let mut follower = LogFollower::open(&log_path)?;
+        assert!(follower.read_lines()?.is_empty(), "Earlier output should be skipped");
+
+        let mut file = std::fs::OpenOptions::new().append(true).open(&log_path)?;
+        write!(file, "first\nsecond\nthi")?;
+        assert_eq!(follower.read_lines()?, vec!["first", "second"]);
+
+        writeln!(file, "rd")?;
+        assert_eq!(follower.read_lines()?, vec!["third"]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_follow_rotated_log() -> Result<()> {
+        let log_dir = tempfile::tempdir()?;
+        let log_path = log_dir.path().join("console.log");
+
+        let mut log = RotatingLog::open(&log_path, 20, 2)?;
+        let mut follower = LogFollower::open(&log_path)?;
+        log.write_line("first line")?;
+        log.write_line("second line")?;
+        assert!(log.rotated_path(1).exists(), "Expected the log to be rotated");
+
+        assert_eq!(follower.read_lines()?, vec!["first line", "second line"]);
+        Ok(())
+    }
+}
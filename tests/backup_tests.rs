@@ -0,0 +1,211 @@
+mod common;
+
+use common::write_level_dat;
+use mvm::backup::*;
+use mvm::backup::chunks::ChunkStore;
+use mvm::backup::storage::LocalStorage;
+use mvm::nbt;
+use anyhow::Result;
+use chrono::{Local, TimeZone};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn create_instance(mvm_dir: &Path) -> Result<PathBuf> {
+        let instance_dir = mvm_dir.join("instances/survival");
+        fs::create_dir_all(instance_dir.join("world/region")).await?;
+        fs::create_dir_all(instance_dir.join("world_nether")).await?;
+        fs::write(instance_dir.join("world/region/r.0.0.mca"), "region data").await?;
+        fs::write(instance_dir.join("server.properties"), "level-name=world\n").await?;
+        fs::write(instance_dir.join("instance.toml"), "server_type = \"paper\"\nversion = \"1.21.1\"\n").await?;
+        write_level_dat(&instance_dir.join("world"), "1.21.1", 3955, &[])?;
+        Ok(instance_dir)
+    }
+
+    fn backup_at(id: &str, day: u32, hour: u32) -> Backup {
+        Backup {
+            key: format!("snapshots/survival/{}.toml", id),
+            manifest: BackupManifest {
+                id: id.to_string(),
+                instance: "survival".to_string(),
+                created: Local.with_ymd_and_hms(2024, 6, day, hour, 0, 0).unwrap(),
+                reason: "scheduled".to_string(),
+                server_type: None,
+                version: None,
+                data_version: None,
+                worlds: vec!["world".to_string()],
+                size: 0,
+                added: 0,
+                index: String::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_read_nbt() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_level_dat(dir.path(), "1.21.1", 3955, &[])?;
+
+        let level = nbt::read_gzip_file(&dir.path().join("level.dat"))?;
+
+        assert_eq!(level.get_path(&["Data", "DataVersion"]).and_then(|tag| tag.as_i64()), Some(3955));
+        assert_eq!(world_data_version(dir.path()), Some(3955));
+        assert!(nbt::read(&mut [10u8, 0].as_slice()).is_err(), "Expected truncated data to be rejected");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backup_and_restore() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let instance_dir = create_instance(mvm_dir.path()).await?;
+
+        let backup = create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+
+        assert!(mvm_dir.path().join("backups").join(&backup.key).is_file());
+        assert_eq!(backup.key, format!("snapshots/survival/{}.toml", backup.manifest.id));
+        assert_eq!(backup.manifest.worlds, vec!["world", "world_nether"]);
+        assert_eq!(backup.manifest.data_version, Some(3955));
+        assert_eq!(backup.manifest.version.as_deref(), Some("1.21.1"));
+        let storage = LocalStorage::new(&mvm_dir.path().join("backups"));
+        assert_eq!(read_manifest(&storage, &backup.key)?, backup.manifest);
+
+        fs::write(instance_dir.join("world/region/r.0.0.mca"), "griefed").await?;
+        fs::remove_dir_all(instance_dir.join("world_nether")).await?;
+
+        let restored = restore_backup(&instance_dir, mvm_dir.path(), "latest").await?;
+
+        assert_eq!(restored.manifest.id, backup.manifest.id);
+        assert_eq!(fs::read_to_string(instance_dir.join("world/region/r.0.0.mca")).await?, "region data");
+        assert!(instance_dir.join("world_nether").is_dir());
+        assert!(!instance_dir.join(".mvm-restore").exists());
+
+        let backups = list_backups(&instance_dir, mvm_dir.path()).await?;
+        assert_eq!(backups.len(), 2, "Expected a pre-restore backup next to the original one");
+        assert_eq!(backups[1].manifest.reason, "pre-restore");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backup_without_world() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let instance_dir = mvm_dir.path().join("instances/empty");
+        fs::create_dir_all(&instance_dir).await?;
+
+        assert!(create_backup(&instance_dir, mvm_dir.path(), "manual").await.is_err());
+        assert!(find_backup(&instance_dir, mvm_dir.path(), "latest").await.is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_backups_to_prune() {
+        // Four backups on the 3rd, one on the 2nd and one on the 1st of June 2024.
+        let backups = vec![
+            backup_at("a", 1, 12),
+            backup_at("b", 2, 12),
+            backup_at("c", 3, 6),
+            backup_at("d", 3, 12),
+            backup_at("e", 3, 18),
+            backup_at("f", 3, 20),
+        ];
+
+        let ids = |policy: &BackupPolicy| -> Vec<String> {
+            backups_to_prune(&backups, policy).into_iter().map(|backup| backup.manifest.id).collect()
+        };
+
+        assert_eq!(ids(&BackupPolicy { keep_last: 2, keep_daily: 0, keep_weekly: 0 }), vec!["d", "c", "b", "a"]);
+        assert_eq!(ids(&BackupPolicy { keep_last: 1, keep_daily: 3, keep_weekly: 0 }), vec!["e", "d", "c"]);
+        // The 1st of June 2024 is a Saturday, so it is in an earlier ISO week than the 3rd.
+        assert_eq!(ids(&BackupPolicy { keep_last: 0, keep_daily: 0, keep_weekly: 2 }), vec!["e", "d", "c", "a"]);
+        assert!(ids(&BackupPolicy::default()).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_prune_backups_dry_run() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let instance_dir = create_instance(mvm_dir.path()).await?;
+        create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+        create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+        let policy = BackupPolicy { keep_last: 1, keep_daily: 0, keep_weekly: 0 };
+
+        let pruned = prune_backups(&instance_dir, mvm_dir.path(), &policy, true).await?;
+        assert_eq!(pruned.len(), 1);
+        assert_eq!(list_backups(&instance_dir, mvm_dir.path()).await?.len(), 2, "A dry run must not delete backups");
+
+        prune_backups(&instance_dir, mvm_dir.path(), &policy, false).await?;
+        assert_eq!(list_backups(&instance_dir, mvm_dir.path()).await?.len(), 1);
+
+        let keep_nothing = BackupPolicy { keep_last: 0, keep_daily: 0, keep_weekly: 0 };
+        assert!(prune_backups(&instance_dir, mvm_dir.path(), &keep_nothing, false).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_backups_are_deduplicated() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let instance_dir = create_instance(mvm_dir.path()).await?;
+        // Incompressible data, so that the stored size reflects how much data was added.
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        let mut region: Vec<u8> = (0..4_000_000).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 32) as u8
+        }).collect();
+        fs::write(instance_dir.join("world/region/r.1.0.mca"), &region).await?;
+
+        let first = create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+        assert!(first.manifest.added > 3_000_000);
+
+        let unchanged = create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+        assert!(unchanged.manifest.added < 10_000, "Expected only the index to be stored again, added {}", unchanged.manifest.added);
+
+        region[2_000_000..2_000_100].fill(0);
+        fs::write(instance_dir.join("world/region/r.1.0.mca"), &region).await?;
+        let changed = create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+        assert!(changed.manifest.added < 1_500_000, "Expected only the changed chunks to be stored, added {}", changed.manifest.added);
+        assert_eq!(changed.manifest.size, first.manifest.size);
+
+        fs::write(instance_dir.join("world/region/r.1.0.mca"), "lost").await?;
+        restore_file(&instance_dir, mvm_dir.path(), &first.manifest.id, "world/region/r.1.0.mca").await?;
+        let restored = fs::read(instance_dir.join("world/region/r.1.0.mca")).await?;
+        assert_eq!(restored.len(), region.len());
+        assert_ne!(restored, region, "Expected the content of the first backup");
+        assert!(restore_file(&instance_dir, mvm_dir.path(), "latest", "world/missing.mca").await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_collect_garbage() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let instance_dir = create_instance(mvm_dir.path()).await?;
+        let first = create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+        fs::write(instance_dir.join("world/region/r.0.0.mca"), "changed region data").await?;
+        let second = create_backup(&instance_dir, mvm_dir.path(), "manual").await?;
+
+        let report = verify_backups(&[first.clone(), second.clone()], mvm_dir.path()).await?;
+        assert!(report.problems.is_empty(), "Unexpected problems: {:?}", report.problems);
+
+        let garbage = collect_garbage(mvm_dir.path(), false).await?;
+        assert_eq!(garbage.chunks, 0, "Chunks of existing backups must be kept");
+
+        fs::remove_file(mvm_dir.path().join("backups").join(&first.key)).await?;
+        let garbage = collect_garbage(mvm_dir.path(), false).await?;
+        assert_eq!(garbage.chunks, 2, "Expected the old region and the old index to be deleted");
+        assert!(verify_backups(std::slice::from_ref(&second), mvm_dir.path()).await?.problems.is_empty());
+
+        // Corrupt a chunk of the remaining backup.
+        let store = ChunkStore::open(mvm_dir.path())?;
+        let (id, _) = store.list()?.into_iter().find(|(id, _)| *id != second.manifest.index).unwrap();
+        let chunk_path = mvm_dir.path().join("backups/chunks").join(&id[..2]).join(&id);
+        fs::write(&chunk_path, zstd::encode_all(&b"tampered"[..], 3)?).await?;
+
+        let report = verify_backups(&[second], mvm_dir.path()).await?;
+        assert_eq!(report.problems.len(), 1);
+        assert!(report.problems[0].contains("corrupt"), "Unexpected problem: {}", report.problems[0]);
+        Ok(())
+    }
+}
@@ -0,0 +1,84 @@
+//! Fixtures shared by the integration tests.
+// Every test crate compiles this module, but each uses only some of the fixtures.
+#![allow(dead_code)]
+
+use mvm::server::server_types::ServerType;
+use anyhow::Result;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use zip::write::SimpleFileOptions;
+use zip::ZipWriter;
+
+/// Installs a placeholder `server.jar` for a version, so that instances can be created without
+/// downloading a server.
+pub async fn install_dummy_jar(mvm_dir: &Path, server_type: &ServerType, version: &str) -> Result<()> {
+    let version_dir = mvm_dir.join(server_type.to_string()).join("versions").join(version);
+    fs::create_dir_all(&version_dir).await?;
+    fs::write(version_dir.join("server.jar"), "dummy content").await?;
+    Ok(())
+}
+
+/// Installs a `server.jar` with the given content for a version.
+///
+/// # Returns
+/// The path of the installed jar
+pub fn install_jar(mvm_dir: &Path, server_type: &ServerType, version: &str, content: &[u8]) -> Result<PathBuf> {
+    let version_dir = mvm_dir.join(server_type.to_string()).join("versions").join(version);
+    std::fs::create_dir_all(&version_dir)?;
+    std::fs::write(version_dir.join("server.jar"), content)?;
+    Ok(version_dir.join("server.jar"))
+}
+
+/// Builds a jar containing the given files.
+pub fn jar(files: &[(&str, &[u8])]) -> Result<Vec<u8>> {
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, content) in files {
+        writer.start_file(*name, SimpleFileOptions::default())?;
+        writer.write_all(content)?;
+    }
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Appends the header of a named NBT tag.
+fn named(document: &mut Vec<u8>, tag_type: u8, name: &str) {
+    document.push(tag_type);
+    document.extend_from_slice(&(name.len() as u16).to_be_bytes());
+    document.extend_from_slice(name.as_bytes());
+}
+
+/// Appends the payload of an NBT string.
+fn string(document: &mut Vec<u8>, value: &str) {
+    document.extend_from_slice(&(value.len() as u16).to_be_bytes());
+    document.extend_from_slice(value.as_bytes());
+}
+
+/// Writes a gzip compressed `level.dat` like the ones the game writes.
+pub fn write_level_dat(world_dir: &Path, name: &str, data_version: i32, brands: &[&str]) -> Result<()> {
+    let mut document = Vec::new();
+    named(&mut document, 10, "");
+    named(&mut document, 10, "Data");
+    named(&mut document, 3, "DataVersion");
+    document.extend_from_slice(&data_version.to_be_bytes());
+    named(&mut document, 10, "Version");
+    named(&mut document, 8, "Name");
+    string(&mut document, name);
+    named(&mut document, 3, "Id");
+    document.extend_from_slice(&data_version.to_be_bytes());
+    document.push(0);
+    named(&mut document, 9, "ServerBrands");
+    document.push(8);
+    document.extend_from_slice(&(brands.len() as i32).to_be_bytes());
+    for brand in brands {
+        string(&mut document, brand);
+    }
+    document.extend_from_slice(&[0, 0]);
+
+    std::fs::create_dir_all(world_dir)?;
+    let mut encoder = GzEncoder::new(std::fs::File::create(world_dir.join("level.dat"))?, Compression::default());
+    encoder.write_all(&document)?;
+    encoder.finish()?;
+    Ok(())
+}
@@ -0,0 +1,154 @@
+#![cfg(unix)]
+
+use mvm::logs::RotatingLog;
+use mvm::daemon::protocol::*;
+use mvm::daemon::scheduler::run_task;
+use mvm::daemon::supervisor::Supervisor;
+use mvm::instance::*;
+use mvm::schedule::*;
+use mvm::server::server_types::ServerType;
+use mvm::stop::StopOptions;
+use anyhow::Result;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Creates an instance whose "java" is a shell script, so that no real server is needed.
+    async fn create_fake_instance(mvm_dir: &Path, script: &str, restart: RestartPolicy) -> Result<PathBuf> {
+        let version_dir = mvm_dir.join("vanilla/versions/1.21");
+        fs::create_dir_all(&version_dir).await?;
+        fs::write(version_dir.join("server.jar"), "dummy content").await?;
+
+        let java_home = mvm_dir.join("java");
+        fs::create_dir_all(java_home.join("bin")).await?;
+        let java = java_home.join("bin/java");
+        fs::write(&java, format!("#!/bin/sh\n{}\n", script)).await?;
+        fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755)).await?;
+        std::env::set_var("JAVA_HOME", &java_home);
+
+        let instance_dir = mvm_dir.join("instances/fake");
+        fs::create_dir_all(&instance_dir).await?;
+        let config = InstanceConfig {
+            server_type: ServerType::Vanilla,
+            version: "1.21".to_string(),
+            java_args: Vec::new(),
+            autostart: false,
+            restart,
+            schedule: Vec::new(),
+            backup: Default::default(),
+        };
+        write_instance_config(&instance_dir, &config).await?;
+        accept_eula(&instance_dir).await?;
+
+        Ok(fs::canonicalize(instance_dir).await?)
+    }
+
+    async fn wait_for(supervisor: &Supervisor, instance_dir: &Path, condition: impl Fn(&InstanceStatus) -> bool) -> InstanceStatus {
+        for _ in 0..100 {
+            let status = supervisor.status(Some(instance_dir)).remove(0);
+            if condition(&status) {
+                return status;
+            }
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+        panic!("Instance never reached the expected status");
+    }
+
+    #[test]
+    fn test_rotating_log() -> Result<()> {
+        let log_dir = tempfile::tempdir()?;
+        let log_path = log_dir.path().join("logs/console.log");
+
+        let mut log = RotatingLog::open(&log_path, 20, 2)?;
+        for line in ["first line", "second line", "third line", "fourth line"] {
+            log.write_line(line)?;
+        }
+
+        assert_eq!(std::fs::read_to_string(&log_path)?, "fourth line\n");
+        assert_eq!(std::fs::read_to_string(log.rotated_path(1))?, "third line\n");
+        assert_eq!(std::fs::read_to_string(log.rotated_path(2))?, "second line\n");
+        assert!(!log.rotated_path(3).exists(), "Only two rotated logs should be kept");
+        Ok(())
+    }
+
+    #[test]
+    fn test_protocol_json() -> Result<()> {
+        let request = Request::Stop {
+            instance: PathBuf::from("/srv/mvm/instances/survival"),
+            countdown: 30,
+            message: "Bye".to_string(),
+            timeout_secs: 60,
+        };
+
+        let json = serde_json::to_string(&request)?;
+
+        assert!(json.starts_with("{\"type\":\"stop\""));
+        assert_eq!(serde_json::from_str::<Request>(&json)?, request);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_supervisor_restarts_and_stops() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+
+        // Crashes on the first start and keeps running on the second one.
+        let script = "if [ -f crashed ]; then echo ready; while read cmd; do [ \"$cmd\" = stop ] && exit 0; done; fi\ntouch crashed\nexit 1";
+        let restart = RestartPolicy { backoff_secs: 0, ..RestartPolicy::default() };
+        let instance_dir = create_fake_instance(mvm_dir.path(), script, restart).await?;
+
+        let supervisor = Supervisor::new(mvm_dir.path());
+        assert_eq!(supervisor.status(Some(&instance_dir)).remove(0).state, InstanceState::NotSupervised);
+        assert!(!supervisor.is_supervised(&instance_dir), "Querying the status should not make the instance supervised");
+        assert!(supervisor.status(None).is_empty());
+        assert!(supervisor.command(&instance_dir, "list").is_err());
+        assert!(!supervisor.is_supervised(&instance_dir));
+
+        supervisor.start(&instance_dir).await?;
+        assert!(supervisor.start(&instance_dir).await.is_err(), "Expected an error when starting twice");
+
+        let status = wait_for(&supervisor, &instance_dir, |status| status.state == InstanceState::Running && status.restarts == 1).await;
+        assert!(status.pid.is_some());
+
+        let options = StopOptions { countdown: 0, message: String::new(), timeout: Duration::from_secs(5) };
+        supervisor.stop(&instance_dir, &options).await?;
+
+        let status = supervisor.status(Some(&instance_dir)).remove(0);
+        assert_eq!(status.state, InstanceState::Stopped);
+        assert_eq!(status.last_exit.as_deref(), Some("exit status: 0"));
+
+        let log = fs::read_to_string(instance_dir.join("logs/mvm-console.log")).await?;
+        assert!(log.contains("ready"), "Server output should be captured in the console log");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_scheduled_backup() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        // No server is started, so the instance does not need a fake java.
+        let instance_dir = mvm_dir.path().join("instances/fake");
+        fs::create_dir_all(&instance_dir).await?;
+        fs::write(instance_dir.join("instance.toml"), "server_type = \"vanilla\"\nversion = \"1.21\"\n").await?;
+        fs::create_dir_all(instance_dir.join("world/region")).await?;
+        fs::write(instance_dir.join("world/level.dat"), "level").await?;
+        let config = read_instance_config(&instance_dir).await?;
+        let task = ScheduledTask { name: None, cron: CronSchedule::parse("@hourly")?, action: TaskAction::Backup };
+
+        let supervisor = Supervisor::new(mvm_dir.path());
+        run_task(&supervisor, &instance_dir, &config, &task, mvm_dir.path()).await;
+
+        let backups: Vec<_> = std::fs::read_dir(mvm_dir.path().join("backups/snapshots/fake"))?.collect::<Result<_, _>>()?;
+        assert_eq!(backups.len(), 1);
+        assert!(backups[0].file_name().to_string_lossy().ends_with(".toml"));
+
+        let log = fs::read_to_string(instance_dir.join("logs/mvm-console.log")).await?;
+        assert!(log.contains("Scheduled task 'backup': Created"), "The outcome should be logged, got: {}", log);
+        assert!(supervisor.status(None).is_empty(), "Running a task should not make the instance supervised");
+        Ok(())
+    }
+}
@@ -0,0 +1,137 @@
+use mvm::provenance::read_install_info;
+use mvm::server::defined::*;
+use mvm::server::server_types::ServerType;
+use mvm::version_manager::{ensure_installed, get_download_url, get_version_download, resolve_version, use_version};
+use anyhow::Result;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+use tokio::fs;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Serves fixed responses by path, answering anything else with 404.
+    fn serve_files(files: HashMap<&'static str, Vec<u8>>) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = format!("http://{}", listener.local_addr()?);
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(match stream.try_clone() {
+                    Ok(reader) => reader,
+                    Err(_) => continue
+                });
+                let mut request_line = String::new();
+                let _ = reader.read_line(&mut request_line);
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim_end().is_empty() {
+                        break;
+                    }
+                }
+
+                let path = request_line.split_whitespace().nth(1).unwrap_or_default();
+                let (status, body) = match files.get(path) {
+                    Some(body) => ("200 OK", body.clone()),
+                    None => ("404 Not Found", Vec::new())
+                };
+                let _ = write!(stream, "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, body.len());
+                let _ = stream.write_all(&body);
+            }
+        });
+        Ok(address)
+    }
+
+    fn definition(versions_pointer: &str, version_pointer: Option<&str>, latest_pointer: Option<&str>) -> ServerTypeDefinition {
+        ServerTypeDefinition {
+            versions_url: "http://127.0.0.1/versions".to_string(),
+            versions_pointer: versions_pointer.to_string(),
+            version_pointer: version_pointer.map(str::to_string),
+            latest_pointer: latest_pointer.map(str::to_string),
+            builds_url: None,
+            build_pointer: None,
+            download_url: "http://127.0.0.1/{version}.jar".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_pointer() {
+        let document = json!({"versions": ["1.20.6", "1.21.4"], "a/b": {"~c": 7}});
+
+        assert_eq!(resolve_pointer(&document, ""), Some(&document));
+        assert_eq!(resolve_pointer(&document, "/versions/0"), Some(&json!("1.20.6")));
+        assert_eq!(resolve_pointer(&document, "/versions/-"), Some(&json!("1.21.4")));
+        assert_eq!(resolve_pointer(&document, "/a~1b/~0c"), Some(&json!(7)));
+        assert_eq!(resolve_pointer(&document, "/versions/2"), None);
+        assert_eq!(resolve_pointer(&document, "versions"), None, "Expected pointers to start with '/'");
+    }
+
+    #[test]
+    fn test_parse_versions() -> Result<()> {
+        let plain = json!({"project": "leaf", "versions": ["1.20.6", "1.21.4"]});
+        let objects = json!([{"id": "1.21.1", "stable": true}, {"id": "1.21.3", "stable": false}]);
+
+        assert_eq!(definition("/versions", None, None).parse_versions(&plain)?, vec!["1.20.6", "1.21.4"]);
+        assert_eq!(definition("/versions", None, None).parse_latest(&plain)?, "1.21.4");
+        assert_eq!(definition("", Some("/id"), None).parse_versions(&objects)?, vec!["1.21.1", "1.21.3"]);
+        assert_eq!(definition("", Some("/id"), Some("/0/id")).parse_latest(&objects)?, "1.21.1");
+        assert!(definition("/project", None, None).parse_versions(&plain).is_err(), "Expected a pointer to a string to be rejected");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_definitions() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        assert!(read_definitions(mvm_dir.path())?.is_empty());
+
+        std::fs::write(mvm_dir.path().join("config.toml"), "vanilla = \"1.21.4\"\n\n[server_types.leaf]\nversions_url = \"http://127.0.0.1/versions\"\ndownload_url = \"http://127.0.0.1/{version}.jar\"\n")?;
+        let leaf = read_definition("leaf", mvm_dir.path())?;
+        assert_eq!(leaf.versions_pointer, "");
+        assert_eq!(leaf.latest_pointer, None);
+        let err = read_definition("purpurr", mvm_dir.path()).expect_err("Expected an undeclared type to be an error");
+        assert!(err.to_string().contains("[server_types.purpurr]"), "Unexpected error: {}", err);
+
+        std::fs::write(mvm_dir.path().join("config.toml"), "[server_types.paper]\nversions_url = \"http://127.0.0.1/versions\"\ndownload_url = \"http://127.0.0.1/{version}.jar\"\n")?;
+        assert!(read_definitions(mvm_dir.path()).is_err(), "Expected built in names to be reserved");
+        Ok(())
+    }
+
+    async fn write_config(mvm_dir: &Path, address: &str) -> Result<()> {
+        let config = format!("[server_types.leaf]\nversions_url = \"{0}/projects/leaf\"\nversions_pointer = \"/versions\"\nbuilds_url = \"{0}/projects/leaf/{{version}}\"\nbuild_pointer = \"/builds/-/id\"\ndownload_url = \"{0}/jars/leaf-{{version}}-{{build}}.jar\"\n", address);
+        fs::write(mvm_dir.join("config.toml"), config).await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_install_declared_type() -> Result<()> {
+        let address = serve_files(HashMap::from([
+            ("/projects/leaf", br#"{"versions": ["1.21.3", "1.21.4"]}"#.to_vec()),
+            ("/projects/leaf/1.21.4", br#"{"builds": [{"id": 6}, {"id": 7}]}"#.to_vec()),
+            ("/jars/leaf-1.21.4-7.jar", b"leaf server".to_vec()),
+        ]))?;
+        let mvm_dir = tempfile::tempdir()?;
+        write_config(mvm_dir.path(), &address).await?;
+        let leaf = ServerType::from_string("leaf".to_string())?;
+
+        assert_eq!(resolve_version("latest", &leaf, mvm_dir.path()).await?, "1.21.4");
+        assert_eq!(get_download_url("latest", &leaf, mvm_dir.path()).await?, format!("{}/jars/leaf-1.21.4-7.jar", address));
+        assert!(get_download_url("1.19", &leaf, mvm_dir.path()).await.is_err(), "Expected unlisted versions to be rejected");
+        assert!(get_version_download("latest", &leaf).await.is_err(), "Expected declared types to need the directory");
+
+        let jar_path = ensure_installed("1.21.4", &leaf, mvm_dir.path()).await?;
+        assert_eq!(jar_path, mvm_dir.path().join("leaf/versions/1.21.4/server.jar"));
+        assert_eq!(fs::read(&jar_path).await?, b"leaf server");
+        let info = read_install_info("1.21.4", &leaf, mvm_dir.path())?.expect("Expected the install to be recorded");
+        assert_eq!(info.server_type, leaf);
+        assert_eq!(info.source, format!("{}/jars/leaf-1.21.4-7.jar", address));
+
+        use_version("1.21.4", &leaf, mvm_dir.path()).await?;
+        assert_eq!(resolve_version("recent", &leaf, mvm_dir.path()).await?, "1.21.4");
+        assert!(read_definition("leaf", mvm_dir.path()).is_ok(), "Expected the declaration to survive writing the current version");
+        Ok(())
+    }
+}
@@ -0,0 +1,194 @@
+mod common;
+
+use common::jar;
+use mvm::import::*;
+use mvm::instance::read_instance_config;
+use mvm::provenance::read_install_info;
+use mvm::server::server_types::ServerType;
+use anyhow::Result;
+use std::path::Path;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version_json(name: &str) -> Vec<u8> {
+        format!("{{\"id\": \"{0}\", \"name\": \"{0}\", \"world_version\": 4189, \"protocol_version\": 769}}", name).into_bytes()
+    }
+
+    fn vanilla_jar(version: &str) -> Result<Vec<u8>> {
+        jar(&[
+            ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\nMain-Class: net.minecraft.bundler.Main\r\n\r\n"),
+            ("version.json", &version_json(version)),
+        ])
+    }
+
+    fn paperclip_jar(version: &str, build: u32) -> Result<Vec<u8>> {
+        let manifest = format!("Manifest-Version: 1.0\r\nImplementation-Title: Paper\r\nImplementation-Version: {}-{}-3\r\n 3c3a2b1\r\n\r\n", version, build);
+        bundling_jar(&format!("paper-{}.jar", version), &manifest, version)
+    }
+
+    fn purpur_jar(version: &str, build: u32) -> Result<Vec<u8>> {
+        let manifest = format!("Manifest-Version: 1.0\r\nImplementation-Title: Purpur\r\nImplementation-Version: git-Purpur-{}\r\n\r\n", build);
+        bundling_jar(&format!("purpur-{}.jar", version), &manifest, version)
+    }
+
+    /// Builds a Paperclip jar bundling a server jar with the given name and manifest.
+    fn bundling_jar(jar_name: &str, manifest: &str, version: &str) -> Result<Vec<u8>> {
+        let bundled = jar(&[("META-INF/MANIFEST.MF", manifest.as_bytes()), ("version.json", &version_json(version))])?;
+        let bundled_name = format!("META-INF/versions/{}/{}", version, jar_name);
+        jar(&[
+            ("META-INF/MANIFEST.MF", b"Manifest-Version: 1.0\r\nMain-Class: io.papermc.paperclip.Main\r\n\r\n"),
+            (bundled_name.as_str(), &bundled),
+        ])
+    }
+
+    fn write(file_path: &Path, content: &[u8]) -> Result<()> {
+        std::fs::create_dir_all(file_path.parent().unwrap())?;
+        std::fs::write(file_path, content)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_paper_build() {
+        assert_eq!(parse_paper_build("1.21.4-232-3c3a2b1"), Some(232));
+        assert_eq!(parse_paper_build("git-Paper-196 (MC: 1.21)"), Some(196));
+        assert_eq!(parse_paper_build("git-Purpur-2416"), Some(2416));
+        assert_eq!(parse_paper_build("1.21.4"), None);
+    }
+
+    #[test]
+    fn test_parse_velocity_version() {
+        assert_eq!(parse_velocity_version("3.4.0-SNAPSHOT (git-e8b8ba4e-b436)"), (Some("3.4.0-SNAPSHOT".to_string()), Some(436)));
+        assert_eq!(parse_velocity_version("3.1.1"), (Some("3.1.1".to_string()), None));
+    }
+
+    #[test]
+    fn test_identify_jar() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        write(&dir.path().join("vanilla.jar"), &vanilla_jar("1.21.4")?)?;
+        write(&dir.path().join("paper.jar"), &paperclip_jar("1.21.4", 232)?)?;
+        write(&dir.path().join("purpur.jar"), &purpur_jar("1.21.4", 2416)?)?;
+        write(&dir.path().join("folia.jar"), &bundling_jar("folia-1.21.4.jar", "Implementation-Title: Folia\r\nImplementation-Version: 1.21.4-5-0f7e5b1\r\n\r\n", "1.21.4")?)?;
+        write(&dir.path().join("fabric.jar"), &jar(&[
+            ("META-INF/MANIFEST.MF", b"Main-Class: net.fabricmc.installer.ServerLauncher\r\n"),
+            ("install.properties", b"fabric-loader-version=0.16.9\ngame-version=1.21.4\n"),
+        ])?)?;
+        write(&dir.path().join("velocity.jar"), &jar(&[
+            ("META-INF/MANIFEST.MF", b"Main-Class: com.velocitypowered.proxy.Velocity\r\nImplementation-Version: 3.4.0-SNAPSHOT (git-e8b8ba4e-b436)\r\n\r\n"),
+        ])?)?;
+        write(&dir.path().join("plugin.jar"), &jar(&[("plugin.yml", b"name: Example\n")])?)?;
+
+        assert_eq!(identify_jar(&dir.path().join("vanilla.jar"))?, JarIdentity {
+            server_type: ServerType::Vanilla,
+            version: Some("1.21.4".to_string()),
+            build: None,
+        });
+        assert_eq!(identify_jar(&dir.path().join("paper.jar"))?, JarIdentity {
+            server_type: ServerType::Paper,
+            version: Some("1.21.4".to_string()),
+            build: Some(232),
+        });
+        assert_eq!(identify_jar(&dir.path().join("purpur.jar"))?, JarIdentity {
+            server_type: ServerType::Purpur,
+            version: Some("1.21.4".to_string()),
+            build: Some(2416),
+        });
+        assert_eq!(identify_jar(&dir.path().join("folia.jar"))?, JarIdentity {
+            server_type: ServerType::Folia,
+            version: Some("1.21.4".to_string()),
+            build: Some(5),
+        });
+        assert_eq!(identify_jar(&dir.path().join("velocity.jar"))?, JarIdentity {
+            server_type: ServerType::Velocity,
+            version: Some("3.4.0-SNAPSHOT".to_string()),
+            build: Some(436),
+        });
+        assert_eq!(identify_jar(&dir.path().join("fabric.jar"))?, JarIdentity {
+            server_type: ServerType::Custom,
+            version: Some("1.21.4-0.16.9".to_string()),
+            build: None,
+        });
+        assert!(identify_jar(&dir.path().join("plugin.jar")).is_err(), "Expected a plugin not to be a server jar");
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_jar() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let source_dir = tempfile::tempdir()?;
+        let paper = source_dir.path().join("paper-1.21.4-232.jar");
+        write(&paper, &paperclip_jar("1.21.4", 232)?)?;
+
+        assert_eq!(import_jar(&paper, mvm_dir.path(), None, false)?, (ServerType::Paper, "1.21.4".to_string()));
+
+        let installed = mvm_dir.path().join("paper/versions/1.21.4/server.jar");
+        assert_eq!(std::fs::read(&installed)?, std::fs::read(&paper)?);
+        let info = read_install_info("1.21.4", &ServerType::Paper, mvm_dir.path())?.expect("Expected the import to be recorded");
+        assert!(info.source.starts_with("file://") && info.source.ends_with("paper-1.21.4-232.jar"), "Unexpected source {}", info.source);
+        assert_eq!(info.build, Some(232));
+        assert!(mvm_dir.path().join("paper/versions/1.21.4/metadata.toml").exists());
+
+        // Importing the same jar again is a no-op, a different jar for the same version is refused.
+        import_jar(&paper, mvm_dir.path(), None, false)?;
+        write(&paper, &paperclip_jar("1.21.4", 233)?)?;
+        assert!(import_jar(&paper, mvm_dir.path(), None, false).is_err(), "Expected a different jar to be refused");
+        Ok(())
+    }
+
+    #[test]
+    fn test_import_old_jar_and_link() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let old = mvm_dir.path().join("minecraft_server.1.12.2.jar");
+        write(&old, &jar(&[("META-INF/MANIFEST.MF", b"Main-Class: net.minecraft.server.MinecraftServer\r\n")])?)?;
+
+        assert!(import_jar(&old, mvm_dir.path(), None, true).is_err(), "Expected a jar without a version to need --version");
+        assert!(import_jar(&old, mvm_dir.path(), Some("../../outside"), true).is_err(), "Expected a version with a path to be refused");
+        assert!(!mvm_dir.path().join("outside").exists());
+        import_jar(&old, mvm_dir.path(), Some("1.12.2"), true)?;
+
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::MetadataExt;
+            let installed = mvm_dir.path().join("vanilla/versions/1.12.2/server.jar");
+            assert_eq!(std::fs::metadata(&installed)?.ino(), std::fs::metadata(&old)?.ino(), "Expected a hard link");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_find_server_jar() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        write(&dir.path().join("paper-1.21.4-232.jar"), &paperclip_jar("1.21.4", 232)?)?;
+        write(&dir.path().join("plugins/example.jar"), &jar(&[("plugin.yml", b"name: Example\n")])?)?;
+        write(&dir.path().join("notes.jar"), b"not a jar")?;
+
+        assert_eq!(find_server_jar(dir.path())?, dir.path().join("paper-1.21.4-232.jar"));
+
+        write(&dir.path().join("server.jar"), &vanilla_jar("1.21.4")?)?;
+        assert_eq!(find_server_jar(dir.path())?, dir.path().join("server.jar"));
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_adopt_instance() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let server_dir = tempfile::tempdir()?;
+        write(&server_dir.path().join("world/level.dat"), b"")?;
+        assert!(adopt_instance(server_dir.path(), "../escape", &ServerType::Paper, "1.21.4", mvm_dir.path()).await.is_err(),
+            "Expected a name containing .. to be refused");
+
+        let instance_dir = adopt_instance(server_dir.path(), "legacy", &ServerType::Paper, "1.21.4", mvm_dir.path()).await?;
+
+        assert_eq!(instance_dir, mvm_dir.path().join("instances/legacy"));
+        assert!(instance_dir.join("world/level.dat").exists(), "Expected the instance to link to the server directory");
+        let config = read_instance_config(&instance_dir).await?;
+        assert_eq!(config.server_type, ServerType::Paper);
+        assert_eq!(config.version, "1.21.4");
+        assert!(adopt_instance(server_dir.path(), "other", &ServerType::Paper, "1.21.4", mvm_dir.path()).await.is_err(),
+            "Expected a directory to be adopted only once");
+        Ok(())
+    }
+}
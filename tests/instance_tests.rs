@@ -0,0 +1,104 @@
+use mvm::instance::*;
+use mvm::server::server_types::ServerType;
+use anyhow::Result;
+use std::path::Path;
+use tokio::fs;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn install_dummy_jar(mvm_dir: &Path, server_type: &ServerType, version: &str) -> Result<()> {
+        let version_dir = mvm_dir.join(server_type.to_string()).join("versions").join(version);
+        fs::create_dir_all(&version_dir).await?;
+        fs::write(version_dir.join("server.jar"), "dummy content").await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_init_instance() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Paper, "1.21").await?;
+
+        let instance_dir = init_instance("survival", "1.21", &ServerType::Paper, mvm_dir.path()).await?;
+
+        assert_eq!(instance_dir, mvm_dir.path().join("instances/survival"));
+        let config = read_instance_config(&instance_dir).await?;
+        assert_eq!(config.server_type, ServerType::Paper);
+        assert_eq!(config.version, "1.21");
+        assert!(!eula_accepted(&instance_dir).await, "EULA must not be accepted implicitly");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_init_instance_already_exists() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21").await?;
+
+        init_instance("survival", "1.21", &ServerType::Vanilla, mvm_dir.path()).await?;
+        let result = init_instance("survival", "1.21", &ServerType::Vanilla, mvm_dir.path()).await;
+
+        assert!(result.is_err(), "Expected an error for an existing instance");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_init_instance_invalid_name() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21").await?;
+
+        let result = init_instance("../outside", "1.21", &ServerType::Vanilla, mvm_dir.path()).await;
+
+        assert!(result.is_err(), "Expected an error for a name containing ..");
+        assert!(!mvm_dir.path().join("outside").exists());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_accept_eula() -> Result<()> {
+        let instance_dir = tempfile::tempdir()?;
+
+        accept_eula(instance_dir.path()).await?;
+
+        assert!(eula_accepted(instance_dir.path()).await);
+        let content = fs::read_to_string(instance_dir.path().join("eula.txt")).await?;
+        assert!(content.contains(EULA_URL));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_proxy_needs_no_eula() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Velocity, "3.4.0-SNAPSHOT").await?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Paper, "1.21").await?;
+
+        let proxy_dir = init_instance("proxy", "3.4.0-SNAPSHOT", &ServerType::Velocity, mvm_dir.path()).await?;
+        let backend_dir = init_instance("lobby", "1.21", &ServerType::Paper, mvm_dir.path()).await?;
+
+        assert!(check_eula(&proxy_dir, &ServerType::Velocity).await.is_ok());
+        assert!(check_eula(&backend_dir, &ServerType::Paper).await.is_err(), "Expected servers to need the EULA");
+        let proxy_command = server_command(&proxy_dir, mvm_dir.path()).await?;
+        assert!(!proxy_command.as_std().get_args().any(|arg| arg == "nogui"), "Expected Velocity to be started without nogui");
+        let backend_command = server_command(&backend_dir, mvm_dir.path()).await?;
+        assert!(backend_command.as_std().get_args().any(|arg| arg == "nogui"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_instance_dir() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let existing_dir = tempfile::tempdir()?;
+        let existing = existing_dir.path().to_str().unwrap();
+
+        assert_eq!(get_instance_dir("survival", mvm_dir.path()), mvm_dir.path().join("instances/survival"));
+        assert_eq!(get_instance_dir(existing, mvm_dir.path()), existing_dir.path());
+
+        Ok(())
+    }
+}
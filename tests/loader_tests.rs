@@ -0,0 +1,204 @@
+use mvm::provenance::read_install_info;
+use mvm::server::forge::*;
+use mvm::server::loader::*;
+use mvm::server::neoforge::*;
+use mvm::server::quilt::*;
+use mvm::server::server_types::ServerType;
+use mvm::version_manager::get_server_jar_path;
+use anyhow::Result;
+use std::io::{BufRead, BufReader, Write};
+use std::net::TcpListener;
+use std::path::Path;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const METADATA: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata>
+  <groupId>net.minecraftforge</groupId>
+  <artifactId>forge</artifactId>
+  <versioning>
+    <versions>
+      <version>1.20.1-47.3.0</version>
+      <version>1.21.4-54.0.9</version>
+      <version>1.21.4-54.0.16</version>
+      <version>1.21.3-53.0.25</version>
+    </versions>
+  </versioning>
+</metadata>"#;
+
+    /// Serves a single file at any path.
+    fn serve_file(body: Vec<u8>) -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = format!("http://{}", listener.local_addr()?);
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut reader = BufReader::new(match stream.try_clone() {
+                    Ok(reader) => reader,
+                    Err(_) => continue
+                });
+                loop {
+                    let mut line = String::new();
+                    if reader.read_line(&mut line).unwrap_or(0) == 0 || line.trim_end().is_empty() {
+                        break;
+                    }
+                }
+                let _ = write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len());
+                let _ = stream.write_all(&body);
+            }
+        });
+        Ok(address)
+    }
+
+    fn write(file_path: &Path, content: &str) -> Result<()> {
+        std::fs::create_dir_all(file_path.parent().unwrap())?;
+        std::fs::write(file_path, content)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_maven_versions() {
+        assert_eq!(parse_maven_versions(METADATA), vec!["1.20.1-47.3.0", "1.21.4-54.0.9", "1.21.4-54.0.16", "1.21.3-53.0.25"]);
+        assert!(parse_maven_versions("<metadata></metadata>").is_empty());
+    }
+
+    #[test]
+    fn test_resolve_forge_version() -> Result<()> {
+        let versions = parse_maven_versions(METADATA);
+        assert_eq!(resolve_forge_version(&versions, "latest")?, "1.21.4-54.0.16");
+        assert_eq!(resolve_forge_version(&versions, "1.21.3")?, "1.21.3-53.0.25");
+        assert_eq!(resolve_forge_version(&versions, "1.21.4-54.0.9")?, "1.21.4-54.0.9");
+        assert!(resolve_forge_version(&versions, "1.21.5").is_err(), "Expected a game version without Forge to fail");
+        assert_eq!(forge_game_version("1.21.4-54.0.16"), "1.21.4");
+        Ok(())
+    }
+
+    #[test]
+    fn test_neoforge_game_version() {
+        assert_eq!(neoforge_game_version("21.4.111-beta"), "1.21.4");
+        assert_eq!(neoforge_game_version("20.2.86"), "1.20.2");
+        assert_eq!(neoforge_game_version("21.0.167"), "1.21");
+        assert_eq!(neoforge_game_version("26.1.0.5"), "26.1");
+        assert_eq!(neoforge_game_version("26.1.1.2-beta"), "26.1.1");
+    }
+
+    #[test]
+    fn test_resolve_neoforge_version() -> Result<()> {
+        let versions = parse_maven_versions("<versions><version>21.1.77</version><version>21.1.80</version><version>21.4.110-beta</version><version>21.4.111-beta</version><version>21.4.99</version><version>21.5.2-beta</version></versions>");
+        assert_eq!(resolve_neoforge_version(&versions, "1.21.1")?, "21.1.80");
+        // Stable builds are preferred, a game version with only betas gets the newest beta.
+        assert_eq!(resolve_neoforge_version(&versions, "1.21.4")?, "21.4.99");
+        assert_eq!(resolve_neoforge_version(&versions, "latest")?, "21.5.2-beta");
+        assert_eq!(resolve_neoforge_version(&versions, "21.4.110-beta")?, "21.4.110-beta");
+        assert!(resolve_neoforge_version(&versions, "1.20.1").is_err(), "Expected a game version without NeoForge to fail");
+        Ok(())
+    }
+
+    #[test]
+    fn test_quilt_versions() -> Result<()> {
+        assert_eq!(split_quilt_version("1.21.4-0.27.1")?, ("1.21.4", "0.27.1"));
+        assert_eq!(split_quilt_version("1.21-pre1-0.26.0-beta.1")?, ("1.21-pre1", "0.26.0-beta.1"));
+        assert!(split_quilt_version("1.21.4").is_err(), "Expected a version without a loader to fail");
+
+        let game_versions: Vec<QuiltGameVersion> = serde_json::from_str(r#"[{"version": "25w02a", "stable": false}, {"version": "1.21.4", "stable": true}, {"version": "1.21.3", "stable": true}]"#)?;
+        assert_eq!(latest_quilt_game_version(&game_versions), Some("1.21.4".to_string()));
+
+        let loaders: Vec<QuiltLoaderEntry> = serde_json::from_str(r#"[
+            {"loader": {"separator": ".", "build": 2, "maven": "org.quiltmc:quilt-loader:0.28.0-beta.2", "version": "0.28.0-beta.2"}},
+            {"loader": {"separator": ".", "build": 1, "maven": "org.quiltmc:quilt-loader:0.27.1", "version": "0.27.1"}},
+            {"loader": {"separator": ".", "build": 0, "maven": "org.quiltmc:quilt-loader:0.27.0", "version": "0.27.0"}}
+        ]"#)?;
+        assert_eq!(latest_quilt_version("1.21.4", &loaders), Some("1.21.4-0.27.1".to_string()));
+        assert_eq!(latest_quilt_version("1.21.4", &[]), None);
+
+        assert_eq!(quilt_installer_args("1.21.4-0.27.1")?, vec!["install", "server", "1.21.4", "0.27.1", "--download-server", "--install-dir=."]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_launch_args() -> Result<()> {
+        let sandbox = tempfile::tempdir()?;
+        let version_dir = Path::new("/srv/mvm/forge/versions/1.21.4-54.0.16");
+        let args_file = "libraries/net/minecraftforge/forge/1.21.4-54.0.16/unix_args.txt";
+        write(&sandbox.path().join("run.sh"), "#!/usr/bin/env sh\n# Add custom JVM arguments to the user_jvm_args.txt\njava @user_jvm_args.txt @libraries/net/minecraftforge/forge/1.21.4-54.0.16/unix_args.txt \"$@\"\n")?;
+        write(&sandbox.path().join(args_file), "-DlibraryDirectory=libraries\n-p libraries/a.jar:libraries/b.jar\n--add-modules ALL-MODULE-PATH\ncpw.mods.bootstraplauncher.BootstrapLauncher\n--launchTarget forgeserver\n")?;
+        write(&sandbox.path().join("libraries/a.jar"), "")?;
+        write(&sandbox.path().join("libraries/b.jar"), "")?;
+
+        let library = |name: &str| version_dir.join(name).to_string_lossy().to_string();
+        assert_eq!(read_launch_args(sandbox.path(), version_dir, FORGE_SERVER_JARS)?, vec![
+            format!("-DlibraryDirectory={}", library("libraries")),
+            "-p".to_string(),
+            format!("{}:{}", library("libraries/a.jar"), library("libraries/b.jar")),
+            "--add-modules".to_string(),
+            "ALL-MODULE-PATH".to_string(),
+            "cpw.mods.bootstraplauncher.BootstrapLauncher".to_string(),
+            "--launchTarget".to_string(),
+            "forgeserver".to_string(),
+        ]);
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_launch_args_without_script() -> Result<()> {
+        let sandbox = tempfile::tempdir()?;
+        let version_dir = Path::new("/srv/mvm/forge/versions/1.12.2-14.23.5.2860");
+        write(&sandbox.path().join(INSTALLER_FILE), "")?;
+        write(&sandbox.path().join("minecraft_server.1.12.2.jar"), "")?;
+        write(&sandbox.path().join("forge-1.12.2-14.23.5.2860.jar"), "")?;
+
+        assert_eq!(read_launch_args(sandbox.path(), version_dir, FORGE_SERVER_JARS)?, vec![
+            "-jar".to_string(),
+            version_dir.join("forge-1.12.2-14.23.5.2860.jar").to_string_lossy().to_string(),
+        ]);
+
+        std::fs::remove_file(sandbox.path().join("forge-1.12.2-14.23.5.2860.jar"))?;
+        assert!(read_launch_args(sandbox.path(), version_dir, FORGE_SERVER_JARS).is_err(), "Expected an install without a server to fail");
+        Ok(())
+    }
+
+    /// Runs the install with a `java` that writes what the Forge installer would.
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_install_server() -> Result<()> {
+        use std::os::unix::fs::PermissionsExt;
+
+        let mvm_dir = tempfile::tempdir()?;
+        let java_home = tempfile::tempdir()?;
+        let java = java_home.path().join("bin/java");
+        write(&java, "#!/bin/sh\nif [ \"$3\" = install ]; then touch quilt-server-launch.jar server.jar; exit 0; fi\n[ \"$3\" = \"--installServer\" ] || exit 1\nmkdir -p libraries\necho '-cp libraries/forge.jar' > libraries/args.txt\ntouch libraries/forge.jar\necho 'java @user_jvm_args.txt @libraries/args.txt \"$@\"' > run.sh\necho installed\n")?;
+        std::fs::set_permissions(&java, std::fs::Permissions::from_mode(0o755))?;
+        std::env::set_var("JAVA_HOME", java_home.path());
+
+        let address = serve_file(b"installer".to_vec())?;
+        let installer_args = vec!["--installServer".to_string()];
+        let version_dir = install_server(&format!("{}/forge-installer.jar", address), "1.21.4-54.0.16", "1.21.4", &ServerType::Forge, &installer_args, &[], FORGE_SERVER_JARS, mvm_dir.path()).await?;
+
+        assert_eq!(version_dir, mvm_dir.path().join("forge/versions/1.21.4-54.0.16"));
+        assert!(!mvm_dir.path().join("forge/installing").exists(), "Expected the sandbox to be moved into place");
+        let launch = read_launch_config(&version_dir)?.expect("Expected the launch arguments to be recorded");
+        assert_eq!(launch.game_version, "1.21.4");
+        assert_eq!(launch.args, vec!["-cp".to_string(), version_dir.join("libraries/forge.jar").to_string_lossy().to_string()]);
+        assert_eq!(get_server_jar_path("1.21.4-54.0.16", &ServerType::Forge, mvm_dir.path()), version_dir.join(INSTALLER_FILE));
+        let info = read_install_info("1.21.4-54.0.16", &ServerType::Forge, mvm_dir.path())?.expect("Expected the install to be recorded");
+        assert!(info.source.ends_with("forge-installer.jar"), "Unexpected source {}", info.source);
+
+        // A failing installer leaves nothing installed.
+        let failed = install_server(&format!("{}/forge-installer.jar", address), "1.20.1-47.3.0", "1.20.1", &ServerType::Forge, &[], &[], FORGE_SERVER_JARS, mvm_dir.path()).await;
+        assert!(failed.is_err(), "Expected the installer to fail without --installServer");
+        assert!(!mvm_dir.path().join("forge/versions/1.20.1-47.3.0").exists());
+
+        // Quilt writes no script, its launcher jar is started with the downloaded server passed in.
+        let jvm_args = QUILT_JVM_ARGS.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        let version_dir = install_server(&format!("{}/quilt-installer.jar", address), "1.21.4-0.27.1", "1.21.4", &ServerType::Quilt, &quilt_installer_args("1.21.4-0.27.1")?, &jvm_args, QUILT_SERVER_JARS, mvm_dir.path()).await?;
+        let launch = read_launch_config(&version_dir)?.expect("Expected the launch arguments to be recorded");
+        assert_eq!(launch.args, vec![
+            format!("-Dloader.gameJarPath={}", version_dir.join("server.jar").display()),
+            "-jar".to_string(),
+            version_dir.join("quilt-server-launch.jar").to_string_lossy().to_string(),
+        ]);
+        Ok(())
+    }
+}
@@ -0,0 +1,114 @@
+mod common;
+
+use common::{install_jar, jar};
+use mvm::metadata::*;
+use mvm::server::server_types::ServerType;
+use anyhow::Result;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VERSION_1_21_4: &str = r#"{"id": "1.21.4", "name": "1.21.4", "world_version": 4189, "series_id": "main",
+        "protocol_version": 769, "pack_version": {"resource": 46, "data": 61}, "java_component": "java-runtime-delta",
+        "java_version": 21, "stable": true}"#;
+    const VERSION_1_16_5: &str = r#"{"id": "1.16.5", "name": "1.16.5", "world_version": 2586, "protocol_version": 754,
+        "pack_version": 6, "stable": true}"#;
+    const VERSION_1_21_10: &str = r#"{"id": "1.21.10", "name": "1.21.10", "world_version": 4556, "protocol_version": 773,
+        "pack_version": {"resource_major": 69, "resource_minor": 0, "data_major": 88, "data_minor": 0}, "java_version": 21}"#;
+
+    #[test]
+    fn test_read_jar_metadata() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let modern = dir.path().join("modern.jar");
+        std::fs::write(&modern, jar(&[("version.json", VERSION_1_21_4.as_bytes()), ("net/minecraft/Main.class", b"")])?)?;
+        let legacy = dir.path().join("legacy.jar");
+        std::fs::write(&legacy, jar(&[("version.json", VERSION_1_16_5.as_bytes())])?)?;
+        let minor = dir.path().join("minor.jar");
+        std::fs::write(&minor, jar(&[("version.json", VERSION_1_21_10.as_bytes())])?)?;
+        let old = dir.path().join("old.jar");
+        std::fs::write(&old, jar(&[("net/minecraft/server/MinecraftServer.class", b"")])?)?;
+
+        let metadata = read_jar_metadata(&modern)?.expect("Expected metadata in the jar");
+        assert_eq!(metadata.name, "1.21.4");
+        assert_eq!(metadata.world_version, 4189);
+        assert_eq!(metadata.protocol_version, 769);
+        assert_eq!(metadata.java_version, Some(21));
+        assert_eq!(metadata.pack_version.map(|pack| pack.to_string()).as_deref(), Some("resource 46, data 61"));
+
+        let metadata = read_jar_metadata(&legacy)?.expect("Expected metadata in the jar");
+        assert_eq!(metadata.java_version, None);
+        assert_eq!(metadata.pack_version.map(|pack| pack.to_string()).as_deref(), Some("6"));
+        let metadata = read_jar_metadata(&minor)?.expect("Expected metadata in the jar");
+        assert_eq!(metadata.pack_version.map(|pack| pack.to_string()).as_deref(), Some("resource 69.0, data 88.0"));
+
+        assert_eq!(read_jar_metadata(&old)?, None);
+        std::fs::write(&old, "not a jar")?;
+        assert!(read_jar_metadata(&old).is_err(), "Expected a file that is not a jar to be rejected");
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_paperclip_metadata() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        // Paperclip jars bundle the server jar that contains version.json.
+        let bundled = jar(&[("version.json", VERSION_1_21_4.as_bytes())])?;
+        let paperclip = dir.path().join("paper.jar");
+        std::fs::write(&paperclip, jar(&[("META-INF/versions.list", b""), ("META-INF/versions/1.21.4/paper-1.21.4.jar", &bundled)])?)?;
+
+        let metadata = read_jar_metadata(&paperclip)?.expect("Expected metadata in the bundled jar");
+        assert_eq!(metadata.world_version, 4189);
+        Ok(())
+    }
+
+    #[test]
+    fn test_version_metadata_is_stored() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.4", &jar(&[("version.json", VERSION_1_21_4.as_bytes())])?)?;
+        let version_dir = mvm_dir.path().join("vanilla/versions/1.21.4");
+
+        let metadata = version_metadata("1.21.4", &ServerType::Vanilla, mvm_dir.path())?;
+        assert_eq!(metadata.as_ref().map(|metadata| metadata.protocol_version), Some(769));
+        assert!(version_dir.join(METADATA_FILE).exists(), "Expected the metadata to be stored next to the jar");
+
+        // The stored metadata is used without opening the jar again.
+        std::fs::write(version_dir.join("server.jar"), "replaced")?;
+        assert_eq!(version_metadata("1.21.4", &ServerType::Vanilla, mvm_dir.path())?, metadata);
+        assert!(version_metadata("1.20", &ServerType::Vanilla, mvm_dir.path()).is_err(), "Expected a missing version to be an error");
+        Ok(())
+    }
+
+    #[test]
+    fn test_installed_versions() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.4", &jar(&[("version.json", VERSION_1_21_4.as_bytes())])?)?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.16.5", &jar(&[("version.json", VERSION_1_16_5.as_bytes())])?)?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "custom", b"not a jar")?;
+        std::fs::create_dir_all(mvm_dir.path().join("vanilla/versions/empty"))?;
+
+        let versions = installed_versions(&ServerType::Vanilla, mvm_dir.path())?;
+
+        let names = versions.iter().map(|(version, _)| version.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["custom", "1.16.5", "1.21.4"]);
+        assert!(versions[0].1.is_none());
+        assert!(installed_versions(&ServerType::Paper, mvm_dir.path())?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_installed_proxy_versions() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        for version in ["3.10.0-SNAPSHOT", "3.4.0-SNAPSHOT", "3.4.0"] {
+            let version_dir = mvm_dir.path().join("velocity/versions").join(version);
+            std::fs::create_dir_all(&version_dir)?;
+            std::fs::write(version_dir.join("server.jar"), jar(&[("velocity-plugin.json", b"{}")])?)?;
+        }
+
+        let versions = installed_versions(&ServerType::Velocity, mvm_dir.path())?;
+
+        let names = versions.iter().map(|(version, _)| version.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["3.4.0", "3.4.0-SNAPSHOT", "3.10.0-SNAPSHOT"], "Expected version numbers to be compared numerically");
+        Ok(())
+    }
+}
@@ -0,0 +1,144 @@
+mod common;
+
+use common::install_dummy_jar;
+use mvm::instance::read_instance_config;
+use mvm::network::*;
+use mvm::properties::read_properties;
+use mvm::server::server_types::ServerType;
+use anyhow::Result;
+use std::collections::BTreeSet;
+use tokio::fs;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_allocate_ports() -> Result<()> {
+        let used = BTreeSet::from([40100, 40102]);
+
+        let ports = allocate_ports(3, 40100, &used)?;
+
+        assert_eq!(ports.len(), 3);
+        assert!(ports.iter().all(|port| !used.contains(port)), "Expected used ports to be skipped: {:?}", ports);
+        assert!(ports.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(allocate_ports(2, u16::MAX, &BTreeSet::new()).is_err(), "Expected running out of ports to be an error");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_network() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Velocity, "3.4.0-SNAPSHOT").await?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Paper, "1.21.4").await?;
+        let existing_dir = mvm_dir.path().join("instances/creative");
+        fs::create_dir_all(&existing_dir).await?;
+        fs::write(existing_dir.join("server.properties"), "server-port=30066\n").await?;
+
+        let backends = vec!["lobby".to_string(), "survival".to_string()];
+        let network = create_network("hub", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", Some(25577), 30066, mvm_dir.path()).await?;
+
+        assert_eq!(network.proxy_dir, mvm_dir.path().join("instances/hub-proxy"));
+        assert_eq!(read_instance_config(&network.proxy_dir).await?.server_type, ServerType::Velocity);
+        let secret = fs::read_to_string(network.proxy_dir.join(SECRET_FILE)).await?;
+        assert!(secret.len() >= 32, "Expected a long secret, got {:?}", secret);
+
+        let velocity = toml::from_str::<toml::Table>(&fs::read_to_string(network.proxy_dir.join("velocity.toml")).await?)?;
+        assert_eq!(velocity["bind"].as_str(), Some("0.0.0.0:25577"));
+        assert_eq!(velocity["player-info-forwarding-mode"].as_str(), Some("modern"));
+        assert_eq!(velocity["servers"]["try"].as_array().map(|try_order| try_order.len()), Some(1));
+        assert_eq!(velocity["servers"]["try"][0].as_str(), Some("lobby"));
+
+        let mut ports = BTreeSet::new();
+        for backend in &network.backends {
+            assert_eq!(backend.instance_dir, mvm_dir.path().join(format!("instances/hub-{}", backend.name)));
+            assert_ne!(backend.port, 30066, "Expected the port of the existing instance to be skipped");
+            for port in [backend.port, backend.rcon_port, backend.query_port] {
+                assert!(ports.insert(port), "Expected every backend to get its own game, RCON and query port");
+            }
+            assert_eq!(velocity["servers"][&backend.name].as_str(), Some(format!("127.0.0.1:{}", backend.port).as_str()));
+
+            let properties = read_properties(&backend.instance_dir.join("server.properties")).await?;
+            assert_eq!(properties.get("online-mode"), Some("false"));
+            assert_eq!(properties.get("server-port"), Some(backend.port.to_string().as_str()));
+            assert_eq!(properties.get("rcon.port"), Some(backend.rcon_port.to_string().as_str()));
+            assert_eq!(properties.get("query.port"), Some(backend.query_port.to_string().as_str()));
+            let paper_global = fs::read_to_string(backend.instance_dir.join(PAPER_GLOBAL_FILE)).await?;
+            assert!(paper_global.contains(&format!("secret: '{}'", secret)), "Unexpected paper-global.yml: {}", paper_global);
+        }
+
+        assert!(used_ports(mvm_dir.path()).await.contains(&25577), "Expected the proxy port to count as used");
+        assert!(create_network("hub", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", Some(25578), 30066, mvm_dir.path()).await.is_err(),
+            "Expected an existing network to be refused");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_used_ports() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        for (name, properties) in [("plain", "server-port=25566\n"), ("remote", "server-port=25567\nenable-rcon=true\nrcon.port=25590\nenable-query=false\n")] {
+            let instance_dir = mvm_dir.path().join("instances").join(name);
+            fs::create_dir_all(&instance_dir).await?;
+            fs::write(instance_dir.join("server.properties"), properties).await?;
+        }
+
+        assert_eq!(used_ports(mvm_dir.path()).await, BTreeSet::from([25566, 25567, 25590]), "Expected disabled RCON and query ports not to count");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_networks_with_default_port() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Velocity, "3.4.0-SNAPSHOT").await?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Paper, "1.21.4").await?;
+        let backends = vec!["lobby".to_string()];
+
+        let hub = create_network("hub", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", None, 30066, mvm_dir.path()).await?;
+        let games = create_network("games", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", None, 30066, mvm_dir.path()).await?;
+
+        assert!(hub.proxy_port >= DEFAULT_PROXY_PORT && games.proxy_port > hub.proxy_port, "Unexpected proxy ports {} and {}", hub.proxy_port, games.proxy_port);
+        assert!(games.proxy_port < DEFAULT_BACKEND_PORT, "Expected the proxy port not to skip past the backends");
+        assert!(create_network("other", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", Some(hub.proxy_port), 30066, mvm_dir.path()).await.is_err(),
+            "Expected an explicitly given port of another proxy to be refused");
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_create_network_cleans_up_on_failure() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Velocity, "3.4.0-SNAPSHOT").await?;
+        install_dummy_jar(mvm_dir.path(), &ServerType::Paper, "1.21.4").await?;
+        let instances_dir = mvm_dir.path().join("instances");
+        fs::create_dir_all(&instances_dir).await?;
+        // A dangling link passes the check for existing instances, but the backend directory cannot
+        // be created through it, so the backend fails after the proxy was created.
+        fs::symlink(mvm_dir.path().join("missing"), instances_dir.join("hub-lobby")).await?;
+        let backends = vec!["lobby".to_string()];
+
+        assert!(create_network("hub", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", Some(25577), 30066, mvm_dir.path()).await.is_err());
+
+        assert!(!instances_dir.join("hub-proxy").exists(), "Expected the proxy to be removed again");
+        assert!(!instances_dir.join("hub-lobby").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_create_network_requires_forwarding_support() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let backends = vec!["lobby".to_string()];
+
+        let err = create_network("hub", "3.4.0-SNAPSHOT", &backends, &ServerType::Vanilla, "1.21.4", Some(25577), 30066, mvm_dir.path()).await
+            .expect_err("Expected vanilla backends to be refused");
+        assert!(err.to_string().contains("modern forwarding"), "Unexpected error: {}", err);
+        assert!(create_network("Hub", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", Some(25577), 30066, mvm_dir.path()).await.is_err());
+        for reserved in RESERVED_BACKEND_NAMES {
+            let backends = vec!["lobby".to_string(), reserved.to_string()];
+            assert!(create_network("hub", "3.4.0-SNAPSHOT", &backends, &ServerType::Paper, "1.21.4", Some(25577), 30066, mvm_dir.path()).await.is_err(),
+                "Expected the backend name {:?} to be refused", reserved);
+        }
+        assert!(!mvm_dir.path().join("instances").exists(), "Expected nothing to be created");
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+use mvm::properties::*;
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE: &str = "#Minecraft server properties\n#Thu Jan 01 00:00:00 UTC 2024\nmotd=A Minecraft Server\n\nserver-port=25565\nview-distance = 10\nlevel-seed=\n";
+
+    #[test]
+    fn test_parse_and_write_roundtrip() {
+        let properties = ServerProperties::parse(SAMPLE);
+
+        assert_eq!(properties.get("motd"), Some("A Minecraft Server"));
+        assert_eq!(properties.get("view-distance"), Some("10"));
+        assert_eq!(properties.get("level-seed"), Some(""));
+        assert_eq!(properties.get("missing"), None);
+        assert_eq!(properties.to_string(), SAMPLE);
+    }
+
+    #[test]
+    fn test_set_preserves_comments_and_order() {
+        let mut properties = ServerProperties::parse(SAMPLE);
+
+        properties.set("server-port", "25566");
+        properties.set("white-list", "true");
+
+        let expected = "#Minecraft server properties\n#Thu Jan 01 00:00:00 UTC 2024\nmotd=A Minecraft Server\n\nserver-port=25566\nview-distance = 10\nlevel-seed=\nwhite-list=true\n";
+        assert_eq!(properties.to_string(), expected);
+    }
+
+    #[test]
+    fn test_local_host() {
+        assert_eq!(ServerProperties::parse("server-ip=\n").local_host(), "127.0.0.1");
+        assert_eq!(ServerProperties::parse("server-ip=0.0.0.0\n").local_host(), "127.0.0.1");
+        assert_eq!(ServerProperties::parse("server-ip=::\n").local_host(), "127.0.0.1");
+        assert_eq!(ServerProperties::parse("motd=A Minecraft Server\n").local_host(), "127.0.0.1");
+        assert_eq!(ServerProperties::parse("server-ip=192.168.1.20\n").local_host(), "192.168.1.20");
+    }
+
+    #[test]
+    fn test_escapes() {
+        let properties = ServerProperties::parse("motd=\\u00A7aGreen\\: server\nkey\\=with\\=equals:value\nspaced   value here\n");
+
+        assert_eq!(properties.get("motd"), Some("\u{a7}aGreen: server"));
+        assert_eq!(properties.get("key=with=equals"), Some("value"));
+        assert_eq!(properties.get("spaced"), Some("value here"));
+
+        let mut properties = ServerProperties::default();
+        properties.set("motd", "\u{a7}aHello: world");
+        assert_eq!(properties.to_string(), "motd=\\u00A7aHello\\: world\n");
+        assert_eq!(ServerProperties::parse(&properties.to_string()).get("motd"), Some("\u{a7}aHello: world"));
+    }
+
+    #[test]
+    fn test_line_continuation() {
+        let properties = ServerProperties::parse("motd=first \\\n    second\nserver-port=25565\n");
+
+        assert_eq!(properties.get("motd"), Some("first second"));
+        assert_eq!(properties.get("server-port"), Some("25565"));
+    }
+
+    #[test]
+    fn test_validate() -> Result<()> {
+        validate("server-port", "25565")?;
+        validate("pvp", "false")?;
+        validate("difficulty", "hard")?;
+        validate("some-plugin-key", "anything")?;
+
+        assert!(validate("server-port", "70000").is_err());
+        assert!(validate("server-port", "abc").is_err());
+        assert!(validate("pvp", "yes").is_err());
+        assert!(validate("difficulty", "nightmare").is_err());
+        assert!(validate("view-distance", "1").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_defaults_per_version() {
+        assert_eq!(default_value("difficulty", Some("1.12.2")), Some("1"));
+        assert_eq!(default_value("difficulty", Some("1.21")), Some("easy"));
+        assert_eq!(default_value("simulation-distance", Some("1.17.1")), None);
+        assert_eq!(default_value("simulation-distance", Some("1.18")), Some("10"));
+        assert_eq!(default_value("level-type", None), Some("minecraft:normal"));
+
+        let defaults = default_properties(Some("1.16.5"));
+        assert_eq!(defaults.get("motd").map(String::as_str), Some("A Minecraft Server"));
+        assert!(!defaults.contains_key("enforce-secure-profile"));
+    }
+
+    #[test]
+    fn test_diff_properties() {
+        let left: BTreeMap<String, String> = [("motd", "Left"), ("pvp", "true"), ("only-left", "1")]
+            .into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+        let right: BTreeMap<String, String> = [("motd", "Right"), ("pvp", "true")]
+            .into_iter().map(|(k, v)| (k.to_string(), v.to_string())).collect();
+
+        let diff = diff_properties(&left, &right);
+
+        assert_eq!(diff, vec![
+            PropertyDiff { key: "motd".to_string(), left: Some("Left".to_string()), right: Some("Right".to_string()) },
+            PropertyDiff { key: "only-left".to_string(), left: Some("1".to_string()), right: None },
+        ]);
+    }
+}
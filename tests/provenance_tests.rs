@@ -0,0 +1,79 @@
+mod common;
+
+use common::install_jar;
+use mvm::provenance::*;
+use mvm::server::server_types::ServerType;
+use anyhow::Result;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PAPER_URL: &str = "https://api.papermc.io/v2/projects/paper/versions/1.21.4/builds/232/downloads/paper-1.21.4-232.jar";
+
+    #[test]
+    fn test_hash_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join("abc"), "abc")?;
+
+        let hashes = hash_file(&dir.path().join("abc"))?;
+
+        assert_eq!(hashes.sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(hashes.sha256, "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(hashes.md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(hashes.size, 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_paper_build() {
+        assert_eq!(paper_build(PAPER_URL), Some(232));
+        assert_eq!(paper_build("https://piston-data.mojang.com/v1/objects/4707d00e/server.jar"), None);
+    }
+
+    #[test]
+    fn test_purpur_build() {
+        assert_eq!(purpur_build("https://api.purpurmc.org/v2/purpur/1.21.4/2416/download"), Some(2416));
+        assert_eq!(purpur_build(PAPER_URL), None);
+    }
+
+    #[test]
+    fn test_record_install() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let jar_path = install_jar(mvm_dir.path(), &ServerType::Paper, "1.21.4", b"paper jar")?;
+
+        let recorded = record_install(&jar_path, PAPER_URL, &ServerType::Paper, "1.21.4", paper_build(PAPER_URL))?;
+        let info = read_install_info("1.21.4", &ServerType::Paper, mvm_dir.path())?
+            .expect("Expected the install to be recorded");
+
+        assert_eq!(info, recorded);
+        assert_eq!(info.source, PAPER_URL);
+        assert_eq!(info.build, Some(232));
+        assert_eq!(info.size, 9);
+        assert_eq!(info.sha256, hash_file(&jar_path)?.sha256);
+        assert_eq!(info.mvm_version, env!("CARGO_PKG_VERSION"));
+        assert!(jar_path.with_file_name(INSTALL_FILE).exists());
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_integrity() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let jar_path = install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.4", b"vanilla jar")?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.20.4", b"copied by hand")?;
+        record_install(&jar_path, "https://piston-data.mojang.com/v1/objects/4707d00e/server.jar", &ServerType::Vanilla, "1.21.4", None)?;
+
+        assert_eq!(check_integrity("1.21.4", &ServerType::Vanilla, mvm_dir.path(), false)?, Integrity::Intact);
+        assert_eq!(check_integrity("1.20.4", &ServerType::Vanilla, mvm_dir.path(), false)?, Integrity::UnknownOrigin);
+        assert_eq!(check_integrity("1.19", &ServerType::Vanilla, mvm_dir.path(), false)?, Integrity::Missing);
+
+        // A change that keeps the size is only noticed by hashing.
+        std::fs::write(&jar_path, "vanilla jaR")?;
+        assert_eq!(check_integrity("1.21.4", &ServerType::Vanilla, mvm_dir.path(), true)?, Integrity::Intact);
+        assert_eq!(check_integrity("1.21.4", &ServerType::Vanilla, mvm_dir.path(), false)?, Integrity::Modified);
+        std::fs::write(&jar_path, "a longer vanilla jar")?;
+        assert_eq!(check_integrity("1.21.4", &ServerType::Vanilla, mvm_dir.path(), true)?, Integrity::Modified);
+        Ok(())
+    }
+}
@@ -0,0 +1,110 @@
+use mvm::rcon::*;
+use anyhow::Result;
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpListener;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Starts a fake RCON server that accepts `secret`, answers `list` with a response split over
+    /// two packets and replies to unknown packet types like Minecraft does.
+    async fn start_mock_server() -> Result<String> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let address = listener.local_addr()?.to_string();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            loop {
+                let Ok(packet) = RconPacket::read_from(&mut stream).await else {
+                    return;
+                };
+                let replies = match packet.packet_type {
+                    SERVERDATA_AUTH if packet.body == "secret" => vec![(packet.id, SERVERDATA_AUTH_RESPONSE, String::new())],
+                    SERVERDATA_AUTH => vec![(-1, SERVERDATA_AUTH_RESPONSE, String::new())],
+                    SERVERDATA_EXECCOMMAND if packet.body == "list" => vec![
+                        (packet.id, SERVERDATA_RESPONSE_VALUE, "There are 2 of a max of 20 players online: ".to_string()),
+                        (packet.id, SERVERDATA_RESPONSE_VALUE, "Alex, Steve".to_string()),
+                    ],
+                    SERVERDATA_EXECCOMMAND => vec![(packet.id, SERVERDATA_RESPONSE_VALUE, format!("Unknown command: {}", packet.body))],
+                    other => vec![(packet.id, SERVERDATA_RESPONSE_VALUE, format!("Unknown request {:x}", other))],
+                };
+                for (id, packet_type, body) in replies {
+                    let reply = RconPacket { id, packet_type, body };
+                    stream.write_all(&reply.encode()).await.unwrap();
+                }
+            }
+        });
+
+        Ok(address)
+    }
+
+    #[test]
+    fn test_packet_encode() {
+        let packet = RconPacket { id: 7, packet_type: SERVERDATA_EXECCOMMAND, body: "list".to_string() };
+
+        let bytes = packet.encode();
+
+        assert_eq!(bytes, vec![14, 0, 0, 0, 7, 0, 0, 0, 2, 0, 0, 0, b'l', b'i', b's', b't', 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_packet_roundtrip() -> Result<()> {
+        let packet = RconPacket { id: 42, packet_type: SERVERDATA_RESPONSE_VALUE, body: "Saved the game".to_string() };
+        let bytes = packet.encode();
+
+        let decoded = RconPacket::read_from(&mut bytes.as_slice()).await?;
+
+        assert_eq!(decoded, packet);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_command_multi_packet_response() -> Result<()> {
+        let address = start_mock_server().await?;
+
+        let mut client = RconClient::connect(&address, "secret").await?;
+        let response = client.command("list").await?;
+
+        assert_eq!(response, "There are 2 of a max of 20 players online: Alex, Steve");
+
+        let response = client.command("foo").await?;
+        assert_eq!(response, "Unknown command: foo");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_wrong_password() -> Result<()> {
+        let address = start_mock_server().await?;
+
+        let result = RconClient::connect(&address, "wrong").await;
+
+        assert!(result.is_err(), "Expected an authentication error");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_rcon_settings() -> Result<()> {
+        let instance_dir = tempfile::tempdir()?;
+        let properties_path = instance_dir.path().join("server.properties");
+
+        tokio::fs::write(&properties_path, "enable-rcon=false\nrcon.password=secret\n").await?;
+        assert!(read_rcon_settings(instance_dir.path()).await.is_err(), "Expected an error with RCON disabled");
+
+        tokio::fs::write(&properties_path, "enable-rcon=true\nrcon.port=25580\nrcon.password=secret\nserver-ip=\n").await?;
+        let settings = read_rcon_settings(instance_dir.path()).await?;
+
+        assert_eq!(settings, RconSettings { address: "127.0.0.1:25580".to_string(), password: "secret".to_string() });
+
+        tokio::fs::write(&properties_path, "enable-rcon=true\nrcon.password=secret\nserver-ip=0.0.0.0\n").await?;
+        assert_eq!(read_rcon_settings(instance_dir.path()).await?.address, "127.0.0.1:25575");
+
+        tokio::fs::write(&properties_path, "enable-rcon=true\nrcon.password=secret\nserver-ip=::\n").await?;
+        assert_eq!(read_rcon_settings(instance_dir.path()).await?.address, "127.0.0.1:25575");
+
+        tokio::fs::write(&properties_path, "enable-rcon=true\nrcon.password=secret\nserver-ip=::1\n").await?;
+        assert_eq!(read_rcon_settings(instance_dir.path()).await?.address, "[::1]:25575");
+        Ok(())
+    }
+}
@@ -0,0 +1,141 @@
+use mvm::instance::*;
+use mvm::schedule::*;
+use anyhow::Result;
+use chrono::{NaiveDate, NaiveDateTime, TimeZone, Utc};
+use std::path::PathBuf;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(year, month, day).unwrap().and_hms_opt(hour, minute, 0).unwrap()
+    }
+
+    #[test]
+    fn test_parse_cron() -> Result<()> {
+        let schedule = CronSchedule::parse("*/15 9-17 * * mon-fri")?;
+
+        // 2024-06-03 is a Monday.
+        assert!(schedule.matches(&time(2024, 6, 3, 9, 45)));
+        assert!(!schedule.matches(&time(2024, 6, 3, 9, 50)));
+        assert!(!schedule.matches(&time(2024, 6, 3, 18, 0)));
+        assert!(!schedule.matches(&time(2024, 6, 2, 9, 0)), "Sunday should not match");
+        assert_eq!(schedule.to_string(), "*/15 9-17 * * mon-fri");
+
+        let schedule = CronSchedule::parse("@daily")?;
+        assert!(schedule.matches(&time(2024, 6, 3, 0, 0)));
+        assert!(!schedule.matches(&time(2024, 6, 3, 0, 1)));
+
+        let schedule = CronSchedule::parse("0 0 * * 7")?;
+        assert!(schedule.matches(&time(2024, 6, 2, 0, 0)), "7 should stand for Sunday");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_invalid_cron() {
+        for expression in ["", "* * * *", "60 * * * *", "* * 0 * *", "*/0 * * * *", "5-1 * * * *", "* * * foo *"] {
+            assert!(CronSchedule::parse(expression).is_err(), "Expected {:?} to be rejected", expression);
+        }
+    }
+
+    #[test]
+    fn test_day_of_month_or_weekday() -> Result<()> {
+        // Runs on the 1st and on every Friday, like Vixie cron.
+        let schedule = CronSchedule::parse("0 12 1 * fri")?;
+
+        assert!(schedule.matches(&time(2024, 6, 1, 12, 0)));
+        assert!(schedule.matches(&time(2024, 6, 7, 12, 0)));
+        assert!(!schedule.matches(&time(2024, 6, 8, 12, 0)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_next_after() -> Result<()> {
+        let schedule = CronSchedule::parse("30 4 * * *")?;
+
+        let next = schedule.next_after(&Utc.with_ymd_and_hms(2024, 12, 31, 5, 0, 0).unwrap());
+        assert_eq!(next, Some(Utc.with_ymd_and_hms(2025, 1, 1, 4, 30, 0).unwrap()));
+
+        let next = schedule.next_after(&Utc.with_ymd_and_hms(2025, 1, 1, 4, 30, 0).unwrap());
+        assert_eq!(next, Some(Utc.with_ymd_and_hms(2025, 1, 2, 4, 30, 0).unwrap()), "The given time itself is excluded");
+
+        let schedule = CronSchedule::parse("0 0 29 2 *")?;
+        let next = schedule.next_after(&Utc.with_ymd_and_hms(2025, 3, 1, 0, 0, 0).unwrap());
+        assert_eq!(next, Some(Utc.with_ymd_and_hms(2028, 2, 29, 0, 0, 0).unwrap()));
+
+        let schedule = CronSchedule::parse("0 0 31 2 *")?;
+        assert_eq!(schedule.next_after(&Utc.with_ymd_and_hms(2025, 1, 1, 0, 0, 0).unwrap()), None);
+        Ok(())
+    }
+
+    #[test]
+    fn test_schedule_config() -> Result<()> {
+        let config = toml::from_str::<InstanceConfig>(r#"
+            server_type = "paper"
+            version = "1.21.1"
+
+            [[schedule]]
+            name = "nightly-restart"
+            cron = "0 4 * * *"
+            action = "restart"
+            countdown = 300
+
+            [[schedule]]
+            cron = "0 * * * *"
+            action = "backup"
+
+            [[schedule]]
+            cron = "*/30 * * * *"
+            action = "command"
+            command = "say Remember to vote!"
+        "#)?;
+
+        assert_eq!(config.schedule.len(), 3);
+        assert_eq!(config.schedule[0].label(), "nightly-restart");
+        assert!(matches!(&config.schedule[0].action, TaskAction::Restart { countdown: 300, .. }));
+        assert_eq!(config.schedule[1].action, TaskAction::Backup);
+        assert_eq!(config.schedule[2].label(), "command: say Remember to vote!");
+
+        let written = toml::to_string_pretty(&config)?;
+        let reread = toml::from_str::<InstanceConfig>(&written)?;
+        assert_eq!(reread.schedule, config.schedule);
+
+        let invalid = toml::from_str::<InstanceConfig>(r#"
+            server_type = "paper"
+            version = "1.21.1"
+
+            [[schedule]]
+            cron = "0 25 * * *"
+            action = "backup"
+        "#);
+        assert!(invalid.is_err(), "Expected an invalid cron expression to be rejected");
+        Ok(())
+    }
+
+    #[test]
+    fn test_upcoming_runs() -> Result<()> {
+        let config = toml::from_str::<InstanceConfig>(r#"
+            server_type = "vanilla"
+            version = "1.21"
+
+            [[schedule]]
+            cron = "0 * * * *"
+            action = "backup"
+
+            [[schedule]]
+            cron = "30 0 * * *"
+            action = "update-check"
+        "#)?;
+        let instances = vec![(PathBuf::from("survival"), config)];
+
+        let runs = upcoming_runs(&instances, &Utc.with_ymd_and_hms(2024, 6, 1, 0, 0, 0).unwrap(), 2);
+
+        let runs: Vec<String> = runs.iter()
+            .map(|run| format!("{} {}", run.time.format("%m-%d %H:%M"), run.task.label()))
+            .collect();
+        assert_eq!(runs, vec!["06-01 00:30 update-check", "06-01 01:00 backup", "06-01 02:00 backup", "06-02 00:30 update-check"]);
+        Ok(())
+    }
+}
@@ -0,0 +1,151 @@
+use mvm::status::*;
+use anyhow::Result;
+use serde_json::json;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(packet_id: i32, payload: &[u8]) -> Vec<u8> {
+        let mut body = Vec::new();
+        write_varint(&mut body, packet_id);
+        body.extend_from_slice(payload);
+        let mut packet = Vec::new();
+        write_varint(&mut packet, body.len() as i32);
+        packet.extend_from_slice(&body);
+        packet
+    }
+
+    /// Starts a fake server answering the modern status handshake and ping.
+    async fn start_modern_server() -> Result<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            for _ in 0..2 {
+                let length = read_varint(&mut stream).await.unwrap();
+                let mut body = vec![0u8; length as usize];
+                stream.read_exact(&mut body).await.unwrap();
+            }
+
+            let status = json!({
+                "version": {"name": "Paper 1.21", "protocol": 767},
+                "players": {"max": 20, "online": 3},
+                "description": {"text": "Welcome ", "extra": [{"text": "home"}]}
+            }).to_string();
+            let mut payload = Vec::new();
+            write_varint(&mut payload, status.len() as i32);
+            payload.extend_from_slice(status.as_bytes());
+            stream.write_all(&frame(0x00, &payload)).await.unwrap();
+
+            let length = read_varint(&mut stream).await.unwrap();
+            let mut ping = vec![0u8; length as usize];
+            stream.read_exact(&mut ping).await.unwrap();
+            stream.write_all(&frame(0x01, &ping[1..])).await.unwrap();
+        });
+
+        Ok(port)
+    }
+
+    /// Starts a fake 1.6 server that only understands the legacy ping.
+    async fn start_legacy_server() -> Result<u16> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let port = listener.local_addr()?.port();
+
+        tokio::spawn(async move {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                let mut first = [0u8; 1];
+                stream.read_exact(&mut first).await.unwrap();
+                if first[0] != 0xfe {
+                    continue;
+                }
+
+                let message = "\u{a7}1\u{0}78\u{0}1.6.4\u{0}Old server\u{0}1\u{0}10";
+                let units: Vec<u16> = message.encode_utf16().collect();
+                let mut response = vec![0xff];
+                response.extend_from_slice(&(units.len() as u16).to_be_bytes());
+                units.iter().for_each(|unit| response.extend_from_slice(&unit.to_be_bytes()));
+                stream.write_all(&response).await.unwrap();
+            }
+        });
+
+        Ok(port)
+    }
+
+    #[tokio::test]
+    async fn test_varint_roundtrip() -> Result<()> {
+        for value in [0, 1, 127, 128, 255, 25565, 2097151, i32::MAX, -1] {
+            let mut buffer = Vec::new();
+            write_varint(&mut buffer, value);
+            assert_eq!(read_varint(&mut buffer.as_slice()).await?, value);
+        }
+
+        let mut buffer = Vec::new();
+        write_varint(&mut buffer, 300);
+        assert_eq!(buffer, vec![0xac, 0x02]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_status_modern() -> Result<()> {
+        let port = start_modern_server().await?;
+
+        let status = query_status("127.0.0.1", port).await?;
+
+        assert_eq!(status.motd, "Welcome home");
+        assert_eq!(status.protocol, 767);
+        assert_eq!(status.version_name, "Paper 1.21");
+        assert_eq!(status.players_online, 3);
+        assert_eq!(status.players_max, 20);
+        assert!(!status.legacy);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_status_legacy_fallback() -> Result<()> {
+        let port = start_legacy_server().await?;
+
+        let status = query_status("127.0.0.1", port).await?;
+
+        assert_eq!(status.motd, "Old server");
+        assert_eq!(status.protocol, 78);
+        assert_eq!(status.version_name, "1.6.4");
+        assert_eq!(status.players_online, 1);
+        assert_eq!(status.players_max, 10);
+        assert!(status.legacy);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_legacy_response_beta() -> Result<()> {
+        let status = parse_legacy_response("A \u{a7}cBeta server\u{a7}4\u{a7}16")?;
+
+        assert_eq!(status.motd, "A \u{a7}cBeta server");
+        assert_eq!(status.players_online, 4);
+        assert_eq!(status.players_max, 16);
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_address() -> Result<()> {
+        assert_eq!(parse_address("mc.example.com")?, ("mc.example.com".to_string(), DEFAULT_PORT));
+        assert_eq!(parse_address("mc.example.com:25570")?, ("mc.example.com".to_string(), 25570));
+        assert_eq!(parse_address("[::1]:25570")?, ("::1".to_string(), 25570));
+        assert_eq!(parse_address("[::1]")?, ("::1".to_string(), DEFAULT_PORT));
+        assert_eq!(parse_address("::1")?, ("::1".to_string(), DEFAULT_PORT));
+        assert!(parse_address("[::1").is_err());
+        assert!(parse_address("mc.example.com:port").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_chat_to_plain_text() {
+        assert_eq!(chat_to_plain_text(&json!("Plain")), "Plain");
+        assert_eq!(chat_to_plain_text(&json!({"text": "A", "extra": ["B", {"text": "C"}]})), "ABC");
+    }
+}
@@ -0,0 +1,59 @@
+use mvm::process::*;
+use mvm::stop::*;
+use anyhow::Result;
+use std::time::Duration;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_countdown_marks() {
+        assert_eq!(countdown_marks(0), Vec::<u64>::new());
+        assert_eq!(countdown_marks(5), vec![5, 4, 3, 2, 1]);
+        assert_eq!(countdown_marks(45), vec![45, 30, 10, 5, 4, 3, 2, 1]);
+        assert_eq!(countdown_marks(60), vec![60, 30, 10, 5, 4, 3, 2, 1]);
+    }
+
+    #[tokio::test]
+    async fn test_stop_without_console_terminates_process() -> Result<()> {
+        let instance_dir = tempfile::tempdir()?;
+        let mut child = tokio::process::Command::new("sleep").arg("60").spawn()?;
+        let pid = child.id().unwrap();
+        write_pid_file(instance_dir.path(), pid).await?;
+        let waiter = tokio::spawn(async move { child.wait().await });
+
+        assert_eq!(find_server_pid(instance_dir.path()).await, Some(pid));
+
+        let options = StopOptions { countdown: 0, message: String::new(), timeout: Duration::from_secs(5) };
+        stop_instance(instance_dir.path(), &options).await?;
+
+        let status = waiter.await??;
+        assert!(!status.success(), "Expected the process to be terminated by a signal");
+        assert!(!is_running(pid));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stop_not_running() -> Result<()> {
+        let instance_dir = tempfile::tempdir()?;
+
+        let options = StopOptions { countdown: 0, message: String::new(), timeout: Duration::from_secs(1) };
+        let result = stop_instance(instance_dir.path(), &options).await;
+
+        assert!(result.is_err(), "Expected an error for an instance that is not running");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_console_pipe_without_reader() -> Result<()> {
+        let instance_dir = tempfile::tempdir()?;
+        create_console_pipe(instance_dir.path())?;
+
+        let result = send_to_console_pipe(instance_dir.path(), "stop");
+
+        assert!(result.is_err(), "Expected an error when no server reads the pipe");
+        Ok(())
+    }
+}
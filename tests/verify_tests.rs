@@ -0,0 +1,56 @@
+mod common;
+
+use common::install_jar;
+use mvm::provenance::{record_install, Integrity};
+use mvm::server::server_types::ServerType;
+use mvm::verify::*;
+use anyhow::Result;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_installed_server_types() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_jar(mvm_dir.path(), &ServerType::Paper, "1.21.4", b"paper")?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.4", b"vanilla")?;
+        std::fs::create_dir_all(mvm_dir.path().join("instances/survival"))?;
+        std::fs::create_dir_all(mvm_dir.path().join("backups/chunks"))?;
+
+        assert_eq!(installed_server_types(mvm_dir.path()).await, vec![ServerType::Paper, ServerType::Vanilla]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_jars_offline() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let intact = install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.4", b"vanilla jar")?;
+        record_install(&intact, "https://example.com/1.21.4/server.jar", &ServerType::Vanilla, "1.21.4", None)?;
+        let modified = install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.3", b"vanilla jar")?;
+        record_install(&modified, "https://example.com/1.21.3/server.jar", &ServerType::Vanilla, "1.21.3", None)?;
+        std::fs::write(&modified, "tampered jar")?;
+        let missing = install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.2", b"vanilla jar")?;
+        record_install(&missing, "https://example.com/1.21.2/server.jar", &ServerType::Vanilla, "1.21.2", None)?;
+        std::fs::remove_file(&missing)?;
+        install_jar(mvm_dir.path(), &ServerType::Paper, "1.21.4", b"copied by hand")?;
+
+        let reports = verify_jars(mvm_dir.path(), false).await?;
+
+        let outcomes = reports.iter()
+            .map(|report| (report.server_type.clone(), report.version.as_str(), report.integrity.clone()))
+            .collect::<Vec<_>>();
+        assert_eq!(outcomes, vec![
+            (ServerType::Paper, "1.21.4", Integrity::UnknownOrigin),
+            (ServerType::Vanilla, "1.21.2", Integrity::Missing),
+            (ServerType::Vanilla, "1.21.3", Integrity::Modified),
+            (ServerType::Vanilla, "1.21.4", Integrity::Intact),
+        ]);
+        assert_eq!(reports[2].details, vec!["differs from install.toml"]);
+        assert_eq!(reports[3].details, vec!["matches install.toml"]);
+        let failures = reports.iter().filter(|report| report.is_failure()).count();
+        assert_eq!(failures, 2, "Expected only modified and missing jars to fail");
+        Ok(())
+    }
+}
@@ -5,7 +5,6 @@ use anyhow::{Context, Result};
 use std::path::PathBuf;
 use tokio::fs::{self, File};
 use tokio::io::AsyncWriteExt;
-use toml;
 
 
 #[cfg(test)]
@@ -161,7 +160,7 @@ mod tests {
         env::set_var("MVM_HOME", &test_home_dir);
 
         let version = "1.20.2";
-        let download_url = get_version_download(&version, &ServerType::Vanilla).await?;
+        let download_url = get_version_download(version, &ServerType::Vanilla).await?;
 
         let result = download_server_jar(download_url, version, &ServerType::Vanilla, &get_dir().await?).await;
 
@@ -190,7 +189,7 @@ mod tests {
         env::set_var("MVM_HOME", &test_home_dir);
 
         let version = "1.20.2";
-        let download_url = get_version_download(&version, &ServerType::Paper).await?;
+        let download_url = get_version_download(version, &ServerType::Paper).await?;
 
         let result = download_server_jar(download_url, version, &ServerType::Paper, &get_dir().await?).await;
 
@@ -304,6 +303,64 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_download_server_jar_needs_resolved_version() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+
+        let result = download_server_jar("http://127.0.0.1:1/server.jar".to_string(), "latest", &ServerType::Vanilla, mvm_dir.path()).await;
+
+        assert!(result.is_err(), "Expected an unresolved version to be refused");
+        assert!(!mvm_dir.path().join("vanilla/versions/latest").exists());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_use_version_keeps_other_settings() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let version_dir = mvm_dir.path().join("paper/versions/1.21.1");
+        fs::create_dir_all(&version_dir).await?;
+        fs::write(version_dir.join("server.jar"), "dummy content").await?;
+        fs::write(mvm_dir.path().join("config.toml"), "vanilla = \"1.21\"\n\n[backup.storage]\ntype = \"s3\"\nbucket = \"mvm-backups\"\n").await?;
+
+        use_version("1.21.1", &ServerType::Paper, mvm_dir.path()).await?;
+
+        let contents = fs::read_to_string(mvm_dir.path().join("config.toml")).await?;
+        let config = contents.parse::<Value>()?;
+        assert_eq!(config["paper"].as_str(), Some("1.21.1"));
+        assert_eq!(config["vanilla"].as_str(), Some("1.21"));
+        assert_eq!(config["backup"]["storage"]["bucket"].as_str(), Some("mvm-backups"), "Other settings must be kept");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_install_custom_from_file() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let source = mvm_dir.path().join("mypatch.jar");
+        fs::write(&source, "patched jar").await?;
+
+        let server_jar = install_from(source.to_str().unwrap(), "mypatch-1.21", &ServerType::Custom, mvm_dir.path()).await?;
+
+        assert_eq!(server_jar, mvm_dir.path().join("custom/versions/mypatch-1.21/server.jar"));
+        assert_eq!(fs::read_to_string(&server_jar).await?, "patched jar");
+        let install = fs::read_to_string(mvm_dir.path().join("custom/versions/mypatch-1.21/install.toml")).await?
+            .parse::<Value>()?;
+        assert!(install["source"].as_str().is_some_and(|source| source.starts_with("file://")));
+        assert!(install_from(source.to_str().unwrap(), "mypatch-1.21", &ServerType::Custom, mvm_dir.path()).await.is_err(),
+            "Expected an installed version not to be replaced");
+        assert!(install_from(source.to_str().unwrap(), "../escape", &ServerType::Custom, mvm_dir.path()).await.is_err(),
+            "Expected names with path separators to be rejected");
+
+        use_version("mypatch-1.21", &ServerType::Custom, mvm_dir.path()).await?;
+        assert_eq!(resolve_version("recent", &ServerType::Custom, mvm_dir.path()).await?, "mypatch-1.21");
+        assert!(get_version_download("mypatch-1.21", &ServerType::Custom).await.is_err(), "Custom versions have no upstream");
+
+        delete_server_jar("mypatch-1.21", &ServerType::Custom, mvm_dir.path()).await?;
+        assert!(!server_jar.exists());
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_use_vanilla_version() -> Result<()> {
         let test_home_dir = PathBuf::from("./tests/test_data/.mvm");
@@ -0,0 +1,101 @@
+mod common;
+
+use common::{install_jar, jar, write_level_dat};
+use mvm::instance::*;
+use mvm::server::server_types::ServerType;
+use mvm::world::*;
+use anyhow::Result;
+use tokio::fs;
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version_json(name: &str, world_version: i32) -> Vec<u8> {
+        format!("{{\"id\": \"{0}\", \"name\": \"{0}\", \"world_version\": {1}, \"protocol_version\": 767}}", name, world_version).into_bytes()
+    }
+
+    #[test]
+    fn test_read_level_info() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        write_level_dat(dir.path(), "1.21.4", 4189, &["vanilla", "Paper"])?;
+
+        let level = read_level_info(dir.path())?;
+
+        assert_eq!(level.version_name.as_deref(), Some("1.21.4"));
+        assert_eq!(level.data_version, Some(4189));
+        assert_eq!(level.last_brand.as_deref(), Some("Paper"));
+        assert_eq!(level.describe(), "1.21.4 (data version 4189, Paper)");
+        assert!(read_level_info(&dir.path().join("missing")).is_err(), "Expected a missing level.dat to be an error");
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_downgrade() {
+        let level = LevelInfo { version_name: Some("1.21.4".to_string()), data_version: Some(4189), last_brand: None };
+        let unknown = LevelInfo { version_name: Some("24w14a".to_string()), data_version: None, last_brand: None };
+
+        assert_eq!(is_downgrade(&level, "1.21", Some(3953)), Some(true));
+        assert_eq!(is_downgrade(&level, "1.21.4", Some(4189)), Some(false));
+        assert_eq!(is_downgrade(&level, "1.21.5", Some(4325)), Some(false));
+        assert_eq!(is_downgrade(&level, "1.12.2", None), Some(true), "Expected release names to be compared without a data version");
+        assert_eq!(is_downgrade(&unknown, "1.12.2", None), None, "Expected versions that cannot be ordered to be unknown");
+        assert_eq!(is_downgrade(&level, "1.21.4-54.0.16", None), None, "Expected loader versions to be unknown");
+    }
+
+    #[tokio::test]
+    async fn test_check_downgrade_without_release_name() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        let instance_dir = mvm_dir.path().join("instances/modded");
+        write_level_dat(&instance_dir.join("world"), "1.21.4", 4189, &["forge"])?;
+
+        let version_dir = mvm_dir.path().join("forge/versions/1.21.4-54.0.16");
+        fs::create_dir_all(&version_dir).await?;
+        fs::write(version_dir.join("installer.jar"), "installer").await?;
+        fs::write(version_dir.join("launch.toml"), "game_version = \"1.21.4\"\nargs = []\n").await?;
+        check_downgrade(&instance_dir, "1.21.4-54.0.16", &ServerType::Forge, mvm_dir.path()).await?;
+
+        let older_dir = mvm_dir.path().join("forge/versions/1.21.1-52.0.40");
+        fs::create_dir_all(&older_dir).await?;
+        fs::write(older_dir.join("installer.jar"), "installer").await?;
+        fs::write(older_dir.join("launch.toml"), "game_version = \"1.21.1\"\nargs = []\n").await?;
+        let err = check_downgrade(&instance_dir, "1.21.1-52.0.40", &ServerType::Forge, mvm_dir.path()).await
+            .expect_err("Expected the game version of the loader to be compared");
+        assert!(format!("{:#}", err).contains("newer than 1.21.1-52.0.40 (1.21.1)"), "Unexpected error: {:#}", err);
+
+        install_jar(mvm_dir.path(), &ServerType::Custom, "mypatch", b"not a jar")?;
+        let err = check_downgrade(&instance_dir, "mypatch", &ServerType::Custom, mvm_dir.path()).await
+            .expect_err("Expected versions that cannot be compared to be refused");
+        assert!(format!("{:#}", err).contains("Cannot tell whether mypatch") && format!("{:#}", err).contains("--force"),
+            "Unexpected error: {:#}", err);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_refuse_downgrade() -> Result<()> {
+        let mvm_dir = tempfile::tempdir()?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21", &jar(&[("version.json", &version_json("1.21", 3953))])?)?;
+        install_jar(mvm_dir.path(), &ServerType::Vanilla, "1.21.4", &jar(&[("version.json", &version_json("1.21.4", 4189))])?)?;
+        let instance_dir = mvm_dir.path().join("instances/survival");
+        fs::create_dir_all(&instance_dir).await?;
+        fs::write(instance_dir.join("instance.toml"), "server_type = \"vanilla\"\nversion = \"1.21.4\"\n").await?;
+        write_level_dat(&instance_dir.join("world"), "1.21.4", 4189, &["vanilla"])?;
+
+        let err = set_instance_version(&instance_dir, "1.21", mvm_dir.path(), false).await
+            .expect_err("Expected switching to an older version to be refused");
+        assert!(format!("{:#}", err).contains("--force"), "Unexpected error: {:#}", err);
+        assert_eq!(read_instance_config(&instance_dir).await?.version, "1.21.4");
+
+        accept_eula(&instance_dir).await?;
+        fs::write(instance_dir.join("instance.toml"), "server_type = \"vanilla\"\nversion = \"1.21\"\n").await?;
+        let err = run_instance(&instance_dir, mvm_dir.path(), false).await
+            .expect_err("Expected starting an older version to be refused");
+        assert!(format!("{:#}", err).contains("newer than 1.21 (data version 3953)"), "Unexpected error: {:#}", err);
+
+        fs::write(instance_dir.join("instance.toml"), "server_type = \"vanilla\"\nversion = \"1.21.4\"\n").await?;
+        set_instance_version(&instance_dir, "1.21", mvm_dir.path(), true).await?;
+        assert_eq!(read_instance_config(&instance_dir).await?.version, "1.21");
+        Ok(())
+    }
+}